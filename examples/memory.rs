@@ -0,0 +1,60 @@
+//! times the fibonacci loop of `main.rs` on both memories, with `Interpreter::execute` and
+//! with the decoded `Engine`, without the `print` so the terminal does not dominate the numbers
+//!
+//! `cargo run --release --example memory [runs] [iterations]`
+
+use plac::codegen::asm::Instruction;
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+use plac::codegen::memory::FlatMemory;
+use plac::codegen::*;
+use std::time::{Duration, Instant};
+
+fn program(iterations: u32) -> (Vec<u8>, Vec<Instruction>, u32) {
+    let mut assembler = Assembler::new();
+    for line in [
+        &format!("load r0 {}", iterations),
+        "load r1 1",
+        "load r2 2",
+        "0: sub r0 r0 1 U",
+        "swap r1 r2",
+        "add r2 r1 r2 U",
+        "cmp r0 0",
+        "jmpn: E 0",
+        "halt",
+    ] {
+        assembler.add_ir(Ir::try_from(line.to_string()).unwrap());
+    }
+    assembler.compile(1).unwrap()
+}
+
+fn time<M: Memory>(memory: impl Fn() -> M, decoded: bool, runs: u32, iterations: u32) -> Duration {
+    let (data, instructions, start) = program(iterations);
+    let begin = Instant::now();
+    for _ in 0..runs {
+        let mut interpreter = Interpreter::with_memory(memory());
+        interpreter
+            .load(data.clone(), instructions.clone(), start)
+            .unwrap();
+        let trap = match decoded {
+            true => Engine::new(&mut interpreter).execute(),
+            false => interpreter.execute(),
+        };
+        assert!(trap.is_halt());
+    }
+    begin.elapsed()
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).map(|arg| arg.parse().unwrap());
+    let runs = args.next().unwrap_or(10_000);
+    let iterations = args.next().unwrap_or(15);
+    println!("{} runs of {} iterations", runs, iterations);
+    for decoded in [false, true] {
+        let map = time(std::collections::BTreeMap::new, decoded, runs, iterations);
+        let flat = time(FlatMemory::default, decoded, runs, iterations);
+        let engine = if decoded { "Engine" } else { "execute" };
+        println!("{:<8} map:  {:?}", engine, map);
+        println!("{:<8} flat: {:?}", engine, flat);
+    }
+}
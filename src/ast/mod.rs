@@ -4,6 +4,11 @@ use crate::Children;
 use crate::StackFrame;
 use crate::Context;
 
+pub struct Node {
+    pub node: Box<dyn Analyzable>,
+    pub children: Children,
+}
+
 pub trait Analyzable {
     fn analyze(&mut self, children: &mut Children, stackframe: Ref<StackFrame>, context: &mut Context) -> Type;
 }
@@ -0,0 +1,132 @@
+//! the calling convention of the plac vm
+//!
+//! | registers   | use                                    | preserved by |
+//! |-------------|----------------------------------------|--------------|
+//! | `r0`–`r7`   | arguments, `r0` holds the return value | caller       |
+//! | `r8`–`r12`  | general purpose                        | callee       |
+//! | `r13`       | frame pointer                          | callee       |
+//! | `r14` `r15` | `TRAP_CODE` and `TRAP_PC`              | nobody       |
+//!
+//! the first eight arguments are passed in `r0`–`r7`, the rest are pushed by the caller,
+//! last argument first, and popped again after the call. flags are not preserved.
+//! `SP` is a multiple of `STACK_ALIGNMENT` at every `call`, after the stack arguments
+//! are pushed, and every frame keeps it that way. every slot is a word, the stack grows down:
+//!
+//! ```text
+//! [FP + size() + 4 + 4i]  stack argument 8 + i
+//! [FP + size()]           return adress, pushed by `call`
+//! [FP + size() - 4]       the caller's FP
+//!                         callee saved registers the function uses
+//!                         padding
+//! [FP + local]            locals
+//! FP = SP
+//! ```
+
+use super::asm::*;
+use crate::Name;
+
+pub const ARGUMENTS: [Register; 8] = [
+    Register::R0,
+    Register::R1,
+    Register::R2,
+    Register::R3,
+    Register::R4,
+    Register::R5,
+    Register::R6,
+    Register::R7,
+];
+pub const RETURN_VALUE: Register = Register::R0;
+pub const CALLEE_SAVED: [Register; 5] = [
+    Register::R8,
+    Register::R9,
+    Register::R10,
+    Register::R11,
+    Register::R12,
+];
+pub const FRAME_POINTER: Register = Register::R13;
+/// in bytes
+pub const STACK_ALIGNMENT: u32 = 8;
+const WORD: u32 = 4;
+
+/// the stack frame of one function, one word per local
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub locals: Vec<Name>,
+    /// the callee saved registers the function uses, in the order they are pushed
+    pub saved: Vec<Register>,
+}
+
+impl Frame {
+    /// the bytes between the locals and the saved registers that keep `SP` aligned
+    pub fn padding(&self) -> u32 {
+        let pushed = WORD * (self.locals.len() + self.saved.len() + 2) as u32;
+        (STACK_ALIGNMENT - pushed % STACK_ALIGNMENT) % STACK_ALIGNMENT
+    }
+
+    /// the number of bytes from FP up to the return adress
+    pub fn size(&self) -> u32 {
+        self.reserved() + WORD * (self.saved.len() as u32 + 1)
+    }
+
+    /// the bytes the prologue reserves below the saved registers
+    fn reserved(&self) -> u32 {
+        WORD * self.locals.len() as u32 + self.padding()
+    }
+
+    /// the offset of a local from FP
+    pub fn local(&self, name: &str) -> Option<u32> {
+        self.locals
+            .iter()
+            .position(|local| local == name)
+            .map(|index| WORD * index as u32)
+    }
+
+    /// where the callee finds argument `index` once the prologue ran
+    pub fn argument(&self, index: usize) -> Value {
+        match ARGUMENTS.get(index) {
+            Some(register) => Value::Direct(Operand::Register(*register)),
+            None => Value::OperationalIndirect(
+                Operand::Register(FRAME_POINTER),
+                Operand::U32(self.size() + WORD * (1 + (index - ARGUMENTS.len()) as u32)),
+            ),
+        }
+    }
+
+    pub fn prologue(&self) -> Vec<Instruction> {
+        let register = |register| Value::Direct(Operand::Register(register));
+        let mut instructions = vec![Instruction::Push(register(FRAME_POINTER))];
+        for saved in &self.saved {
+            instructions.push(Instruction::Push(register(*saved)));
+        }
+        let reserved = self.reserved();
+        if reserved > 0 {
+            instructions.push(Instruction::Sub(
+                Register::StackPointer,
+                register(Register::StackPointer),
+                Value::Direct(Operand::U32(reserved)),
+                MathType::Unsigned,
+            ));
+        }
+        instructions.push(Instruction::Load(
+            FRAME_POINTER,
+            register(Register::StackPointer),
+        ));
+        instructions
+    }
+
+    /// ends with the `ret`
+    pub fn epilogue(&self) -> Vec<Instruction> {
+        let mut instructions = vec![Instruction::Add(
+            Register::StackPointer,
+            Value::Direct(Operand::Register(FRAME_POINTER)),
+            Value::Direct(Operand::U32(self.reserved())),
+            MathType::Unsigned,
+        )];
+        for saved in self.saved.iter().rev() {
+            instructions.push(Instruction::Pop(*saved));
+        }
+        instructions.push(Instruction::Pop(FRAME_POINTER));
+        instructions.push(Instruction::Ret);
+        instructions
+    }
+}
@@ -1,40 +1,162 @@
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
-pub enum Flag {
-    Overflow,
-    Lesser,
-    Greater,
-    Equal,
-    Always,
+/// an enum whose variants are written as the given text, with `ALL` in declaration order and
+/// the conversions to and from that text
+macro_rules! text_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $text:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+        }
+
+        impl $name {
+            pub const ALL: [$name; [$($text),*].len()] = [$($name::$variant),*];
+
+            /// how the assembly writes it
+            pub fn text(self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)*
+                }
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> String {
+                value.text().to_string()
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = ();
+            fn try_from(text: String) -> Result<$name, ()> {
+                $name::ALL.into_iter().find(|value| value.text() == text).ok_or(())
+            }
+        }
+    };
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
-pub enum Register {
-    R0,
-    R1,
-    R2,
-    R3,
-    R4,
-    R5,
-    R6,
-    R7,
-    R8,
-    R9,
-    R10,
-    R11,
-    R12,
-    R13,
-    R14,
-    R15,
-    ProgramCounter,
-    StackPointer,
-    ProgramPointer,
+/// the conversions of `$type` to and from assembly: a mnemonic followed by the fields of the
+/// variant, in order. a `sized` mnemonic ends in the width that is the last field of its
+/// variant, a field after `;` in `defaults` may be left out when it is the default, and
+/// `nested` reads every other mnemonic as the enum of the same name, held by that variant
+macro_rules! mnemonics {
+    (
+        $type:ident {
+            $($text:literal => $variant:ident $(($($tuple:ident),*))? $({$($field:ident),*})?,)*
+        }
+        $(sized {
+            $($sized_text:literal => $sized_variant:ident($($sized:ident),*),)*
+        })?
+        $(defaults {
+            $($default_text:literal => $default_variant:ident(
+                $($given:ident),*; $optional:ident = $default:expr
+            ),)*
+        })?
+        $(nested $nested:ident)?
+    ) => {
+        impl From<$type> for String {
+            fn from(value: $type) -> String {
+                match value {
+                    $($type::$variant $(($($tuple),*))? $({$($field),*})? => {
+                        [$text.to_string() $($(, String::from($tuple))*)? $($(, String::from($field))*)?].join(" ")
+                    })*
+                    $($($type::$sized_variant($($sized,)* width) => {
+                        let mnemonic = format!("{}{}", $sized_text, width.text());
+                        [mnemonic $(, String::from($sized))*].join(" ")
+                    })*)?
+                    $($($type::$default_variant($($given,)* $optional) => {
+                        let mut words = vec![$default_text.to_string() $(, String::from($given))*];
+                        if $optional != $default {
+                            words.push(String::from($optional));
+                        }
+                        words.join(" ")
+                    })*)?
+                    $($type::$nested(nested) => nested.into(),)?
+                }
+            }
+        }
+
+        impl $type {
+            /// the fields following `mnemonic`, with the words after them left in `words`
+            fn parse(mnemonic: &str, words: &mut Words) -> Result<$type, ()> {
+                Ok(match (mnemonic, sized(mnemonic)) {
+                    $(($text, _) => {
+                        $($(let $tuple = words.next()?;)*)?
+                        $($(let $field = words.next()?;)*)?
+                        $type::$variant $(($($tuple),*))? $({$($field),*})?
+                    })*
+                    $($((_, Some(($sized_text, width))) => {
+                        $(let $sized = words.next()?;)*
+                        $type::$sized_variant($($sized,)* width)
+                    })*)?
+                    $($(($default_text, _) => {
+                        $(let $given = words.next()?;)*
+                        let $optional = words.optional()?.unwrap_or($default);
+                        $type::$default_variant($($given,)* $optional)
+                    })*)?
+                    $(_ => $type::$nested($nested::parse(mnemonic, words)?),)?
+                    #[allow(unreachable_patterns)]
+                    _ => return Err(()),
+                })
+            }
+        }
+
+        impl TryFrom<String> for $type {
+            type Error = ();
+            fn try_from(text: String) -> Result<$type, ()> {
+                let mut words = text.split_whitespace();
+                let mnemonic = words.next().ok_or(())?;
+                let mut words = Words::Text(words);
+                let value = $type::parse(mnemonic, &mut words)?;
+                words.end()?;
+                Ok(value)
+            }
+        }
+    };
+}
+
+text_enum! {
+    Flag {
+        Overflow = "O",
+        Lesser = "L",
+        Greater = "G",
+        Equal = "E",
+        Always = "A",
+        /// set by the read syscalls when there was no input left
+        EndOfInput = "I",
+    }
+}
+
+text_enum! {
+    Register {
+        R0 = "r0",
+        R1 = "r1",
+        R2 = "r2",
+        R3 = "r3",
+        R4 = "r4",
+        R5 = "r5",
+        R6 = "r6",
+        R7 = "r7",
+        R8 = "r8",
+        R9 = "r9",
+        R10 = "r10",
+        R11 = "r11",
+        R12 = "r12",
+        R13 = "r13",
+        R14 = "r14",
+        R15 = "r15",
+        ProgramCounter = "PC",
+        StackPointer = "SP",
+        ProgramPointer = "PP",
+    }
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
 pub enum Value {
     Direct(Operand),
     Indirect(Operand),
+    /// `a+b`
     OperationalDirect(Operand, Operand),
+    /// `[a+b]`, the word at adress a + b
     OperationalIndirect(Operand, Operand),
 }
 
@@ -44,11 +166,21 @@ pub enum Operand {
     Register(Register),
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
-pub enum MathType {
-    Unsigned,
-    Signed,
-    Float,
+text_enum! {
+    MathType {
+        Unsigned = "U",
+        Signed = "S",
+        Float = "F",
+    }
+}
+
+text_enum! {
+    /// how many bytes a sized load or store moves
+    Width {
+        Byte = "8",
+        Half = "16",
+        Word = "32",
+    }
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
@@ -64,7 +196,10 @@ pub enum Instruction {
     Not(Register, Value),
     ShiftLeft(Register, Value, Value),
     ShiftRight(Register, Value, Value),
-    Compare(Value, Value),
+    /// sets `Lesser`, `Greater` or `Equal` for the first value against the second as the math
+    /// type, which `cmp` leaves out when it is unsigned. a float compared with NaN sets none of
+    /// them
+    Compare(Value, Value, MathType),
     Jump(Flag, Value),
     JumpNot(Flag, Value),
     Load(Register, Value),
@@ -72,6 +207,24 @@ pub enum Instruction {
     Swap(Register, Register),
     Halt,
     SysCall(SysCall),
+    Push(Value),
+    Pop(Register),
+    /// pushes the adress of the next instruction and jumps if the flag is set
+    Call(Flag, Value),
+    CallNot(Flag, Value),
+    /// pops the return adress pushed by `Call` and jumps to it
+    Ret,
+    /// reads the little endian value at the adress, zero extended
+    LoadMemory(Register, Value, Width),
+    /// writes the low bytes of the second value to the adress in the first
+    Store(Value, Value, Width),
+    /// the value as the first math type, converted to the second. a float is rounded toward
+    /// zero, NaN becomes 0 and a number that does not fit saturates, setting `Overflow`
+    Convert(Register, Value, MathType, MathType),
+    /// the low bytes of the value, sign extended
+    SignExtend(Register, Value, Width),
+    /// the low bytes of the value, zero extended
+    ZeroExtend(Register, Value, Width),
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
@@ -79,87 +232,53 @@ pub enum SysCall {
     Print(Value),
     PrintChar(Value),
     PrintString { start: Value, length: Value },
+    /// a decimal number, after skipping whitespace
+    ReadInt(Register),
+    /// a single byte
+    ReadChar(Register),
+    /// stores up to `max_len` bytes of the next line at `start`, without its newline,
+    /// and their number in the register
+    ReadLine { length: Register, start: Value, max_len: Value },
+    /// opens the file named by the `path_len` bytes at `path`, relative to the root the
+    /// embedder gave the interpreter. mode 0 reads, 1 writes and 2 appends. the register
+    /// gets the file descriptor, or `files::FAILED`
+    Open { fd: Register, path: Value, path_len: Value, mode: Value },
+    /// reads up to `length` bytes from a file descriptor to `start`, the register gets
+    /// their number, or `files::FAILED`
+    Read { count: Register, fd: Value, start: Value, length: Value },
+    /// writes `length` bytes from `start` to a file descriptor, the register gets their
+    /// number, or `files::FAILED`
+    Write { count: Register, fd: Value, start: Value, length: Value },
+    /// the register gets 0, or `files::FAILED` when the file descriptor was not open
+    Close { result: Register, fd: Value },
+    /// halts with the value as exit code
+    Exit(Value),
+    /// the register gets the adress of a heap block of at least `size` bytes, or 0
+    Alloc { pointer: Register, size: Value },
+    /// hands a block `alloc` returned back to the heap
+    Free(Value),
 }
 
-impl From<Flag> for String {
-    fn from(flag: Flag) -> String {
-        match flag {
-            Flag::Overflow => "O".to_string(),
-            Flag::Lesser => "L".to_string(),
-            Flag::Greater => "G".to_string(),
-            Flag::Equal => "E".to_string(),
-            Flag::Always => "A".to_string(),
-        }
+impl Register {
+    pub fn index(self) -> usize {
+        self as usize
     }
-}
 
-impl TryFrom<String> for Flag {
-    type Error = ();
-    fn try_from(flag: String) -> Result<Flag, ()> {
-        Ok(match flag.as_str() {
-            "O" => Flag::Overflow,
-            "L" => Flag::Lesser,
-            "G" => Flag::Greater,
-            "E" => Flag::Equal,
-            "A" => Flag::Always,
-            _ => return Err(()),
-        })
+    pub fn from_index(index: usize) -> Option<Register> {
+        Register::ALL.get(index).copied()
     }
 }
 
-impl From<Register> for String {
-    fn from(register: Register) -> String {
-        match register {
-            Register::R0 => "r0".to_string(),
-            Register::R1 => "r1".to_string(),
-            Register::R2 => "r2".to_string(),
-            Register::R3 => "r3".to_string(),
-            Register::R4 => "r4".to_string(),
-            Register::R5 => "r5".to_string(),
-            Register::R6 => "r6".to_string(),
-            Register::R7 => "r7".to_string(),
-            Register::R8 => "r8".to_string(),
-            Register::R9 => "r9".to_string(),
-            Register::R10 => "r10".to_string(),
-            Register::R11 => "r11".to_string(),
-            Register::R12 => "r12".to_string(),
-            Register::R13 => "r13".to_string(),
-            Register::R14 => "r14".to_string(),
-            Register::R15 => "r15".to_string(),
-            Register::ProgramCounter => "PC".to_string(),
-            Register::StackPointer => "SP".to_string(),
-            Register::ProgramPointer => "PP".to_string(),
+impl Width {
+    pub fn bytes(self) -> u32 {
+        match self {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
         }
     }
 }
 
-impl TryFrom<String> for Register {
-    type Error = ();
-    fn try_from(register: String) -> Result<Register, ()> {
-        Ok(match register.as_str() {
-            "r0" => Register::R0,
-            "r1" => Register::R1,
-            "r2" => Register::R2,
-            "r3" => Register::R3,
-            "r4" => Register::R4,
-            "r6" => Register::R6,
-            "r7" => Register::R7,
-            "r8" => Register::R8,
-            "r9" => Register::R9,
-            "r10" => Register::R10,
-            "r11" => Register::R11,
-            "r12" => Register::R12,
-            "r13" => Register::R13,
-            "r14" => Register::R14,
-            "r15" => Register::R15,
-            "PC" => Register::ProgramCounter,
-            "SP" => Register::StackPointer,
-            "PP" => Register::ProgramPointer,
-            _ => return Err(()),
-        })
-    }
-}
-
 impl From<Operand> for String {
     fn from(operand: Operand) -> String {
         match operand {
@@ -196,220 +315,307 @@ impl From<Value> for String {
 
 impl TryFrom<String> for Value {
     type Error = ();
-    fn try_from(mut value: String) -> Result<Value, ()> {
-        if value.starts_with('[') {
-            if value.contains('+') {
-                value.pop();
-                value.remove(0);
-                let (first, second) = value.split_once('+').unwrap();
-                Ok(Value::OperationalIndirect(
-                    Operand::try_from(first.to_string())?,
-                    Operand::try_from(second.to_string())?,
-                ))
-            } else {
-                Ok(Value::Indirect(Operand::try_from(
-                    value[1..value.len() - 1].to_string(),
-                )?))
+    fn try_from(value: String) -> Result<Value, ()> {
+        let (indirect, inner) = match value.strip_prefix('[') {
+            Some(rest) => (true, rest.strip_suffix(']').ok_or(())?),
+            None => (false, value.as_str()),
+        };
+        let operand = |text: &str| Operand::try_from(text.to_string());
+        Ok(match (indirect, inner.split_once('+')) {
+            (false, None) => Value::Direct(operand(inner)?),
+            (true, None) => Value::Indirect(operand(inner)?),
+            (false, Some((first, second))) => {
+                Value::OperationalDirect(operand(first)?, operand(second)?)
             }
-        } else if value.contains('+') {
-            let (first, second) = value.split_once('+').unwrap();
-            Ok(Value::OperationalDirect(
-                Operand::try_from(first.to_string())?,
-                Operand::try_from(second.to_string())?,
-            ))
-        } else {
-            Ok(Value::Direct(Operand::try_from(value)?))
-        }
+            (true, Some((first, second))) => {
+                Value::OperationalIndirect(operand(first)?, operand(second)?)
+            }
+        })
     }
 }
 
-impl From<MathType> for String {
-    fn from(math_type: MathType) -> String {
-        match math_type {
-            MathType::Unsigned => "U".to_string(),
-            MathType::Signed => "S".to_string(),
-            MathType::Float => "F".to_string(),
+/// what a field of an instruction is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Register,
+    Value,
+    Flag,
+    MathType,
+}
+
+impl Field {
+    /// a word that is read as the field
+    fn example(self) -> &'static str {
+        match self {
+            Field::Register => "r0",
+            Field::Value => "0",
+            Field::Flag => "A",
+            Field::MathType => "U",
         }
     }
 }
 
-impl TryFrom<String> for MathType {
-    type Error = ();
-    fn try_from(math_type: String) -> Result<MathType, ()> {
-        Ok(match math_type.as_str() {
-            "U" => MathType::Unsigned,
-            "S" => MathType::Signed,
-            "F" => MathType::Float,
-            _ => return Err(()),
-        })
-    }
+/// the types the fields of an instruction are read as
+trait Word: TryFrom<String, Error = ()> {
+    const FIELD: Field;
 }
 
-impl From<SysCall> for String {
-    fn from(print: SysCall) -> String {
-        match print {
-            SysCall::Print(value) => format!("print {}", String::from(value)),
-            SysCall::PrintChar(value) => format!("printc {}", String::from(value)),
-            SysCall::PrintString { start, length } => {
-                format!("printstr {} {}", String::from(start), String::from(length))
+impl Word for Register {
+    const FIELD: Field = Field::Register;
+}
+
+impl Word for Value {
+    const FIELD: Field = Field::Value;
+}
+
+impl Word for Flag {
+    const FIELD: Field = Field::Flag;
+}
+
+impl Word for MathType {
+    const FIELD: Field = Field::MathType;
+}
+
+/// the words of an instruction after its mnemonic
+enum Words<'a> {
+    Text(std::str::SplitWhitespace<'a>),
+    /// records the fields that are read instead, and reads every field from its example.
+    /// the fields that may be left out come last
+    Signature { fields: Vec<Field>, required: usize },
+}
+
+impl Words<'_> {
+    fn next<T: Word>(&mut self) -> Result<T, ()> {
+        match self {
+            Words::Text(words) => T::try_from(words.next().ok_or(())?.to_string()),
+            Words::Signature { fields, required } => {
+                fields.push(T::FIELD);
+                *required = fields.len();
+                T::try_from(T::FIELD.example().to_string())
             }
         }
     }
-}
 
-impl TryFrom<String> for SysCall {
-    type Error = ();
-    fn try_from(mut print: String) -> Result<Self, ()> {
-        if print.starts_with("printstr") {
-            print = print.replace("printstr", "").trim().to_string();
-            let (start, length) = print.split_once(' ').unwrap();
-            Ok(Self::PrintString {
-                start: Value::try_from(start.to_string())?,
-                length: Value::try_from(length.to_string())?,
-            })
-        } else if print.starts_with("printc") {
-            print = print.replace("printc", "").trim().to_string();
-            Ok(Self::PrintChar(Value::try_from(print)?))
-        } else {
-            print = print.replace("print", "").trim().to_string();
-            Ok(Self::Print(Value::try_from(print)?))
+    /// the next word if there is one
+    fn optional<T: Word>(&mut self) -> Result<Option<T>, ()> {
+        match self {
+            Words::Text(words) => words
+                .next()
+                .map(|word| T::try_from(word.to_string()))
+                .transpose(),
+            Words::Signature { fields, .. } => {
+                fields.push(T::FIELD);
+                T::try_from(T::FIELD.example().to_string()).map(Some)
+            }
+        }
+    }
+
+    /// fails when there are words left
+    fn end(&mut self) -> Result<(), ()> {
+        match self {
+            Words::Text(words) => words.next().map_or(Ok(()), |_| Err(())),
+            Words::Signature { .. } => Ok(()),
         }
     }
 }
 
-macro_rules! fmt_string {
-    ($first:expr, $($rest:expr),*) => {
-        format!($first, $(String::from($rest)),*)
-    };
+mnemonics! {
+    SysCall {
+        "print" => Print(value),
+        "printc" => PrintChar(value),
+        "printstr" => PrintString { start, length },
+        "readint" => ReadInt(register),
+        "readc" => ReadChar(register),
+        "readline" => ReadLine { length, start, max_len },
+        "open" => Open { fd, path, path_len, mode },
+        "read" => Read { count, fd, start, length },
+        "write" => Write { count, fd, start, length },
+        "close" => Close { result, fd },
+        "exit" => Exit(value),
+        "alloc" => Alloc { pointer, size },
+        "free" => Free(value),
+    }
 }
 
-impl From<Instruction> for String {
-    fn from(instruction: Instruction) -> String {
-        match instruction {
-            Instruction::Add(register, value1, value2, math_type) => {
-                fmt_string!("add {} {} {} {}", register, value1, value2, math_type)
-            }
-            Instruction::Sub(register, value1, value2, math_type) => {
-                fmt_string!("sub {} {} {} {}", register, value1, value2, math_type)
-            }
-            Instruction::Mul(register, value1, value2, math_type) => {
-                fmt_string!("mul {} {} {} {}", register, value1, value2, math_type)
-            }
-            Instruction::Div(register, value1, value2, math_type) => {
-                fmt_string!("div {} {} {} {}", register, value1, value2, math_type)
-            }
-            Instruction::Mod(register, value1, value2, math_type) => {
-                fmt_string!("mod {} {} {} {}", register, value1, value2, math_type)
-            }
-            Instruction::And(register, value1, value2) => {
-                fmt_string!("and {} {} {}", register, value1, value2)
-            }
-            Instruction::Or(register, value1, value2) => {
-                fmt_string!("or {} {} {}", register, value1, value2)
-            }
-            Instruction::Xor(register, value1, value2) => {
-                fmt_string!("xor {} {} {}", register, value1, value2)
-            }
-            Instruction::Not(register, value) => fmt_string!("not {} {}", register, value),
-            Instruction::ShiftLeft(register, value1, value2) => {
-                fmt_string!("shl {} {} {}", register, value1, value2)
-            }
-            Instruction::ShiftRight(register, value1, value2) => {
-                fmt_string!("shr {} {} {}", register, value1, value2)
-            }
-            Instruction::Compare(value1, value2) => fmt_string!("cmp {} {}", value1, value2),
-            Instruction::Jump(flag, value) => fmt_string!("jmp {} {}", flag, value),
-            Instruction::Load(register, value) => fmt_string!("load {} {}", register, value),
-            Instruction::Move(value1, value2) => fmt_string!("mov {} {}", value1, value2),
-            Instruction::Halt => "halt".to_string(),
-            Instruction::SysCall(print) => fmt_string!("{}", print),
-            Instruction::Swap(reg1, reg2) => fmt_string!("swap {} {}", reg1, reg2),
-            Instruction::JumpNot(flag, value) => fmt_string!("jmpn {} {}", flag, value),
+/// `load8`, `sext16` and the other sized mnemonics, split into their name and the width
+fn sized(mnemonic: &str) -> Option<(&str, Width)> {
+    let width = mnemonic.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let width = Width::try_from(width.to_string()).ok()?;
+    Some((&mnemonic[..mnemonic.len() - width.text().len()], width))
+}
+
+mnemonics! {
+    Instruction {
+        "add" => Add(register, value1, value2, math_type),
+        "sub" => Sub(register, value1, value2, math_type),
+        "mul" => Mul(register, value1, value2, math_type),
+        "div" => Div(register, value1, value2, math_type),
+        "mod" => Mod(register, value1, value2, math_type),
+        "and" => And(register, value1, value2),
+        "or" => Or(register, value1, value2),
+        "xor" => Xor(register, value1, value2),
+        "not" => Not(register, value),
+        "shl" => ShiftLeft(register, value1, value2),
+        "shr" => ShiftRight(register, value1, value2),
+        "jmp" => Jump(flag, value),
+        "jmpn" => JumpNot(flag, value),
+        "load" => Load(register, value),
+        "mov" => Move(value1, value2),
+        "swap" => Swap(register1, register2),
+        "halt" => Halt,
+        "push" => Push(value),
+        "pop" => Pop(register),
+        "call" => Call(flag, value),
+        "calln" => CallNot(flag, value),
+        "ret" => Ret,
+        "cvt" => Convert(register, value, from, to),
+    }
+    sized {
+        "load" => LoadMemory(register, value),
+        "store" => Store(value1, value2),
+        "sext" => SignExtend(register, value),
+        "zext" => ZeroExtend(register, value),
+    }
+    defaults {
+        "cmp" => Compare(value1, value2; math_type = MathType::Unsigned),
+    }
+    nested SysCall
+}
+
+impl Instruction {
+    /// the fields that follow `mnemonic` and how many of them have to be written, the others
+    /// can be left out at the end. `None` when `mnemonic` is not an instruction
+    pub fn signature(mnemonic: &str) -> Option<(Vec<Field>, usize)> {
+        let mut words = Words::Signature {
+            fields: Vec::new(),
+            required: 0,
+        };
+        Instruction::parse(mnemonic, &mut words).ok()?;
+        match words {
+            Words::Signature { fields, required } => Some((fields, required)),
+            Words::Text(_) => unreachable!(),
+        }
+    }
+}
+
+impl Value {
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Value::Direct(operand) | Value::Indirect(operand) => vec![operand],
+            Value::OperationalDirect(operand1, operand2)
+            | Value::OperationalIndirect(operand1, operand2) => vec![operand1, operand2],
         }
     }
 }
 
-macro_rules! from_str {
-    ($first:expr, $($rest:ty),*) => {
-        (|str:&str| {
-            if !str.starts_with($first) {
-                return Err(());
+impl SysCall {
+    pub fn values_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            SysCall::Print(value)
+            | SysCall::PrintChar(value)
+            | SysCall::Exit(value)
+            | SysCall::Free(value)
+            | SysCall::Alloc { size: value, .. } => vec![value],
+            SysCall::PrintString { start, length } => vec![start, length],
+            SysCall::ReadLine { start, max_len, .. } => vec![start, max_len],
+            SysCall::Open { path, path_len, mode, .. } => vec![path, path_len, mode],
+            SysCall::Read { fd, start, length, .. } | SysCall::Write { fd, start, length, .. } => {
+                vec![fd, start, length]
             }
-            let str = str.trim_start_matches($first);
-            let str = str.trim();
-            let mut iter = str.split_whitespace();
-            Ok((
-                $(
-                    <$rest>::try_from(iter.next().ok_or(())?.to_string())?,
-                )*
-            ))
-        })
-    };
+            SysCall::Close { fd, .. } => vec![fd],
+            SysCall::ReadInt(_) | SysCall::ReadChar(_) => vec![],
+        }
+    }
+
+    /// the register the syscall writes
+    pub fn register(&self) -> Option<Register> {
+        match self {
+            SysCall::ReadInt(register)
+            | SysCall::ReadChar(register)
+            | SysCall::ReadLine { length: register, .. }
+            | SysCall::Open { fd: register, .. }
+            | SysCall::Read { count: register, .. }
+            | SysCall::Write { count: register, .. }
+            | SysCall::Close { result: register, .. }
+            | SysCall::Alloc { pointer: register, .. } => Some(*register),
+            _ => None,
+        }
+    }
 }
 
-impl TryFrom<String> for Instruction {
-    type Error = ();
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        if let Ok((register, value1, value2, value3)) =
-            from_str!("add", Register, Value, Value, MathType)(value.as_str())
-        {
-            Ok(Instruction::Add(register, value1, value2, value3))
-        } else if let Ok((register, value1, value2, value3)) =
-            from_str!("sub", Register, Value, Value, MathType)(value.as_str())
-        {
-            Ok(Instruction::Sub(register, value1, value2, value3))
-        } else if let Ok((register, value1, value2, value3)) =
-            from_str!("mul", Register, Value, Value, MathType)(value.as_str())
-        {
-            Ok(Instruction::Mul(register, value1, value2, value3))
-        } else if let Ok((register, value1, value2, value3)) =
-            from_str!("div", Register, Value, Value, MathType)(value.as_str())
-        {
-            Ok(Instruction::Div(register, value1, value2, value3))
-        } else if let Ok((register, value1, value2, value3)) =
-            from_str!("mod", Register, Value, Value, MathType)(value.as_str())
-        {
-            Ok(Instruction::Mod(register, value1, value2, value3))
-        } else if let Ok((register, value1, value2)) =
-            from_str!("and", Register, Value, Value)(value.as_str())
-        {
-            Ok(Instruction::And(register, value1, value2))
-        } else if let Ok((register, value1, value2)) =
-            from_str!("or", Register, Value, Value)(value.as_str())
-        {
-            Ok(Instruction::Or(register, value1, value2))
-        } else if let Ok((register, value1, value2)) =
-            from_str!("xor", Register, Value, Value)(value.as_str())
-        {
-            Ok(Instruction::Xor(register, value1, value2))
-        } else if let Ok((register, value)) = from_str!("not", Register, Value)(value.as_str()) {
-            Ok(Instruction::Not(register, value))
-        } else if let Ok((register, value1, value2)) =
-            from_str!("shl", Register, Value, Value)(value.as_str())
-        {
-            Ok(Instruction::ShiftLeft(register, value1, value2))
-        } else if let Ok((register, value1, value2)) =
-            from_str!("shr", Register, Value, Value)(value.as_str())
-        {
-            Ok(Instruction::ShiftRight(register, value1, value2))
-        } else if let Ok((value1, value2)) = from_str!("cmp", Value, Value)(value.as_str()) {
-            Ok(Instruction::Compare(value1, value2))
-        } else if let Ok((flag, value)) = from_str!("jmp", Flag, Value)(value.as_str()) {
-            Ok(Instruction::Jump(flag, value))
-        } else if let Ok((register, value)) = from_str!("load", Register, Value)(value.as_str()) {
-            Ok(Instruction::Load(register, value))
-        } else if let Ok((value1, value2)) = from_str!("mov", Value, Value)(value.as_str()) {
-            Ok(Instruction::Move(value1, value2))
-        } else if value == "halt" {
-            Ok(Instruction::Halt)
-        } else if let Ok((reg1, reg2)) = from_str!("swap", Register, Register)(value.as_str()) {
-            Ok(Instruction::Swap(reg1, reg2))
-        } else if let Ok(print) = SysCall::try_from(value.clone()) {
-            Ok(Instruction::SysCall(print))
-        } else if let Ok((flag, value)) = from_str!("jmpn", Flag, Value)(value.as_str()) {
-            Ok(Instruction::JumpNot(flag, value))
-        } else {
-            Err(())
+impl Instruction {
+    /// every value of the instruction, in the order they are written in assembly
+    pub fn values_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instruction::Add(_, value1, value2, _)
+            | Instruction::Sub(_, value1, value2, _)
+            | Instruction::Mul(_, value1, value2, _)
+            | Instruction::Div(_, value1, value2, _)
+            | Instruction::Mod(_, value1, value2, _)
+            | Instruction::And(_, value1, value2)
+            | Instruction::Or(_, value1, value2)
+            | Instruction::Xor(_, value1, value2)
+            | Instruction::ShiftLeft(_, value1, value2)
+            | Instruction::ShiftRight(_, value1, value2)
+            | Instruction::Compare(value1, value2, _)
+            | Instruction::Move(value1, value2) => vec![value1, value2],
+            Instruction::Not(_, value)
+            | Instruction::Jump(_, value)
+            | Instruction::JumpNot(_, value)
+            | Instruction::Load(_, value)
+            | Instruction::Push(value)
+            | Instruction::Call(_, value)
+            | Instruction::CallNot(_, value)
+            | Instruction::LoadMemory(_, value, _)
+            | Instruction::Convert(_, value, ..)
+            | Instruction::SignExtend(_, value, _)
+            | Instruction::ZeroExtend(_, value, _) => vec![value],
+            Instruction::Store(value1, value2, _) => vec![value1, value2],
+            Instruction::Swap(_, _)
+            | Instruction::Halt
+            | Instruction::Pop(_)
+            | Instruction::Ret => vec![],
+            Instruction::SysCall(syscall) => syscall.values_mut(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        self.values_mut()
+            .into_iter()
+            .flat_map(Value::operands_mut)
+            .collect()
+    }
+
+    /// every register the instruction names, whether it reads or writes it
+    pub fn registers(&self) -> Vec<Register> {
+        let mut registers = match self {
+            Instruction::Add(register, ..)
+            | Instruction::Sub(register, ..)
+            | Instruction::Mul(register, ..)
+            | Instruction::Div(register, ..)
+            | Instruction::Mod(register, ..)
+            | Instruction::And(register, ..)
+            | Instruction::Or(register, ..)
+            | Instruction::Xor(register, ..)
+            | Instruction::Not(register, ..)
+            | Instruction::ShiftLeft(register, ..)
+            | Instruction::ShiftRight(register, ..)
+            | Instruction::Load(register, ..)
+            | Instruction::LoadMemory(register, ..)
+            | Instruction::Convert(register, ..)
+            | Instruction::SignExtend(register, ..)
+            | Instruction::ZeroExtend(register, ..)
+            | Instruction::Pop(register) => vec![*register],
+            Instruction::Swap(register1, register2) => vec![*register1, *register2],
+            Instruction::SysCall(syscall) => syscall.register().into_iter().collect(),
+            _ => vec![],
+        };
+        let mut instruction = *self;
+        for operand in instruction.operands_mut() {
+            if let Operand::Register(register) = operand {
+                registers.push(*register);
+            }
         }
+        registers
     }
 }
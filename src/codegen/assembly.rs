@@ -0,0 +1,528 @@
+use super::asm::*;
+use super::{Assembler, Data, Ir};
+use crate::error::{Basic, Error, Info, Level};
+use crate::parser::{Parser, Position};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+pub const UNKNOWN_MNEMONIC: u64 = 101;
+pub const EXPECTED_OPERAND: u64 = 102;
+pub const UNEXPECTED_OPERAND: u64 = 103;
+pub const UNKNOWN_DIRECTIVE: u64 = 104;
+pub const INVALID_LABEL: u64 = 105;
+pub const DUPLICATE_LABEL: u64 = 106;
+pub const UNDEFINED_LABEL: u64 = 107;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Register,
+    Value,
+    Flag,
+    MathType,
+    Label,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Register => "register",
+            Kind::Value => "value",
+            Kind::Flag => "flag",
+            Kind::MathType => "math type",
+            Kind::Label => "label",
+        }
+    }
+
+    fn accepts(&self, token: &str) -> bool {
+        match self {
+            Kind::Register => Register::try_from(token.to_string()).is_ok(),
+            Kind::Value => value(token).is_some(),
+            Kind::Flag => Flag::try_from(token.to_string()).is_ok(),
+            Kind::MathType => MathType::try_from(token.to_string()).is_ok(),
+            Kind::Label => is_label(token),
+        }
+    }
+}
+
+/// the operands `mnemonic` takes and how many of them are required, as `Instruction` reads
+/// them. `jmp:` and `jmpn:` take a label where `jmp` and `jmpn` take a value
+fn signature(mnemonic: &str) -> Option<(Vec<Kind>, usize)> {
+    let (mnemonic, label) = match mnemonic {
+        "jmp:" | "jmpn:" => (mnemonic.trim_end_matches(':'), true),
+        _ => (mnemonic, false),
+    };
+    let (fields, required) = Instruction::signature(mnemonic)?;
+    let kinds = fields
+        .into_iter()
+        .map(|field| match field {
+            Field::Register => Kind::Register,
+            Field::Value if label => Kind::Label,
+            Field::Value => Kind::Value,
+            Field::Flag => Kind::Flag,
+            Field::MathType => Kind::MathType,
+        })
+        .collect();
+    Some((kinds, required))
+}
+
+fn is_label(token: &str) -> bool {
+    !token.is_empty()
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// a word that can name a label, rather than a number or something written like a register
+/// (`r16` is a register that does not exist, not a label)
+fn is_symbol(token: &str) -> bool {
+    let register = match token.strip_prefix('r') {
+        Some(index) => !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    };
+    is_label(token) && !register && !token.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// splits a value into the text that `Value::try_from` accepts, with every symbol
+/// replaced by `0`, and the symbol (if any) of each of its operands
+fn value(token: &str) -> Option<(String, Vec<Option<String>>)> {
+    let (indirect, inner) = match token.strip_prefix('[') {
+        Some(rest) => (true, rest.strip_suffix(']')?),
+        None => (false, token),
+    };
+    let operands: Vec<&str> = inner.split('+').collect();
+    if operands.len() > 2 {
+        return None;
+    }
+    let mut text = Vec::new();
+    let mut symbols = Vec::new();
+    for operand in operands {
+        if Operand::try_from(operand.to_string()).is_ok() {
+            text.push(operand);
+            symbols.push(None);
+        } else if is_symbol(operand) {
+            text.push("0");
+            symbols.push(Some(operand.to_string()));
+        } else {
+            return None;
+        }
+    }
+    let text = text.join("+");
+    Some((if indirect { format!("[{}]", text) } else { text }, symbols))
+}
+
+fn string(token: &str) -> Option<Vec<u8>> {
+    let inner = token.strip_prefix('"')?.strip_suffix('"')?;
+    let mut chars = inner.chars();
+    let mut result = Vec::new();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' => '\\',
+                '"' => '"',
+                _ => return None,
+            },
+            '"' => return None,
+            c => c,
+        };
+        result.extend(c.encode_utf8(&mut [0; 4]).bytes());
+    }
+    Some(result)
+}
+
+fn byte(token: &str) -> Option<u8> {
+    token
+        .parse::<u8>()
+        .ok()
+        .or_else(|| token.parse::<i8>().ok().map(|value| value as u8))
+}
+
+fn word(token: &str) -> Option<u32> {
+    token
+        .parse::<u32>()
+        .ok()
+        .or_else(|| token.parse::<i32>().ok().map(|value| value as u32))
+}
+
+struct Token<'a> {
+    text: &'a str,
+    position: Position,
+}
+
+fn tokenize<'a>(file: &str, number: usize, line: &'a str) -> Vec<Token<'a>> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    loop {
+        while index < bytes.len() && bytes[index].is_ascii_whitespace() {
+            index += 1;
+        }
+        if index >= bytes.len() || bytes[index] == b';' {
+            break;
+        }
+        let begin = index;
+        if bytes[index] == b'"' {
+            index += 1;
+            while index < bytes.len() && bytes[index] != b'"' {
+                if bytes[index] == b'\\' {
+                    index += 1;
+                }
+                index += 1;
+            }
+            index = (index + 1).min(bytes.len());
+        } else {
+            while index < bytes.len() && !bytes[index].is_ascii_whitespace() && bytes[index] != b';' {
+                index += 1;
+            }
+        }
+        tokens.push(Token {
+            text: &line[begin..index],
+            position: Position {
+                line: (number, number),
+                column: (begin + 1, index + 1),
+                file: file.to_string(),
+            },
+        });
+    }
+    tokens
+}
+
+fn end_of_line(file: &str, number: usize, line: &str) -> Position {
+    let column = line.trim_end().len() + 1;
+    Position {
+        line: (number, number),
+        column: (column, column + 1),
+        file: file.to_string(),
+    }
+}
+
+type Diagnostic = (Position, (u64, &'static str), String, String);
+type Failure = Box<Diagnostic>;
+
+fn failure(token: &Token, code: (u64, &'static str), message: String, label: &str) -> Failure {
+    Box::new((token.position.clone(), code, message, label.to_string()))
+}
+
+struct Diagnostics<'a> {
+    parser: &'a mut Parser,
+    error: Error,
+    failed: bool,
+}
+
+impl Diagnostics<'_> {
+    fn report(&mut self, (position, (code, name), message, label): Diagnostic) {
+        self.failed = true;
+        let info = Info {
+            name: name.to_string(),
+            code,
+            level: Level::Error,
+        };
+        let messages = vec![(label, position.clone())];
+        if let Some(basic) = Basic::new(position, info, self.parser, messages, message) {
+            self.error.push(basic);
+        }
+    }
+}
+
+/// parses an assembly file into `assembler`: code goes into the init block and data
+/// directives into the statics. every problem in the file is reported, not just the first one
+pub fn parse(parser: &mut Parser, assembler: &mut Assembler) -> Result<(), Error> {
+    let file = parser.file_name.clone();
+    let input = parser.input.clone();
+    let mut diagnostics = Diagnostics {
+        parser,
+        error: Error::new(),
+        failed: false,
+    };
+    let mut labels: HashMap<String, Position> = HashMap::new();
+    let mut references: Vec<(String, Position)> = Vec::new();
+    let mut pending: Vec<String> = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let number = index + 1;
+        let tokens = tokenize(&file, number, line);
+        let mut tokens = tokens.iter().peekable();
+
+        while let Some(token) = tokens.peek() {
+            if !token.text.ends_with(':') || signature(token.text).is_some() {
+                break;
+            }
+            let label = token.text.trim_end_matches(':');
+            if !is_label(label) {
+                diagnostics.report(*failure(
+                    token,
+                    (INVALID_LABEL, "invalid-label"),
+                    format!("invalid label `{}`", label),
+                    "labels may only contain letters, digits, `_` and `.`",
+                ));
+            } else if labels.contains_key(label) {
+                diagnostics.report(*failure(
+                    token,
+                    (DUPLICATE_LABEL, "duplicate-label"),
+                    format!("label `{}` is defined more than once", label),
+                    "redefined here",
+                ));
+            } else {
+                labels.insert(label.to_string(), token.position.clone());
+                pending.push(label.to_string());
+            }
+            tokens.next();
+        }
+
+        let Some(head) = tokens.next() else {
+            continue;
+        };
+        let operands: Vec<&Token> = tokens.collect();
+
+        if head.text.starts_with('.') {
+            match directive(head, &operands) {
+                Ok(Directive::Entry(label, position)) => {
+                    references.push((label.clone(), position));
+                    assembler.set_entry(label);
+                }
+                Ok(Directive::Global(label, position)) => {
+                    references.push((label.clone(), position));
+                    assembler.set_global(label);
+                }
+                Ok(Directive::Extern(label, position)) => match labels.entry(label) {
+                    Entry::Occupied(entry) => diagnostics.report((
+                        position,
+                        (DUPLICATE_LABEL, "duplicate-label"),
+                        format!("label `{}` is defined more than once", entry.key()),
+                        "declared as external here".to_string(),
+                    )),
+                    Entry::Vacant(entry) => {
+                        entry.insert(position);
+                    }
+                },
+                Ok(Directive::Data(items)) => {
+                    for label in pending.drain(..) {
+                        let length = format!("{}_len", label);
+                        if let Some(position) = labels.get(&label).cloned() {
+                            if labels.contains_key(&length) {
+                                diagnostics.report((
+                                    position.clone(),
+                                    (DUPLICATE_LABEL, "duplicate-label"),
+                                    format!("label `{}` is defined more than once", length),
+                                    format!("data label `{}` defines `{}`", label, length),
+                                ));
+                            }
+                            labels.insert(length, position);
+                        }
+                        assembler.add_data(Data::Label(label));
+                    }
+                    for item in items {
+                        assembler.add_data(item);
+                    }
+                }
+                Err(failure) => diagnostics.report(*failure),
+            }
+            continue;
+        }
+
+        let Some((kinds, required)) = signature(head.text) else {
+            diagnostics.report(*failure(
+                head,
+                (UNKNOWN_MNEMONIC, "unknown-mnemonic"),
+                format!("unknown instruction `{}`", head.text),
+                "not an instruction",
+            ));
+            continue;
+        };
+
+        let mut valid = true;
+        let mut text = vec![head.text.to_string()];
+        let mut symbols = Vec::new();
+        for (index, kind) in kinds.iter().enumerate() {
+            match operands.get(index) {
+                Some(token) if kind.accepts(token.text) => match kind {
+                    Kind::Label => {
+                        references.push((token.text.to_string(), token.position.clone()));
+                        text.push(token.text.to_string());
+                    }
+                    Kind::Value => {
+                        let (placeholder, operands) = value(token.text).unwrap();
+                        for symbol in operands {
+                            if let Some(symbol) = &symbol {
+                                references.push((symbol.clone(), token.position.clone()));
+                            }
+                            symbols.push(symbol);
+                        }
+                        text.push(placeholder);
+                    }
+                    _ => text.push(token.text.to_string()),
+                },
+                Some(token) => {
+                    valid = false;
+                    diagnostics.report(*failure(
+                        token,
+                        (EXPECTED_OPERAND, "expected-operand"),
+                        format!("expected {}, found `{}`", kind.name(), token.text),
+                        &format!("expected {}", kind.name()),
+                    ));
+                }
+                None if index >= required => break,
+                None => {
+                    valid = false;
+                    diagnostics.report((
+                        end_of_line(&file, number, line),
+                        (EXPECTED_OPERAND, "expected-operand"),
+                        format!("expected {}, found end of line", kind.name()),
+                        format!("`{}` takes {} operands", head.text, required),
+                    ));
+                    break;
+                }
+            }
+        }
+        for token in operands.iter().skip(kinds.len()) {
+            valid = false;
+            diagnostics.report(*failure(
+                token,
+                (UNEXPECTED_OPERAND, "unexpected-operand"),
+                format!("unexpected operand `{}`", token.text),
+                &format!("`{}` takes {} operands", head.text, kinds.len()),
+            ));
+        }
+        if !valid {
+            continue;
+        }
+
+        let mut ir = Ir::try_from(text.join(" ")).expect("operands were validated");
+        let symbols: Vec<(usize, String)> = symbols
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, symbol)| Some((index, symbol?)))
+            .collect();
+        if let (Ir::Instruction(instruction), false) = (&ir, symbols.is_empty()) {
+            ir = Ir::Symbolic(*instruction, symbols);
+        }
+        for label in pending.drain(..).rev() {
+            ir = Ir::Labeled(label, Box::new(ir));
+        }
+        assembler.add_ir(ir);
+    }
+
+    for label in pending {
+        assembler.add_ir(Ir::Label(label));
+    }
+
+    for (label, position) in references {
+        if !labels.contains_key(&label) {
+            diagnostics.report((
+                position,
+                (UNDEFINED_LABEL, "undefined-label"),
+                format!("cannot find label `{}`", label),
+                "not defined in this file".to_string(),
+            ));
+        }
+    }
+
+    if diagnostics.failed {
+        Err(diagnostics.error)
+    } else {
+        Ok(())
+    }
+}
+
+enum Directive {
+    Entry(String, Position),
+    Global(String, Position),
+    Extern(String, Position),
+    Data(Vec<Data>),
+}
+
+fn directive(head: &Token, operands: &[&Token]) -> Result<Directive, Failure> {
+    let expect = |count: usize| -> Result<(), Failure> {
+        if let Some(token) = operands.get(count) {
+            return Err(failure(
+                token,
+                (UNEXPECTED_OPERAND, "unexpected-operand"),
+                format!("unexpected operand `{}`", token.text),
+                &format!("`{}` takes {} operands", head.text, count),
+            ));
+        }
+        if operands.len() < count {
+            return Err(failure(
+                head,
+                (EXPECTED_OPERAND, "expected-operand"),
+                format!("`{}` expects {} operands", head.text, count),
+                "missing operands",
+            ));
+        }
+        Ok(())
+    };
+    let expected = |token: &Token, kind: &str| -> Failure {
+        failure(
+            token,
+            (EXPECTED_OPERAND, "expected-operand"),
+            format!("expected {}, found `{}`", kind, token.text),
+            &format!("expected {}", kind),
+        )
+    };
+    match head.text {
+        ".entry" | ".global" | ".extern" => {
+            expect(1)?;
+            let label = operands[0];
+            if !is_label(label.text) {
+                return Err(expected(label, "label"));
+            }
+            let (label, position) = (label.text.to_string(), label.position.clone());
+            Ok(match head.text {
+                ".entry" => Directive::Entry(label, position),
+                ".global" => Directive::Global(label, position),
+                _ => Directive::Extern(label, position),
+            })
+        }
+        ".word" => {
+            if operands.is_empty() {
+                expect(1)?;
+            }
+            let words = operands
+                .iter()
+                .map(|token| word(token.text).ok_or_else(|| expected(token, "word")))
+                .collect::<Result<Vec<u32>, Failure>>()?;
+            Ok(Directive::Data(vec![Data::Words(words)]))
+        }
+        ".byte" => {
+            if operands.is_empty() {
+                expect(1)?;
+            }
+            let bytes = operands
+                .iter()
+                .map(|token| byte(token.text).ok_or_else(|| expected(token, "byte")))
+                .collect::<Result<Vec<u8>, Failure>>()?;
+            Ok(Directive::Data(vec![Data::Bytes(bytes)]))
+        }
+        ".string" => {
+            expect(1)?;
+            let text = string(operands[0].text).ok_or_else(|| expected(operands[0], "string literal"))?;
+            Ok(Directive::Data(vec![Data::Bytes(text)]))
+        }
+        ".zero" => {
+            expect(1)?;
+            // more bytes than the 32 bit adress space holds could never be loaded
+            let count = operands[0]
+                .text
+                .parse::<u32>()
+                .map_err(|_| expected(operands[0], "count"))?;
+            Ok(Directive::Data(vec![Data::Bytes(vec![0; count as usize])]))
+        }
+        ".align" => {
+            expect(1)?;
+            let alignment = match operands[0].text.parse::<u32>() {
+                Ok(alignment) if alignment > 0 => alignment,
+                _ => return Err(expected(operands[0], "positive alignment")),
+            };
+            Ok(Directive::Data(vec![Data::Align(alignment)]))
+        }
+        _ => Err(failure(
+            head,
+            (UNKNOWN_DIRECTIVE, "unknown-directive"),
+            format!("unknown directive `{}`", head.text),
+            "not a directive",
+        )),
+    }
+}
@@ -0,0 +1,421 @@
+//! a debugger on top of `Interpreter::tick`: breakpoints at adresses or labels, single steps,
+//! stepping over calls, watches on registers and memory words, and views of the registers,
+//! flags, stack and code around `PC`. `command` runs the commands of `plac debug`
+
+use super::asm::*;
+use super::interpreter::*;
+use super::object::{Section, Symbol};
+use super::INSTRUCTION_SIZE;
+use crate::Name;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Register(Register),
+    /// the word at the adress
+    Memory(u32),
+}
+
+/// why a step, `step_over` or `run` returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// the instruction was executed and nothing else happened
+    Step,
+    /// `PC` reached a breakpoint, carries the adress
+    Breakpoint(u32),
+    /// a watched value changed, `None` when the memory could not be read
+    Changed {
+        watch: Watch,
+        old: Option<u32>,
+        new: Option<u32>,
+    },
+    /// the program halted, faulted without a trap handler or exhausted a limit
+    Trap(Trap),
+}
+
+#[derive(Debug, Clone)]
+pub struct Debugger<M = BTreeMap<u32, MemoryNode>, I = Console> {
+    pub interpreter: Interpreter<M, I>,
+    /// the code and data labels of the program
+    pub labels: BTreeMap<Name, u32>,
+    breakpoints: BTreeSet<u32>,
+    /// every watch with its value after the last step
+    watches: Vec<(Watch, Option<u32>)>,
+}
+
+/// the most instructions `list` shows on either side of `PC`
+pub const LIST_LIMIT: u32 = 64;
+
+const HELP: &str = "\
+s, step [n]        execute n instructions, 1 without
+n, next            execute one instruction, a call runs until it returns
+c, continue        run until a breakpoint, a watch or a trap
+p, back [n]        undo n instructions, 1 without
+rewind <at>        undo up to the last write to an adress or label
+b, break <at>      stop when PC reaches an adress or label
+d, delete <at>     remove a breakpoint
+w, watch <what>    stop when a register or the word at an adress or label changes
+u, unwatch <what>  remove a watch
+r, registers       print the registers
+f, flags           print the set flags
+k, stack [n]       print n words from SP up, 8 without
+l, list [n]        disassemble n instructions before and after PC, 4 without, 64 at most
+q, quit            leave the debugger
+";
+
+impl<M: Memory, I: Io> Debugger<M, I> {
+    pub fn new(interpreter: Interpreter<M, I>) -> Debugger<M, I> {
+        Debugger {
+            interpreter,
+            labels: BTreeMap::new(),
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    /// makes the code and data symbols of the loaded object usable as labels
+    pub fn add_symbols(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            if symbol.section != Section::Absolute {
+                self.labels.insert(symbol.name.clone(), symbol.adress);
+            }
+        }
+    }
+
+    /// an adress, in decimal or with `0x`, or a label
+    pub fn resolve(&self, location: &str) -> Option<u32> {
+        match location.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => location
+                .parse()
+                .ok()
+                .or_else(|| self.labels.get(location).copied()),
+        }
+    }
+
+    /// the label at `adress`, one the assembler did not make up when there are several
+    pub fn label(&self, adress: u32) -> Option<&str> {
+        let mut labels = self
+            .labels
+            .iter()
+            .filter(|(_, label)| **label == adress)
+            .map(|(name, _)| name.as_str());
+        let first = labels.next()?;
+        Some(
+            labels.fold(first, |label, name| match label.starts_with('.') {
+                true => name,
+                false => label,
+            }),
+        )
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.register(Register::ProgramCounter)
+    }
+
+    fn register(&self, register: Register) -> u32 {
+        self.interpreter
+            .registers
+            .get(&register)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn break_at(&mut self, adress: u32) {
+        self.breakpoints.insert(adress);
+    }
+
+    /// whether there was a breakpoint at `adress`
+    pub fn delete(&mut self, adress: u32) -> bool {
+        self.breakpoints.remove(&adress)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    fn value(&self, watch: Watch) -> Option<u32> {
+        match watch {
+            Watch::Register(register) => Some(self.register(register)),
+            Watch::Memory(adress) => self.interpreter.read(adress, Width::Word).ok(),
+        }
+    }
+
+    pub fn watch(&mut self, watch: Watch) {
+        if !self.watches.iter().any(|(watched, _)| *watched == watch) {
+            self.watches.push((watch, self.value(watch)));
+        }
+    }
+
+    /// whether `watch` was watched
+    pub fn unwatch(&mut self, watch: Watch) -> bool {
+        let watches = self.watches.len();
+        self.watches.retain(|(watched, _)| *watched != watch);
+        self.watches.len() != watches
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = Watch> + '_ {
+        self.watches.iter().map(|(watch, _)| *watch)
+    }
+
+    /// executes one instruction. a change of a watch is reported before a breakpoint
+    pub fn step(&mut self) -> Stop {
+        if let Err(trap) = self.interpreter.tick() {
+            return Stop::Trap(trap);
+        }
+        let mut changed = None;
+        for index in 0..self.watches.len() {
+            let (watch, old) = self.watches[index];
+            let new = self.value(watch);
+            self.watches[index].1 = new;
+            if new != old && changed.is_none() {
+                changed = Some(Stop::Changed { watch, old, new });
+            }
+        }
+        let pc = self.pc();
+        match changed {
+            Some(stop) => stop,
+            None if self.breakpoints.contains(&pc) => Stop::Breakpoint(pc),
+            None => Stop::Step,
+        }
+    }
+
+    /// like `step`, but a call that is taken runs until it returns
+    pub fn step_over(&mut self) -> Stop {
+        let pc = self.pc();
+        let sp = self.register(Register::StackPointer);
+        let call = matches!(
+            self.interpreter.get(pc),
+            MemoryNode::Instruction(Instruction::Call(..) | Instruction::CallNot(..))
+        );
+        let next = pc.wrapping_add(INSTRUCTION_SIZE as u32);
+        let mut stop = self.step();
+        while call
+            && stop == Stop::Step
+            && !(self.pc() == next && self.register(Register::StackPointer) >= sp)
+        {
+            stop = self.step();
+        }
+        stop
+    }
+
+    /// undoes the last tick, which needs `Interpreter::history`. `false` when there is none
+    pub fn step_back(&mut self) -> bool {
+        let stepped = self.interpreter.step_back();
+        self.refresh();
+        stepped
+    }
+
+    /// undoes the ticks up to the last write to `adress`, their number
+    pub fn rewind_to_write(&mut self, adress: u32) -> Option<usize> {
+        let ticks = self.interpreter.rewind_to_write(adress);
+        self.refresh();
+        ticks
+    }
+
+    /// takes the current values of the watches as their old ones
+    fn refresh(&mut self) {
+        for index in 0..self.watches.len() {
+            self.watches[index].1 = self.value(self.watches[index].0);
+        }
+    }
+
+    /// steps until something other than `Stop::Step` happens
+    pub fn run(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Step => (),
+                stop => return stop,
+            }
+        }
+    }
+
+    /// every register with its value in hex, unsigned and signed
+    pub fn registers(&self) -> String {
+        Register::ALL
+            .iter()
+            .map(|register| {
+                let value = self.register(*register);
+                format!(
+                    "{:<3} {:#010x} {:>10} {:>11}\n",
+                    String::from(*register),
+                    value,
+                    value,
+                    value as i32
+                )
+            })
+            .collect()
+    }
+
+    pub fn flags(&self) -> String {
+        let flags: Vec<String> = self
+            .interpreter
+            .flags
+            .iter()
+            .map(|flag| format!("{:?}", flag))
+            .collect();
+        format!("{}\n", flags.join(" "))
+    }
+
+    /// up to `words` words from `SP` up to the end of the stack
+    pub fn stack(&self, words: u32) -> String {
+        let word = Width::Word.bytes();
+        let sp = self.register(Register::StackPointer);
+        let end = self.interpreter.stack.end;
+        (0..words)
+            .map(|index| sp.wrapping_add(index * word))
+            .take_while(|adress| *adress < end && *adress >= sp)
+            .map(|adress| match self.interpreter.read(adress, Width::Word) {
+                Ok(value) => format!("{:>8}: {:#010x} {}\n", adress, value, value),
+                Err(_) => format!("{:>8}: ?\n", adress),
+            })
+            .collect()
+    }
+
+    /// the instructions from `around` instructions before `PC` to as many after it, up to
+    /// `LIST_LIMIT` and the ends of the adress space. `PC` is marked with `=>` and breakpoints
+    /// with `*`
+    pub fn list(&self, around: u32) -> String {
+        let size = INSTRUCTION_SIZE as u32;
+        let pc = self.pc();
+        let around = around.min(LIST_LIMIT);
+        let start = pc - around.min(pc / size) * size;
+        let end = pc + around.min((u32::MAX - pc) / size) * size;
+        let mut output = String::new();
+        for adress in (start..=end).step_by(size as usize) {
+            let MemoryNode::Instruction(instruction) = self.interpreter.get(adress) else {
+                continue;
+            };
+            if let Some(label) = self.label(adress) {
+                output.push_str(&format!("{}:\n", label));
+            }
+            let marker = match (adress == pc, self.breakpoints.contains(&adress)) {
+                (true, _) => "=>",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            output.push_str(&format!(
+                "{} {:>8}  {}\n",
+                marker,
+                adress,
+                String::from(*instruction)
+            ));
+        }
+        output
+    }
+
+    fn describe(&self, stop: Stop) -> String {
+        let value = |value: Option<u32>| value.map_or("?".to_string(), |value| value.to_string());
+        let stop = match stop {
+            Stop::Step => String::new(),
+            Stop::Breakpoint(adress) => format!("breakpoint at {}\n", adress),
+            Stop::Changed { watch, old, new } => {
+                let watch = match watch {
+                    Watch::Register(register) => String::from(register),
+                    Watch::Memory(adress) => format!("[{}]", adress),
+                };
+                format!("{} changed from {} to {}\n", watch, value(old), value(new))
+            }
+            Stop::Trap(trap) => format!("stopped at {}: {:?}\n", trap.pc, trap.kind),
+        };
+        stop + &self.list(0)
+    }
+
+    fn watch_of(&self, what: &str) -> Option<Watch> {
+        match Register::try_from(what.to_string()) {
+            Ok(register) => Some(Watch::Register(register)),
+            Err(()) => self.resolve(what).map(Watch::Memory),
+        }
+    }
+
+    /// runs one line of the `plac debug` command language, listed by `help`, and returns what
+    /// it prints. quitting is left to the caller
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(String::new());
+        };
+        let argument = words.next();
+        let count = |default: u32| match argument {
+            Some(count) => count
+                .parse()
+                .map_err(|_| format!("expected a number, not `{}`", count)),
+            None => Ok(default),
+        };
+        let location = || {
+            let location = argument.ok_or("expected an adress or label")?;
+            self.resolve(location)
+                .ok_or(format!("no adress or label `{}`", location))
+        };
+        let watch = || {
+            let what = argument.ok_or("expected a register, adress or label")?;
+            self.watch_of(what)
+                .ok_or(format!("no register, adress or label `{}`", what))
+        };
+        Ok(match command {
+            "s" | "step" => {
+                let mut stop = Stop::Step;
+                for _ in 0..count(1)? {
+                    stop = self.step();
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                self.describe(stop)
+            }
+            "p" | "back" => {
+                for _ in 0..count(1)? {
+                    if !self.step_back() {
+                        return Err("nothing to step back over".to_string());
+                    }
+                }
+                self.list(0)
+            }
+            "rewind" => {
+                let adress = location()?;
+                match self.rewind_to_write(adress) {
+                    Some(_) => self.list(0),
+                    None => return Err(format!("no write to {} in the history", adress)),
+                }
+            }
+            "n" | "next" => {
+                let stop = self.step_over();
+                self.describe(stop)
+            }
+            "c" | "continue" => {
+                let stop = self.run();
+                self.describe(stop)
+            }
+            "b" | "break" => {
+                let adress = location()?;
+                self.break_at(adress);
+                format!("breakpoint at {}\n", adress)
+            }
+            "d" | "delete" => {
+                let adress = location()?;
+                match self.delete(adress) {
+                    true => String::new(),
+                    false => return Err(format!("no breakpoint at {}", adress)),
+                }
+            }
+            "w" | "watch" => {
+                let watch = watch()?;
+                self.watch(watch);
+                String::new()
+            }
+            "u" | "unwatch" => {
+                let watch = watch()?;
+                match self.unwatch(watch) {
+                    true => String::new(),
+                    false => return Err(format!("{} is not watched", argument.unwrap())),
+                }
+            }
+            "r" | "registers" => self.registers(),
+            "f" | "flags" => self.flags(),
+            "k" | "stack" => self.stack(count(8)?),
+            "l" | "list" => self.list(count(4)?),
+            "h" | "help" => HELP.to_string(),
+            _ => return Err(format!("unknown command `{}`, try `help`", command)),
+        })
+    }
+}
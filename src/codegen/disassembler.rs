@@ -0,0 +1,176 @@
+use super::asm::*;
+use super::interpreter::Interpreter;
+use super::memory::{Memory, MemoryNode};
+use super::object::{Object, Section};
+use super::INSTRUCTION_SIZE;
+use std::collections::{BTreeMap, HashSet};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// rebuilds the loaded program from memory: everything before the first instruction is
+/// data, followed by the instructions up to the first node that is not one.
+/// memory after the code, e.g. the stack, is not part of the program
+pub fn from_memory(memory: &impl Memory, entry: u32) -> Object {
+    let start = memory
+        .nodes()
+        .find(|(_, node)| matches!(node, MemoryNode::Instruction(_)))
+        .map(|(adress, _)| adress)
+        .unwrap_or_else(|| memory.nodes().last().map_or(0, |(last, _)| last + 1));
+    let data = (0..start)
+        .map(|adress| match memory.get(adress) {
+            Some(MemoryNode::Data(value)) => *value,
+            _ => 0,
+        })
+        .collect();
+    let mut code = Vec::new();
+    let mut adress = start;
+    while let Some(MemoryNode::Instruction(instruction)) = memory.get(adress) {
+        code.push(*instruction);
+        adress += INSTRUCTION_SIZE as u32;
+    }
+    Object {
+        data,
+        code,
+        entry,
+        symbols: Vec::new(),
+        relocations: Vec::new(),
+    }
+}
+
+pub fn disassemble_interpreter<M: Memory, I>(interpreter: &Interpreter<M, I>) -> String {
+    let entry = interpreter
+        .registers
+        .get(&Register::ProgramCounter)
+        .copied()
+        .unwrap_or(0);
+    disassemble(&from_memory(&interpreter.memory, entry))
+}
+
+struct Labels {
+    names: BTreeMap<u32, String>,
+    taken: HashSet<String>,
+}
+
+impl Labels {
+    fn insert(&mut self, adress: u32, name: String) {
+        if self.names.contains_key(&adress) {
+            return;
+        }
+        let mut unique = name.clone();
+        while self.taken.contains(&unique) || self.taken.contains(&format!("{}_len", unique)) {
+            unique.push('_');
+        }
+        self.taken.insert(unique.clone());
+        self.names.insert(adress, unique);
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && Register::try_from(name.to_string()).is_err()
+}
+
+/// emits assembly that assembles back to exactly `object`: the data as `.byte`s and the
+/// code with every jump target and the entry replaced by a label. symbol names are reused
+/// where possible, other labels are named after their adress
+pub fn disassemble(object: &Object) -> String {
+    let start = object.data.len() as u32;
+    let end = start + (object.code.len() * INSTRUCTION_SIZE) as u32;
+    let is_code = |adress: u32| {
+        adress >= start && adress < end && (adress - start).is_multiple_of(INSTRUCTION_SIZE as u32)
+    };
+
+    let mut data = Labels {
+        names: BTreeMap::new(),
+        taken: HashSet::new(),
+    };
+    let mut symbols = object.symbols.clone();
+    symbols.sort_by_key(|symbol| symbol.name.starts_with('.'));
+    for symbol in &symbols {
+        if symbol.section == Section::Data && symbol.adress < start && is_name(&symbol.name) {
+            data.insert(symbol.adress, symbol.name.clone());
+        }
+    }
+
+    let mut code = Labels {
+        names: BTreeMap::new(),
+        taken: data.taken.clone(),
+    };
+    let mut targets = vec![object.entry];
+    for instruction in &object.code {
+        if let Instruction::Jump(_, Value::Direct(Operand::U32(adress)))
+        | Instruction::JumpNot(_, Value::Direct(Operand::U32(adress)))
+        | Instruction::Call(_, Value::Direct(Operand::U32(adress)))
+        | Instruction::CallNot(_, Value::Direct(Operand::U32(adress))) = instruction
+        {
+            targets.push(*adress);
+        }
+    }
+    targets.retain(|adress| is_code(*adress));
+    for adress in &targets {
+        let symbol = symbols.iter().find(|symbol| {
+            symbol.section == Section::Code && symbol.adress == *adress && is_name(&symbol.name)
+        });
+        match symbol {
+            Some(symbol) => code.insert(*adress, symbol.name.clone()),
+            None => code.insert(*adress, format!("L{}", adress)),
+        }
+    }
+
+    let mut output = String::new();
+    let mut adress = 0;
+    let mut boundaries: Vec<u32> = data.names.keys().copied().collect();
+    boundaries.push(start);
+    for boundary in boundaries {
+        for chunk in object.data[adress as usize..boundary as usize].chunks(BYTES_PER_LINE) {
+            let bytes: Vec<String> = chunk.iter().map(u8::to_string).collect();
+            output.push_str(&format!("    .byte {}\n", bytes.join(" ")));
+        }
+        adress = boundary;
+        if let Some(name) = data.names.get(&boundary) {
+            output.push_str(&format!("{}:\n", name));
+        }
+    }
+    if !data.names.is_empty() || !object.data.is_empty() {
+        output.push('\n');
+    }
+
+    for (index, instruction) in object.code.iter().enumerate() {
+        let adress = start + (index * INSTRUCTION_SIZE) as u32;
+        if let Some(name) = code.names.get(&adress) {
+            output.push_str(&format!("{}:\n", name));
+        }
+        let line = match instruction {
+            Instruction::Jump(flag, Value::Direct(Operand::U32(target)))
+            | Instruction::JumpNot(flag, Value::Direct(Operand::U32(target)))
+                if code.names.contains_key(target) =>
+            {
+                let mnemonic = match instruction {
+                    Instruction::Jump(..) => "jmp:",
+                    _ => "jmpn:",
+                };
+                format!("{} {} {}", mnemonic, String::from(*flag), code.names[target])
+            }
+            Instruction::Call(flag, Value::Direct(Operand::U32(target)))
+            | Instruction::CallNot(flag, Value::Direct(Operand::U32(target)))
+                if code.names.contains_key(target) =>
+            {
+                let mnemonic = match instruction {
+                    Instruction::Call(..) => "call",
+                    _ => "calln",
+                };
+                format!("{} {} {}", mnemonic, String::from(*flag), code.names[target])
+            }
+            instruction => String::from(*instruction),
+        };
+        output.push_str(&format!("    {}\n", line));
+    }
+    if let Some(name) = code.names.get(&object.entry) {
+        output.push_str(&format!(".entry {}\n", name));
+    }
+    output
+}
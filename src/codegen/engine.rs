@@ -0,0 +1,485 @@
+//! a faster way to run an `Interpreter`. the code is decoded once into `Op`s whose operands
+//! are register indices, registers live in an array and flags in a bit set. it behaves exactly
+//! like `Interpreter::execute`, the registers and flags are written back when it stops
+
+use super::asm::*;
+use super::interpreter::*;
+use super::interpreter::{compare, convert, resumes, sign, sign_extend, zero_extend};
+use super::interpreter::{Arithmetic, Machine, ADD, DIV, MOD, MUL, SUB};
+use super::INSTRUCTION_SIZE;
+use std::cmp::Ordering;
+
+const PC: usize = Register::ProgramCounter as usize;
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Constant(u32),
+    Register(u8),
+}
+
+/// a decoded `Value`
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Constant(u32),
+    Register(u8),
+    Sum(Slot, Slot),
+    /// the word at adress a + b
+    Word(Slot, Slot),
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Swap(u8, u8),
+    Arithmetic(&'static Arithmetic, u8, Source, Source, MathType),
+    Bitwise(fn(u32, u32) -> u32, u8, Source, Source),
+    Compare(Source, Source, MathType),
+    Convert(u8, Source, MathType, MathType),
+    SignExtend(u8, Source, Width),
+    ZeroExtend(u8, Source, Width),
+    /// jumps when the flags intersect the mask, or don't with `false`. the last field is
+    /// whether the jump leaves the trap handler
+    Jump(u8, bool, Source, bool),
+    Load(u8, Source),
+    LoadMemory(u8, Source, Width),
+    Store(Source, Source, Width),
+    Push(Source),
+    Pop(u8),
+    Call(u8, bool, Source),
+    Ret,
+    /// left undecoded, the syscalls are shared with `Interpreter`
+    SysCall(SysCall),
+    Halt,
+    /// not an instruction
+    Invalid,
+}
+
+fn register(register: Register) -> u8 {
+    register as u8
+}
+
+fn flag(flag: Flag) -> u8 {
+    1 << flag as u8
+}
+
+impl From<Operand> for Slot {
+    fn from(operand: Operand) -> Slot {
+        match operand {
+            Operand::U32(value) => Slot::Constant(value),
+            Operand::Register(reg) => Slot::Register(register(reg)),
+        }
+    }
+}
+
+impl From<Value> for Source {
+    fn from(value: Value) -> Source {
+        match value {
+            Value::Direct(Operand::U32(value)) => Source::Constant(value),
+            Value::Direct(Operand::Register(reg)) => Source::Register(register(reg)),
+            Value::Indirect(operand) => Source::Word(operand.into(), Slot::Constant(0)),
+            Value::OperationalDirect(Operand::U32(a), Operand::U32(b)) => {
+                Source::Constant(a.wrapping_add(b))
+            }
+            Value::OperationalDirect(a, b) => Source::Sum(a.into(), b.into()),
+            Value::OperationalIndirect(a, b) => Source::Word(a.into(), b.into()),
+        }
+    }
+}
+
+impl From<Instruction> for Op {
+    fn from(instruction: Instruction) -> Op {
+        let bitwise = |operation, reg, value1: Value, value2: Value| {
+            Op::Bitwise(operation, register(reg), value1.into(), value2.into())
+        };
+        let arithmetic = |operation, reg, value1: Value, value2: Value, math_type| {
+            Op::Arithmetic(
+                operation,
+                register(reg),
+                value1.into(),
+                value2.into(),
+                math_type,
+            )
+        };
+        match instruction {
+            Instruction::Swap(reg1, reg2) => Op::Swap(register(reg1), register(reg2)),
+            Instruction::SysCall(syscall) => Op::SysCall(syscall),
+            Instruction::Add(reg, value1, value2, math_type) => {
+                arithmetic(&ADD, reg, value1, value2, math_type)
+            }
+            Instruction::Sub(reg, value1, value2, math_type) => {
+                arithmetic(&SUB, reg, value1, value2, math_type)
+            }
+            Instruction::Mul(reg, value1, value2, math_type) => {
+                arithmetic(&MUL, reg, value1, value2, math_type)
+            }
+            Instruction::Div(reg, value1, value2, math_type) => {
+                arithmetic(&DIV, reg, value1, value2, math_type)
+            }
+            Instruction::Mod(reg, value1, value2, math_type) => {
+                arithmetic(&MOD, reg, value1, value2, math_type)
+            }
+            Instruction::And(reg, value1, value2) => bitwise(|a, b| a & b, reg, value1, value2),
+            Instruction::Or(reg, value1, value2) => bitwise(|a, b| a | b, reg, value1, value2),
+            Instruction::Xor(reg, value1, value2) => bitwise(|a, b| a ^ b, reg, value1, value2),
+            Instruction::Not(reg, value) => {
+                bitwise(|a, _| !a, reg, value, Value::Direct(Operand::U32(0)))
+            }
+            Instruction::ShiftLeft(reg, value1, value2) => {
+                bitwise(|a, b| a.checked_shl(b).unwrap_or(0), reg, value1, value2)
+            }
+            Instruction::ShiftRight(reg, value1, value2) => {
+                bitwise(|a, b| a.checked_shr(b).unwrap_or(0), reg, value1, value2)
+            }
+            Instruction::Compare(value1, value2, math_type) => {
+                Op::Compare(value1.into(), value2.into(), math_type)
+            }
+            Instruction::Convert(reg, value, from, to) => {
+                Op::Convert(register(reg), value.into(), from, to)
+            }
+            Instruction::SignExtend(reg, value, width) => {
+                Op::SignExtend(register(reg), value.into(), width)
+            }
+            Instruction::ZeroExtend(reg, value, width) => {
+                Op::ZeroExtend(register(reg), value.into(), width)
+            }
+            Instruction::Jump(condition, value) => {
+                Op::Jump(flag(condition), true, value.into(), resumes(&value))
+            }
+            Instruction::JumpNot(condition, value) => {
+                Op::Jump(flag(condition), false, value.into(), resumes(&value))
+            }
+            Instruction::Load(reg, value) => Op::Load(register(reg), value.into()),
+            Instruction::Move(value1, value2) => {
+                Op::Store(value1.into(), value2.into(), Width::Word)
+            }
+            Instruction::LoadMemory(reg, value, width) => {
+                Op::LoadMemory(register(reg), value.into(), width)
+            }
+            Instruction::Store(value1, value2, width) => {
+                Op::Store(value1.into(), value2.into(), width)
+            }
+            Instruction::Push(value) => Op::Push(value.into()),
+            Instruction::Pop(reg) => Op::Pop(register(reg)),
+            Instruction::Call(condition, value) => Op::Call(flag(condition), true, value.into()),
+            Instruction::CallNot(condition, value) => {
+                Op::Call(flag(condition), false, value.into())
+            }
+            Instruction::Ret => Op::Ret,
+            Instruction::Halt => Op::Halt,
+        }
+    }
+}
+
+fn decode(node: Option<&MemoryNode>) -> Op {
+    match node {
+        Some(MemoryNode::Instruction(instruction)) => Op::from(*instruction),
+        _ => Op::Invalid,
+    }
+}
+
+pub struct Engine<'a, M: Memory, I: Io> {
+    interpreter: &'a mut Interpreter<M, I>,
+    registers: [u32; Register::ALL.len()],
+    flags: u8,
+    /// the adress of `ops[0]`
+    start: u32,
+    /// one op per `INSTRUCTION_SIZE` bytes from the first to the last instruction in memory,
+    /// decoded again whenever the program writes to them
+    ops: Vec<Op>,
+    /// `Limits::instructions`
+    instructions: u64,
+}
+
+impl<'a, M: Memory, I: Io> Engine<'a, M, I> {
+    pub fn new(interpreter: &'a mut Interpreter<M, I>) -> Engine<'a, M, I> {
+        let mut registers = [0; Register::ALL.len()];
+        for (register, value) in &interpreter.registers {
+            registers[*register as usize] = *value;
+        }
+        let flags = interpreter
+            .flags
+            .iter()
+            .fold(0, |flags, f| flags | flag(*f));
+        let mut instructions = interpreter
+            .memory
+            .nodes()
+            .filter(|(_, node)| matches!(node, MemoryNode::Instruction(_)))
+            .map(|(adress, _)| adress);
+        let start = instructions.next().unwrap_or(0);
+        let end = instructions.last().unwrap_or(start);
+        let ops = (start..=end)
+            .step_by(INSTRUCTION_SIZE)
+            .map(|adress| decode(interpreter.memory.get(adress)))
+            .collect();
+        let instructions = interpreter.limits.instructions.unwrap_or(u64::MAX);
+        Engine {
+            interpreter,
+            registers,
+            flags,
+            start,
+            ops,
+            instructions,
+        }
+    }
+
+    /// runs until the program halts or faults, like `Interpreter::execute`. nothing is traced
+    /// or recorded in `Interpreter::history`, which is cleared
+    pub fn execute(&mut self) -> Trap {
+        let mut history = self.interpreter.history.take();
+        if let Some(history) = &mut history {
+            history.clear();
+        }
+        self.flags |= flag(Flag::Always);
+        let trap = loop {
+            if let Err(trap) = self.tick() {
+                break trap;
+            }
+        };
+        self.write_back();
+        self.interpreter.history = history;
+        trap
+    }
+
+    fn write_back(&mut self) {
+        for register in Register::ALL {
+            let value = self.registers[register as usize];
+            if value != 0 || self.interpreter.registers.contains_key(&register) {
+                self.interpreter.registers.insert(register, value);
+            }
+        }
+        self.interpreter.flags = Flag::ALL
+            .into_iter()
+            .filter(|f| self.flags & flag(*f) != 0)
+            .collect();
+    }
+
+    fn index(&self, adress: u32) -> Option<usize> {
+        let offset = adress.wrapping_sub(self.start);
+        let index = (offset / INSTRUCTION_SIZE as u32) as usize;
+        (offset.is_multiple_of(INSTRUCTION_SIZE as u32) && index < self.ops.len()).then_some(index)
+    }
+
+    fn tick(&mut self) -> Result<(), Trap> {
+        let pc = self.registers[PC];
+        let op = match self.index(pc) {
+            Some(index) => self.ops[index],
+            None => decode(self.interpreter.memory.get(pc)),
+        };
+        if let Op::Invalid = op {
+            return self.deliver(pc, TrapKind::InvalidInstruction);
+        }
+        if self.interpreter.usage.instructions >= self.instructions {
+            let usage = self.interpreter.usage;
+            return self.deliver(pc, TrapKind::Exhausted(Resource::Instructions, usage));
+        }
+        self.interpreter.usage.instructions += 1;
+        self.registers[PC] = pc + INSTRUCTION_SIZE as u32;
+        match self.run(op) {
+            Ok(()) => Ok(()),
+            Err(TrapKind::Exhausted(resource, _)) => {
+                self.interpreter.usage.instructions -= 1;
+                self.registers[PC] = pc;
+                let usage = self.interpreter.usage;
+                self.deliver(pc, TrapKind::Exhausted(resource, usage))
+            }
+            Err(kind) => self.deliver(pc, kind),
+        }
+    }
+
+    /// `Machine::deliver` for the instruction at `pc`
+    fn deliver(&mut self, pc: u32, kind: TrapKind) -> Result<(), Trap> {
+        let instruction = match self.interpreter.memory.get(pc) {
+            Some(MemoryNode::Instruction(instruction)) => Some(*instruction),
+            _ => None,
+        };
+        Machine::deliver(
+            self,
+            Trap {
+                pc,
+                instruction,
+                kind,
+            },
+        )
+    }
+
+    fn slot(&self, slot: Slot) -> u32 {
+        match slot {
+            Slot::Constant(value) => value,
+            Slot::Register(register) => self.registers[register as usize],
+        }
+    }
+
+    fn aquire(&self, source: Source) -> Result<u32, TrapKind> {
+        Ok(match source {
+            Source::Constant(value) => value,
+            Source::Register(register) => self.registers[register as usize],
+            Source::Sum(a, b) => self.slot(a).wrapping_add(self.slot(b)),
+            Source::Word(a, b) => {
+                let adress = self.slot(a).wrapping_add(self.slot(b));
+                self.interpreter.read(adress, Width::Word)?
+            }
+        })
+    }
+
+    fn write(&mut self, adress: u32, value: u32, width: Width) -> Result<(), TrapKind> {
+        self.interpreter.write(adress, value, width)?;
+        self.redecode(adress, width.bytes());
+        Ok(())
+    }
+
+    /// decodes the ops in the `length` bytes from `adress` on again, walking whichever of the
+    /// bytes and the ops are fewer
+    fn redecode(&mut self, adress: u32, length: u32) {
+        if (length as usize) < self.ops.len() {
+            for offset in 0..length {
+                let adress = adress.wrapping_add(offset);
+                if let Some(index) = self.index(adress) {
+                    self.ops[index] = decode(self.interpreter.memory.get(adress));
+                }
+            }
+            return;
+        }
+        for index in 0..self.ops.len() {
+            let op = self.start + (index * INSTRUCTION_SIZE) as u32;
+            if op.wrapping_sub(adress) < length {
+                self.ops[index] = decode(self.interpreter.memory.get(op));
+            }
+        }
+    }
+
+    fn set_ordering(&mut self, ordering: Option<Ordering>) {
+        self.flags &= !(flag(Flag::Lesser) | flag(Flag::Greater) | flag(Flag::Equal));
+        self.flags |= match ordering {
+            Some(Ordering::Less) => flag(Flag::Lesser),
+            Some(Ordering::Greater) => flag(Flag::Greater),
+            Some(Ordering::Equal) => flag(Flag::Equal),
+            None => 0,
+        };
+    }
+
+    fn assign_flag(&mut self, f: Flag, set: bool) {
+        if set {
+            self.flags |= flag(f);
+        } else {
+            self.flags &= !flag(f);
+        }
+    }
+
+    fn set_result(&mut self, result: u32, math_type: MathType, overflow: bool) {
+        self.set_ordering(sign(result, math_type));
+        self.assign_flag(Flag::Overflow, overflow);
+    }
+
+    fn run(&mut self, op: Op) -> Result<(), TrapKind> {
+        match op {
+            Op::Swap(reg1, reg2) => self.registers.swap(reg1 as usize, reg2 as usize),
+            Op::Arithmetic(operation, register, value1, value2, math_type) => {
+                let value = self.aquire(value1)?;
+                let value2 = self.aquire(value2)?;
+                let (result, overflow) = operation.apply(value, value2, math_type)?;
+                if let (true, MathType::Unsigned, Some(kind)) =
+                    (overflow, math_type, operation.trap)
+                {
+                    self.flags |= flag(Flag::Overflow);
+                    return Err(kind);
+                }
+                self.set_result(result, math_type, overflow);
+                self.registers[register as usize] = result;
+            }
+            Op::Bitwise(operation, register, value1, value2) => {
+                let value = self.aquire(value1)?;
+                let value2 = self.aquire(value2)?;
+                let result = operation(value, value2);
+                self.set_result(result, MathType::Unsigned, false);
+                self.registers[register as usize] = result;
+            }
+            Op::Compare(value1, value2, math_type) => {
+                let value = self.aquire(value1)?;
+                let value2 = self.aquire(value2)?;
+                self.set_ordering(compare(value, value2, math_type));
+            }
+            Op::Convert(register, value, from, to) => {
+                let (result, overflow) = convert(self.aquire(value)?, from, to);
+                self.set_result(result, to, overflow);
+                self.registers[register as usize] = result;
+            }
+            Op::SignExtend(register, value, width) => {
+                let result = sign_extend(self.aquire(value)?, width);
+                self.set_result(result, MathType::Signed, false);
+                self.registers[register as usize] = result;
+            }
+            Op::ZeroExtend(register, value, width) => {
+                let result = zero_extend(self.aquire(value)?, width);
+                self.set_result(result, MathType::Unsigned, false);
+                self.registers[register as usize] = result;
+            }
+            Op::Jump(mask, when, target, resumes) => {
+                if (self.flags & mask != 0) == when {
+                    self.registers[PC] = self.aquire(target)?;
+                    if resumes {
+                        self.interpreter.in_handler = false;
+                    }
+                }
+            }
+            Op::Load(register, value) => {
+                self.registers[register as usize] = self.aquire(value)?;
+            }
+            Op::LoadMemory(register, value, width) => {
+                let adress = self.aquire(value)?;
+                self.registers[register as usize] = self.interpreter.read(adress, width)?;
+            }
+            Op::Store(value1, value2, width) => {
+                let adress = self.aquire(value1)?;
+                let value = self.aquire(value2)?;
+                self.write(adress, value, width)?;
+            }
+            Op::Push(value) => {
+                let value = self.aquire(value)?;
+                self.push(value)?;
+            }
+            Op::Pop(register) => {
+                let value = self.pop()?;
+                self.registers[register as usize] = value;
+            }
+            Op::Call(mask, when, target) => {
+                if (self.flags & mask != 0) == when {
+                    let target = self.aquire(target)?;
+                    self.push(self.registers[PC])?;
+                    self.registers[PC] = target;
+                }
+            }
+            Op::Ret => {
+                self.registers[PC] = self.pop()?;
+            }
+            Op::SysCall(syscall) => self.syscall(&syscall)?,
+            Op::Halt => return Err(TrapKind::Halt(self.registers[Register::R0 as usize])),
+            Op::Invalid => return Err(TrapKind::InvalidInstruction),
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory, I: Io> Machine<M, I> for Engine<'_, M, I> {
+    fn interpreter(&mut self) -> &mut Interpreter<M, I> {
+        self.interpreter
+    }
+
+    fn register(&mut self, register: Register) -> u32 {
+        self.registers[register as usize]
+    }
+
+    fn set_register(&mut self, register: Register, value: u32) {
+        self.registers[register as usize] = value;
+    }
+
+    fn assign_flag(&mut self, flag: Flag, set: bool) {
+        Engine::assign_flag(self, flag, set);
+    }
+
+    fn value(&mut self, value: &Value) -> Result<u32, TrapKind> {
+        Engine::aquire(self, Source::from(*value))
+    }
+
+    fn written(&mut self, start: u32, length: u32) {
+        self.redecode(start, length);
+    }
+}
@@ -0,0 +1,248 @@
+//! the files a guest program opens with the file syscalls. paths are relative to a root
+//! directory the embedder chooses, without one nothing can be opened
+
+use std::collections::BTreeMap;
+use std::fs::File;
+#[cfg(not(unix))]
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// what the file syscalls return in their register when they fail
+pub const FAILED: u32 = u32::MAX;
+/// file descriptors below this are the `Io` of the interpreter: 0 is its input,
+/// 1 and 2 its output
+pub const FIRST_FILE: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Read,
+    /// creates the file, or empties it
+    Write,
+    /// creates the file, or writes at its end
+    Append,
+}
+
+impl TryFrom<u32> for Mode {
+    type Error = ();
+    fn try_from(mode: u32) -> Result<Mode, ()> {
+        Ok(match mode {
+            0 => Mode::Read,
+            1 => Mode::Write,
+            2 => Mode::Append,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Handle {
+    path: PathBuf,
+    mode: Mode,
+    /// clones of the interpreter share the file and its position
+    file: Arc<File>,
+}
+
+impl PartialEq for Handle {
+    fn eq(&self, other: &Handle) -> bool {
+        self.path == other.path && self.mode == other.mode
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Files {
+    root: Option<PathBuf>,
+    open: BTreeMap<u32, Handle>,
+}
+
+impl Files {
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// fails when `root` is not a directory
+    pub fn set_root(&mut self, root: impl AsRef<Path>) -> std::io::Result<()> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(std::io::ErrorKind::NotADirectory.into());
+        }
+        self.root = Some(root);
+        Ok(())
+    }
+
+    /// where `path` is on the host, `None` when it is not a relative path that stays inside
+    /// the root, also through symbolic links. `open` does not follow symbolic links at all
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        let path = Path::new(path);
+        let normal = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !normal || path.file_name().is_none() {
+            return None;
+        }
+        let full = root.join(path);
+        let inside = match full.canonicalize() {
+            Ok(target) => target.starts_with(root),
+            // a file that does not exist yet, its directory has to
+            Err(_) => {
+                full.symlink_metadata().is_err()
+                    && full.parent()?.canonicalize().ok()?.starts_with(root)
+            }
+        };
+        inside.then_some(full)
+    }
+
+    /// the file descriptor of the opened file. only regular files are opened, and a file is
+    /// only emptied once that is known
+    pub fn open(&mut self, path: &[u8], mode: u32) -> Option<u32> {
+        let mode = Mode::try_from(mode).ok()?;
+        let path = self.resolve(std::str::from_utf8(path).ok()?)?;
+        let root = self.root.as_ref()?;
+        let file = open_beneath(root, path.strip_prefix(root).ok()?, mode, true).ok()?;
+        if !file.metadata().ok()?.is_file() {
+            return None;
+        }
+        if mode == Mode::Write {
+            file.set_len(0).ok()?;
+        }
+        let fd = (FIRST_FILE..).find(|fd| !self.open.contains_key(fd))?;
+        let file = Arc::new(file);
+        self.open.insert(fd, Handle { path, mode, file });
+        Some(fd)
+    }
+
+    /// up to `length` bytes, fewer at the end of the file
+    pub fn read(&mut self, fd: u32, length: u32) -> Option<Vec<u8>> {
+        let handle = self
+            .open
+            .get(&fd)
+            .filter(|handle| handle.mode == Mode::Read)?;
+        let mut bytes = Vec::new();
+        (&*handle.file)
+            .take(length as u64)
+            .read_to_end(&mut bytes)
+            .ok()?;
+        Some(bytes)
+    }
+
+    /// the number of bytes written
+    pub fn write(&mut self, fd: u32, bytes: &[u8]) -> Option<u32> {
+        let handle = self
+            .open
+            .get(&fd)
+            .filter(|handle| handle.mode != Mode::Read)?;
+        (&*handle.file).write_all(bytes).ok()?;
+        Some(bytes.len() as u32)
+    }
+
+    /// whether `fd` was open
+    pub fn close(&mut self, fd: u32) -> bool {
+        self.open.remove(&fd).is_some()
+    }
+
+    /// every open file by descriptor, with its path relative to the root, its mode and position
+    pub fn handles(&self) -> std::io::Result<Vec<(u32, PathBuf, Mode, u64)>> {
+        self.open
+            .iter()
+            .map(|(fd, handle)| {
+                let position = (&*handle.file).stream_position()?;
+                let root = self.root.as_deref().unwrap_or(Path::new(""));
+                let path = handle.path.strip_prefix(root).unwrap_or(&handle.path);
+                Ok((*fd, path.to_path_buf(), handle.mode, position))
+            })
+            .collect()
+    }
+
+    /// opens the file at `path` in the root as `fd` again, at `position`. unlike `open` it is
+    /// not created or emptied. fails with `PermissionDenied` when `open` would not accept `path`
+    pub fn reopen(
+        &mut self,
+        fd: u32,
+        path: &str,
+        mode: Mode,
+        position: u64,
+    ) -> std::io::Result<()> {
+        let denied = std::io::ErrorKind::PermissionDenied;
+        let path = self.resolve(path).ok_or(denied)?;
+        let root = self.root.as_ref().ok_or(denied)?;
+        let mut file = open_beneath(
+            root,
+            path.strip_prefix(root).map_err(|_| denied)?,
+            mode,
+            false,
+        )?;
+        if !file.metadata()?.is_file() {
+            return Err(denied.into());
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let file = Arc::new(file);
+        self.open.insert(fd, Handle { path, mode, file });
+        Ok(())
+    }
+
+    /// the path of every open file, by descriptor
+    pub fn paths(&self) -> impl Iterator<Item = (u32, &Path)> {
+        self.open
+            .iter()
+            .map(|(fd, handle)| (*fd, handle.path.as_path()))
+    }
+}
+
+/// opens `path` in `root` a component at a time without following symbolic links, so nothing
+/// swapped in after `Files::resolve` looked at the path can lead out of the root. a FIFO is
+/// opened without waiting for its other end. `Mode::Write` does not empty the file, a missing
+/// file is only created with `create`
+#[cfg(unix)]
+fn open_beneath(root: &Path, path: &Path, mode: Mode, create: bool) -> std::io::Result<File> {
+    let mut names = path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name),
+        _ => None,
+    });
+    let last = names.next_back().ok_or(std::io::ErrorKind::InvalidInput)?;
+    let mut directory = File::open(root)?;
+    for name in names {
+        directory = open_at(&directory, name, libc::O_RDONLY | libc::O_DIRECTORY)?;
+    }
+    let flags = match mode {
+        Mode::Read => libc::O_RDONLY,
+        Mode::Write if create => libc::O_WRONLY | libc::O_CREAT,
+        Mode::Write => libc::O_WRONLY,
+        Mode::Append if create => libc::O_WRONLY | libc::O_APPEND | libc::O_CREAT,
+        Mode::Append => libc::O_WRONLY | libc::O_APPEND,
+    };
+    open_at(&directory, last, flags | libc::O_NONBLOCK)
+}
+
+/// `name` in `directory`, never through a symbolic link
+#[cfg(unix)]
+fn open_at(directory: &File, name: &std::ffi::OsStr, flags: libc::c_int) -> std::io::Result<File> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::ffi::OsStrExt;
+    let name = std::ffi::CString::new(name.as_bytes())?;
+    let flags = flags | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let fd = unsafe {
+        libc::openat(
+            directory.as_raw_fd(),
+            name.as_ptr(),
+            flags,
+            0o666 as libc::c_uint,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn open_beneath(root: &Path, path: &Path, mode: Mode, create: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    match mode {
+        Mode::Read => options.read(true),
+        Mode::Write => options.write(true).create(create),
+        Mode::Append => options.append(true).create(create),
+    };
+    options.open(root.join(path))
+}
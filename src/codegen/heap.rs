@@ -0,0 +1,130 @@
+//! the memory `alloc` hands out. the heap lies directly above the stack, its bookkeeping is
+//! kept by the host so a program cannot corrupt it. with `checked` set freed blocks are never
+//! handed out again, which lets the interpreter catch double frees and uses after a free
+
+use super::interpreter::TrapKind;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// the number of bytes `Interpreter::load` reserves for the heap
+pub const HEAP_SIZE: u32 = 1 << 16;
+/// every block starts at a multiple of this and is a multiple of it long
+pub const HEAP_ALIGNMENT: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Heap {
+    pub range: Range<u32>,
+    /// catches double frees, frees of adresses `alloc` did not return and uses after a free
+    pub checked: bool,
+    /// the blocks in use, by adress, with their size
+    pub(super) blocks: BTreeMap<u32, u32>,
+    /// the space that can still be allocated, adjacent spaces are merged
+    pub(super) free: BTreeMap<u32, u32>,
+    /// the blocks freed while `checked`
+    pub(super) freed: BTreeMap<u32, u32>,
+}
+
+impl Heap {
+    /// `range` has to start at a multiple of `HEAP_ALIGNMENT`
+    pub fn new(range: Range<u32>) -> Heap {
+        let size = (range.end - range.start) / HEAP_ALIGNMENT * HEAP_ALIGNMENT;
+        Heap {
+            free: (size > 0)
+                .then_some((range.start, size))
+                .into_iter()
+                .collect(),
+            range,
+            ..Heap::default()
+        }
+    }
+
+    /// the adress of a block of at least `size` bytes, the first that fits.
+    /// `None` when there is no such block
+    pub fn alloc(&mut self, size: u32) -> Option<u32> {
+        let size = size.max(1).checked_next_multiple_of(HEAP_ALIGNMENT)?;
+        let (&start, &space) = self.free.iter().find(|(_, space)| **space >= size)?;
+        self.free.remove(&start);
+        if space > size {
+            self.free.insert(start + size, space - size);
+        }
+        self.blocks.insert(start, size);
+        Some(start)
+    }
+
+    /// freeing 0 does nothing, as does freeing anything else `alloc` did not return
+    /// unless `checked` is set
+    pub fn free(&mut self, adress: u32) -> Result<(), TrapKind> {
+        if adress == 0 {
+            return Ok(());
+        }
+        let size = match self.blocks.remove(&adress) {
+            Some(size) => size,
+            None if !self.checked => return Ok(()),
+            None if self.freed.contains_key(&adress) => return Err(TrapKind::DoubleFree(adress)),
+            None => return Err(TrapKind::InvalidFree(adress)),
+        };
+        if self.checked {
+            self.freed.insert(adress, size);
+            return Ok(());
+        }
+        let (mut start, mut size) = (adress, size);
+        if let Some((&before, &space)) = self.free.range(..start).next_back() {
+            if before + space == start {
+                self.free.remove(&before);
+                (start, size) = (before, space + size);
+            }
+        }
+        if let Some(space) = self.free.remove(&(start + size)) {
+            size += space;
+        }
+        self.free.insert(start, size);
+        Ok(())
+    }
+
+    /// fails with `UseAfterFree` when `adress` lies in a freed block and `checked` is set
+    pub fn check(&self, adress: u32) -> Result<(), TrapKind> {
+        if !self.checked || !self.range.contains(&adress) {
+            return Ok(());
+        }
+        match self.freed.range(..=adress).next_back() {
+            Some((start, size)) if adress - start < *size => Err(TrapKind::UseAfterFree(adress)),
+            _ => Ok(()),
+        }
+    }
+
+    /// `check` for the `length` bytes from `start` on, which may wrap around. fails with the
+    /// first of them that lies in a freed block
+    pub fn check_range(&self, start: u32, length: u32) -> Result<(), TrapKind> {
+        if !self.checked {
+            return Ok(());
+        }
+        let end = start as u64 + length as u64;
+        let pieces = [
+            (start as u64, end.min(1 << 32)),
+            (0, end.saturating_sub(1 << 32)),
+        ];
+        for (from, to) in pieces.into_iter().filter(|(from, to)| from < to) {
+            let inside = self
+                .freed
+                .range(..=from as u32)
+                .next_back()
+                .filter(|(block, size)| from < **block as u64 + **size as u64)
+                .map(|_| from);
+            let after = self
+                .freed
+                .range(from as u32..)
+                .next()
+                .map(|(block, _)| *block as u64)
+                .filter(|block| *block < to);
+            if let Some(adress) = inside.or(after) {
+                return Err(TrapKind::UseAfterFree(adress as u32));
+            }
+        }
+        Ok(())
+    }
+
+    /// the blocks in use, by adress, with their size
+    pub fn blocks(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.blocks.iter().map(|(adress, size)| (*adress, *size))
+    }
+}
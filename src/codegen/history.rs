@@ -0,0 +1,218 @@
+//! what every `tick` changed, kept so the interpreter can step backwards. a `History` holds
+//! the deltas of the last `capacity` ticks. stepping back moves deltas to be replayed, and
+//! `tick` replays them exactly as recorded before it executes anything new. I/O is neither
+//! undone nor repeated, open files stay open. `Engine` does not record and forgets the history
+
+use super::asm::*;
+use super::heap::Heap;
+use super::interpreter::*;
+use super::trace::changed_registers;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// the changes of one tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    /// the adress of the instruction
+    pub pc: u32,
+    /// every register that changed, before and after. `None` when it was never set
+    pub registers: Vec<(Register, Option<u32>, Option<u32>)>,
+    /// the flags before and after, when they changed
+    pub flags: Option<(BTreeSet<Flag>, BTreeSet<Flag>)>,
+    /// every byte written, in order, with the node before and after
+    pub memory: Vec<(u32, MemoryNode, MemoryNode)>,
+    pub usage: (Usage, Usage),
+    /// whether the trap handler was running before and after
+    pub in_handler: (bool, bool),
+    /// the heap before and after `alloc` or `free`
+    pub heap: Option<Box<(Heap, Heap)>>,
+    /// what `tick` returned
+    pub trap: Option<Trap>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    capacity: usize,
+    /// the ticks that can be stepped back over, oldest first
+    past: VecDeque<Delta>,
+    /// the ticks stepped back over, the next to replay last
+    future: Vec<Delta>,
+    /// the bytes written by the tick being recorded, with the node before and after
+    pub(super) writes: Vec<(u32, MemoryNode, MemoryNode)>,
+}
+
+impl History {
+    /// keeps the deltas of the last `capacity` ticks
+    pub fn new(capacity: usize) -> History {
+        History {
+            capacity,
+            past: VecDeque::new(),
+            future: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// the ticks that can be stepped back over, oldest first
+    pub fn past(&self) -> impl DoubleEndedIterator<Item = &Delta> {
+        self.past.iter()
+    }
+
+    /// the number of ticks that can be replayed
+    pub fn future(&self) -> usize {
+        self.future.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.past.clear();
+        self.future.clear();
+    }
+
+    fn push(&mut self, delta: Delta) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.past.len() == self.capacity {
+            self.past.pop_front();
+        }
+        self.past.push_back(delta);
+    }
+}
+
+fn set<K: Ord + Copy>(map: &mut BTreeMap<K, u32>, key: K, value: Option<u32>) {
+    match value {
+        Some(value) => map.insert(key, value),
+        None => map.remove(&key),
+    };
+}
+
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    pub fn set_history(&mut self, history: Option<History>) {
+        self.history = history;
+    }
+
+    /// `tick` while a history is kept
+    pub(super) fn recorded_tick(&mut self) -> Result<(), Trap> {
+        let history = self.history.as_mut().unwrap();
+        if let Some(delta) = history.future.pop() {
+            self.redo(&delta);
+            let trap = delta.trap;
+            self.history.as_mut().unwrap().push(delta);
+            return trap.map_or(Ok(()), Err);
+        }
+        history.writes.clear();
+        let pc = *self.get_register(&Register::ProgramCounter);
+        let registers = self.registers.clone();
+        let flags = self.flags.clone();
+        let usage = self.usage;
+        let in_handler = self.in_handler;
+        let heap = matches!(
+            self.get(pc),
+            MemoryNode::Instruction(Instruction::SysCall(
+                SysCall::Alloc { .. } | SysCall::Free(_)
+            ))
+        )
+        .then(|| self.heap.clone());
+
+        let result = self.advance();
+
+        let history = self.history.as_mut().unwrap();
+        let delta = Delta {
+            pc,
+            registers: changed_registers(&registers, &self.registers),
+            flags: (flags != self.flags).then(|| (flags, self.flags.clone())),
+            memory: std::mem::take(&mut history.writes),
+            usage: (usage, self.usage),
+            in_handler: (in_handler, self.in_handler),
+            heap: heap
+                .filter(|heap| *heap != self.heap)
+                .map(|heap| Box::new((heap, self.heap.clone()))),
+            trap: result.err(),
+        };
+        let changed = !delta.registers.is_empty()
+            || delta.flags.is_some()
+            || !delta.memory.is_empty()
+            || delta.usage.0 != delta.usage.1
+            || delta.in_handler.0 != delta.in_handler.1;
+        if changed {
+            history.push(delta);
+        }
+        result
+    }
+
+    fn undo(&mut self, delta: &Delta) {
+        for (register, old, _) in &delta.registers {
+            set(&mut self.registers, *register, *old);
+        }
+        if let Some((old, _)) = &delta.flags {
+            self.flags = old.clone();
+        }
+        for (adress, old, _) in delta.memory.iter().rev() {
+            match old {
+                MemoryNode::None => self.memory.remove(*adress),
+                old => self.memory.insert(*adress, old.clone()).unwrap(),
+            }
+        }
+        self.usage = delta.usage.0;
+        self.in_handler = delta.in_handler.0;
+        if let Some(heap) = &delta.heap {
+            self.heap = heap.0.clone();
+        }
+    }
+
+    fn redo(&mut self, delta: &Delta) {
+        for (register, _, new) in &delta.registers {
+            set(&mut self.registers, *register, *new);
+        }
+        if let Some((_, new)) = &delta.flags {
+            self.flags = new.clone();
+        }
+        for (adress, _, new) in &delta.memory {
+            self.memory.insert(*adress, new.clone()).unwrap();
+        }
+        self.usage = delta.usage.1;
+        self.in_handler = delta.in_handler.1;
+        if let Some(heap) = &delta.heap {
+            self.heap = heap.1.clone();
+        }
+    }
+
+    /// undoes the last tick, `false` when there is no history or it is empty
+    pub fn step_back(&mut self) -> bool {
+        let Some(delta) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.past.pop_back())
+        else {
+            return false;
+        };
+        self.undo(&delta);
+        self.history.as_mut().unwrap().future.push(delta);
+        true
+    }
+
+    /// replays the tick undone last, `None` when there is none
+    pub fn replay(&mut self) -> Option<Result<(), Trap>> {
+        let history = self.history.as_ref()?;
+        (!history.future.is_empty()).then(|| self.recorded_tick())
+    }
+
+    /// steps back to the last instruction that wrote to `adress`, before it executed.
+    /// the number of ticks undone, `None` when the history does not go back that far
+    pub fn rewind_to_write(&mut self, adress: u32) -> Option<usize> {
+        let ticks = self
+            .history
+            .as_ref()?
+            .past
+            .iter()
+            .rev()
+            .position(|delta| delta.memory.iter().any(|(written, ..)| *written == adress))?
+            + 1;
+        for _ in 0..ticks {
+            self.step_back();
+        }
+        Some(ticks)
+    }
+}
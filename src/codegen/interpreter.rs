@@ -1,62 +1,273 @@
+use super::abi::STACK_ALIGNMENT;
 use super::asm::*;
+use super::files::{Files, FAILED};
+use super::heap::{Heap, HEAP_SIZE};
+use super::history::History;
+pub use super::io::{Console, Io};
+pub use super::memory::{Memory, MemoryNode};
+use super::object::Object;
+use super::trace::SharedTracer;
 use super::INSTRUCTION_SIZE;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum MemoryNode {
-    None,
-    Data(u32),
-    Instruction(Instruction),
-    FilledByInstruction,
+/// the number of bytes `load` reserves for the stack, directly after the code.
+/// a multiple of `STACK_ALIGNMENT`, so the stack starts out aligned
+pub const STACK_SIZE: u32 = 4096;
+
+/// why the interpreter stopped. `Halt` is the normal end of a program, everything else is a fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapKind {
+    /// carries the exit code, the value of `R0` when `halt` was executed
+    Halt(u32),
+    InvalidInstruction,
+    DivisionByZero,
+    Underflow,
+    Overflow,
+    /// carries the stack pointer that would have left `Interpreter::stack`
+    StackOutOfBounds(u32),
+    /// carries the adress that was read before anything was written to it
+    UninitialisedRead(u32),
+    /// carries the adress of a write past the end of the memory
+    OutOfMemory(u32),
+    /// the host failed to do the I/O of a syscall
+    IoError,
+    /// carries the adress of a block that was freed before, only raised by a checked `Heap`
+    DoubleFree(u32),
+    /// carries an adress `alloc` did not return, only raised by a checked `Heap`
+    InvalidFree(u32),
+    /// carries the adress in a freed block that was read or written, only raised by a
+    /// checked `Heap`
+    UseAfterFree(u32),
+    /// a limit in `Interpreter::limits` was reached, carries what was used so far. the
+    /// instruction at `pc` was not executed, `execute` continues with it once the limit is raised
+    Exhausted(Resource, Usage),
+}
+
+/// what `Limits` bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Instructions,
+    Memory,
+    Stack,
+}
+
+/// how far a program may go, `None` is unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    /// the memory cells the program may write to that held nothing before
+    pub memory: Option<u32>,
+    /// the bytes the stack may grow to
+    pub stack: Option<u32>,
+}
+
+/// what a program used so far, counted against `Limits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub instructions: u64,
+    pub memory: u32,
+    /// the deepest the stack was, in bytes
+    pub stack: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trap {
+    pub pc: u32,
+    /// `None` when there is no instruction at `pc`
+    pub instruction: Option<Instruction>,
+    pub kind: TrapKind,
+}
+
+impl TrapKind {
+    /// the number a trap handler finds in `TRAP_CODE`
+    pub fn code(&self) -> u32 {
+        match self {
+            TrapKind::Halt(_) => 0,
+            TrapKind::InvalidInstruction => 1,
+            TrapKind::DivisionByZero => 2,
+            TrapKind::Underflow => 3,
+            TrapKind::Overflow => 4,
+            TrapKind::StackOutOfBounds(_) => 5,
+            TrapKind::UninitialisedRead(_) => 6,
+            TrapKind::OutOfMemory(_) => 7,
+            TrapKind::Exhausted(..) => 8,
+            TrapKind::IoError => 9,
+            TrapKind::DoubleFree(_) => 10,
+            TrapKind::InvalidFree(_) => 11,
+            TrapKind::UseAfterFree(_) => 12,
+        }
+    }
+}
+
+/// set to the code of the trap when the trap handler is entered
+pub const TRAP_CODE: Register = Register::R14;
+/// set to the adress of the faulting instruction when the trap handler is entered. the handler
+/// is left by jumping to it, or to it plus an offset like `jmp A r15+4`
+pub const TRAP_PC: Register = Register::R15;
+
+impl Trap {
+    pub fn is_halt(&self) -> bool {
+        matches!(self.kind, TrapKind::Halt(_))
+    }
+
+    /// the value of `halt` or `exit`, `None` for faults
+    pub fn exit_code(&self) -> Option<u32> {
+        match self.kind {
+            TrapKind::Halt(code) => Some(code),
+            _ => None,
+        }
+    }
 }
 
 trait Executable {
-    fn execute(&self, interpreter: &mut Interpreter) -> bool;
+    fn execute<M: Memory, I: Io>(
+        &self,
+        interpreter: &mut Interpreter<M, I>,
+    ) -> Result<(), TrapKind>;
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Interpreter {
-    pub memory: BTreeMap<u32, MemoryNode>,
+pub struct Interpreter<M = BTreeMap<u32, MemoryNode>, I = Console> {
+    pub memory: M,
+    /// what the syscalls read from and write to
+    pub io: I,
     pub registers: BTreeMap<Register, u32>,
+    /// how each instruction leaves the flags, where `result` is the value it writes:
+    ///
+    /// | instruction                  | Equal       | Lesser     | Greater    | Overflow                      |
+    /// |------------------------------|-------------|------------|------------|-------------------------------|
+    /// | `cmp a b` with U S or F      | a == b      | a < b      | a > b      | unchanged                     |
+    /// | `add sub mul div mod` with U | result == 0 | never      | result > 0 | carry or borrow               |
+    /// | `add sub mul div mod` with S | result == 0 | result < 0 | result > 0 | result does not fit a i32     |
+    /// | `add sub mul div mod` with F | result == 0 | result < 0 | result > 0 | infinite from finite operands |
+    /// | `cvt` as the type it is to   | result == 0 | result < 0 | result > 0 | saturated, or NaN to U or S   |
+    /// | `sext8 sext16 sext32`        | result == 0 | result < 0 | result > 0 | cleared                       |
+    /// | `and or xor not shl shr`     | result == 0 | never      | result > 0 | cleared                       |
+    /// | `zext8 zext16 zext32`        | result == 0 | never      | result > 0 | cleared                       |
+    /// | everything else              | unchanged   | unchanged  | unchanged  | unchanged                     |
+    ///
+    /// a NaN result, or a float `cmp` with a NaN, clears `Equal`, `Lesser` and `Greater`. an
+    /// unsigned `sub` or `mul` that overflows only sets `Overflow` and traps without writing its
+    /// result.
+    /// `Always` is always set.
+    ///
+    /// the read syscalls set `EndOfInput` when the input ended before they read anything and
+    /// clear it otherwise. `readint` also sets `Overflow` when there was no number or it does
+    /// not fit 32 bits, and clears it otherwise
     pub flags: BTreeSet<Flag>,
+    /// the adresses the stack may use. it grows down from `stack.end`, where `SP` starts
+    pub stack: Range<u32>,
+    /// where execution continues when a fault is raised, faults go to the host without one
+    pub trap_handler: Option<u32>,
+    /// set while the trap handler runs, from the delivery of a fault until the handler resumes
+    /// with a `jmp` or `jmpn` to `TRAP_PC` (see `resumes`). faults raised in the meantime go to
+    /// the host
+    pub in_handler: bool,
+    pub limits: Limits,
+    pub usage: Usage,
+    /// the files the program opened, nothing can be opened until `set_root` is called
+    pub files: Files,
+    /// `load` places it directly above the stack
+    pub heap: Heap,
+    /// what the last ticks changed, nothing is recorded without one
+    pub history: Option<History>,
+    /// told about every tick
+    pub tracer: Option<SharedTracer>,
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
+        Interpreter::with_memory(BTreeMap::new())
+    }
+}
+
+impl<M: Memory> Interpreter<M> {
+    pub fn with_memory(memory: M) -> Interpreter<M> {
         Interpreter {
-            memory: BTreeMap::new(),
+            memory,
+            io: Console,
             registers: BTreeMap::new(),
             flags: BTreeSet::new(),
+            stack: 0..0,
+            trap_handler: None,
+            in_handler: false,
+            limits: Limits::default(),
+            usage: Usage::default(),
+            files: Files::default(),
+            heap: Heap::default(),
+            history: None,
+            tracer: None,
         }
     }
+}
 
-    pub fn load(&mut self, data: Vec<u32>, instructions: Vec<Instruction>, start: u32) {
-        let mut adress = 0;
-        for value in data {
-            self.memory.insert(adress, MemoryNode::Data(value));
-            adress += 1;
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    /// the same interpreter, doing its I/O with `io`
+    pub fn with_io<J: Io>(self, io: J) -> Interpreter<M, J> {
+        Interpreter {
+            memory: self.memory,
+            io,
+            registers: self.registers,
+            flags: self.flags,
+            stack: self.stack,
+            trap_handler: self.trap_handler,
+            in_handler: self.in_handler,
+            limits: self.limits,
+            usage: self.usage,
+            files: self.files,
+            heap: self.heap,
+            history: self.history,
+            tracer: self.tracer,
         }
+    }
+
+    /// fails with `TrapKind::OutOfMemory` when the program does not fit into the memory, which
+    /// is left as it was
+    pub fn load(
+        &mut self,
+        data: Vec<u8>,
+        instructions: Vec<Instruction>,
+        start: u32,
+    ) -> Result<(), TrapKind> {
+        let mut nodes = Vec::new();
+        nodes.extend(data.into_iter().map(MemoryNode::Data));
         for instruction in instructions {
-            self.memory
-                .insert(adress, MemoryNode::Instruction(instruction));
-            for i in 1..INSTRUCTION_SIZE as u32 {
-                self.memory
-                    .insert(adress + i, MemoryNode::FilledByInstruction);
+            nodes.push(MemoryNode::Instruction(instruction));
+            for _ in 1..INSTRUCTION_SIZE {
+                nodes.push(MemoryNode::FilledByInstruction);
             }
-            adress += INSTRUCTION_SIZE as u32;
         }
+        let Ok(length) = u32::try_from(nodes.len()) else {
+            return Err(TrapKind::OutOfMemory(u32::MAX));
+        };
+        if let Some(end) = self.memory.end().filter(|end| length > *end) {
+            return Err(TrapKind::OutOfMemory(end));
+        }
+        let mut adress = 0;
+        for node in nodes {
+            self.memory.insert(adress, node)?;
+            adress += 1;
+        }
+        let base = adress.next_multiple_of(STACK_ALIGNMENT);
+        self.stack = base..base + STACK_SIZE;
+        let checked = self.heap.checked;
+        self.heap = Heap::new(self.stack.end..self.stack.end.saturating_add(HEAP_SIZE));
+        self.heap.checked = checked;
         self.registers.insert(Register::ProgramCounter, start);
-        self.registers.insert(Register::StackPointer, 1000);
+        self.in_handler = false;
+        self.registers.insert(Register::StackPointer, self.stack.end);
         self.registers.insert(Register::ProgramPointer, 0);
+        Ok(())
     }
 
-    pub fn get(&mut self, adress: u32) -> &mut MemoryNode {
-        if let std::collections::btree_map::Entry::Vacant(e) = self.memory.entry(adress) {
-            e.insert(MemoryNode::None);
-            self.memory.get_mut(&adress).unwrap()
-        } else {
-            self.memory.get_mut(&adress).unwrap()
-        }
+    pub fn load_object(&mut self, object: Object) -> Result<(), TrapKind> {
+        self.load(object.data, object.code, object.entry)
+    }
+
+    pub fn get(&self, adress: u32) -> &MemoryNode {
+        self.memory.get(adress).unwrap_or(&MemoryNode::None)
     }
 
     pub fn get_register(&mut self, register: &Register) -> &mut u32 {
@@ -80,26 +291,171 @@ impl Interpreter {
         self.flags.remove(&flag);
     }
 
-    pub fn tick(&mut self) -> Result<(), String> {
+    fn assign_flag(&mut self, flag: Flag, set: bool) {
+        if set {
+            self.set_flag(flag);
+        } else {
+            self.clear_flag(flag);
+        }
+    }
+
+    pub fn push(&mut self, value: u32) -> Result<(), TrapKind> {
+        Machine::push(self, value)
+    }
+
+    /// where a push at `sp` goes, if the stack and `Limits::stack` allow it
+    pub(super) fn below(&self, sp: u32) -> Result<u32, TrapKind> {
+        let word = Width::Word.bytes();
+        if sp < self.stack.start + word || sp > self.stack.end {
+            return Err(TrapKind::StackOutOfBounds(sp));
+        }
+        let depth = self.stack.end - (sp - word);
+        if self.limits.stack.is_some_and(|limit| depth > limit) {
+            return Err(TrapKind::Exhausted(Resource::Stack, self.usage));
+        }
+        Ok(sp - word)
+    }
+
+    pub fn pop(&mut self) -> Result<u32, TrapKind> {
+        Machine::pop(self)
+    }
+
+    pub fn set_trap_handler(&mut self, handler: Option<u32>) {
+        self.trap_handler = handler;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// makes the heap catch double frees and uses after a free, at the cost of never
+    /// reusing freed memory
+    pub fn set_heap_checks(&mut self, checked: bool) {
+        self.heap.checked = checked;
+    }
+
+    /// the directory the paths of `open` are relative to, the program cannot leave it
+    pub fn set_root(&mut self, root: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        self.files.set_root(root)
+    }
+
+    /// the trap handler `trap` is delivered to. halts and exhausted limits belong to the host,
+    /// faults of the handler itself would only trap again
+    pub(super) fn handler(&self, trap: &Trap) -> Option<u32> {
+        match (self.trap_handler, trap.kind) {
+            (_, TrapKind::Halt(_) | TrapKind::Exhausted(..)) => None,
+            (Some(handler), _) if !self.in_handler => Some(handler),
+            _ => None,
+        }
+    }
+
+    /// hands a fault to the trap handler, with its code in `TRAP_CODE` and the adress of the
+    /// faulting instruction in `TRAP_PC`. traps without a handler are returned
+    pub fn deliver(&mut self, trap: Trap) -> Result<(), Trap> {
+        Machine::deliver(self, trap)
+    }
+
+    fn set_ordering(&mut self, ordering: Option<Ordering>) {
+        self.clear_flag(Flag::Lesser);
+        self.clear_flag(Flag::Greater);
+        self.clear_flag(Flag::Equal);
+        match ordering {
+            Some(Ordering::Less) => self.set_flag(Flag::Lesser),
+            Some(Ordering::Greater) => self.set_flag(Flag::Greater),
+            Some(Ordering::Equal) => self.set_flag(Flag::Equal),
+            None => (),
+        }
+    }
+
+    /// sets the flags for the `result` of an arithmetic or bitwise instruction, as if it was
+    /// compared to zero
+    fn set_result(&mut self, result: u32, math_type: MathType, overflow: bool) {
+        self.set_ordering(sign(result, math_type));
+        self.assign_flag(Flag::Overflow, overflow);
+    }
+
+    fn arithmetic(
+        &mut self,
+        operation: &Arithmetic,
+        value: u32,
+        value2: u32,
+        math_type: MathType,
+    ) -> Result<u32, TrapKind> {
+        let (result, overflow) = operation.apply(value, value2, math_type)?;
+        if let (true, MathType::Unsigned, Some(kind)) = (overflow, math_type, operation.trap) {
+            self.set_flag(Flag::Overflow);
+            return Err(kind);
+        }
+        self.set_result(result, math_type, overflow);
+        Ok(result)
+    }
+
+    /// executes the instruction at `PC`, or replays the tick `step_back` undid last
+    pub fn tick(&mut self) -> Result<(), Trap> {
+        match self.tracer {
+            Some(_) => self.traced_tick(),
+            None => self.untraced_tick(),
+        }
+    }
+
+    pub(super) fn untraced_tick(&mut self) -> Result<(), Trap> {
+        match self.history {
+            Some(_) => self.recorded_tick(),
+            None => self.advance(),
+        }
+    }
+
+    pub(super) fn advance(&mut self) -> Result<(), Trap> {
         self.set_flag(Flag::Always);
         let pc = *self.get_register(&Register::ProgramCounter);
-        let instruction = *match self.get(pc) {
-            MemoryNode::Instruction(instruction) => instruction,
-            _ => return Err("Invalid instruction".to_string()),
+        let instruction = match self.get(pc) {
+            MemoryNode::Instruction(instruction) => *instruction,
+            _ => {
+                return self.deliver(Trap {
+                    pc,
+                    instruction: None,
+                    kind: TrapKind::InvalidInstruction,
+                })
+            }
         };
+        if self
+            .limits
+            .instructions
+            .is_some_and(|limit| self.usage.instructions >= limit)
+        {
+            return Err(Trap {
+                pc,
+                instruction: Some(instruction),
+                kind: TrapKind::Exhausted(Resource::Instructions, self.usage),
+            });
+        }
+        self.usage.instructions += 1;
         self.registers
             .insert(Register::ProgramCounter, pc + INSTRUCTION_SIZE as u32);
         match instruction.execute(self) {
-            true => Ok(()),
-            false => Err("Halt".to_string()),
+            Ok(()) => Ok(()),
+            Err(TrapKind::Exhausted(resource, _)) => {
+                self.usage.instructions -= 1;
+                self.set_register(Register::ProgramCounter, pc);
+                Err(Trap {
+                    pc,
+                    instruction: Some(instruction),
+                    kind: TrapKind::Exhausted(resource, self.usage),
+                })
+            }
+            Err(kind) => self.deliver(Trap {
+                pc,
+                instruction: Some(instruction),
+                kind,
+            }),
         }
     }
 
-    pub fn execute(&mut self) -> Result<(), String> {
+    /// runs until the program halts or faults
+    pub fn execute(&mut self) -> Trap {
         loop {
-            match self.tick() {
-                Ok(_) => (),
-                Err(e) => return Err(e),
+            if let Err(trap) = self.tick() {
+                return trap;
             }
         }
     }
@@ -109,7 +465,7 @@ impl Interpreter {
         for (register, value) in &self.registers {
             output.push_str(&format!("{:?}: {}\n", register, value));
         }
-        for (adress, node) in &self.memory {
+        for (adress, node) in self.memory.nodes() {
             if let MemoryNode::Instruction(instruction) = node {
                 output.push_str(&format!("{}: {:?}\n", adress, String::from(*instruction)));
             } else if let MemoryNode::Data(value) = node {
@@ -124,7 +480,7 @@ impl Interpreter {
 }
 
 impl Operand {
-    pub fn aquire(&self, interpreter: &mut Interpreter) -> u32 {
+    pub fn aquire<M: Memory, I: Io>(&self, interpreter: &mut Interpreter<M, I>) -> u32 {
         match self {
             Operand::U32(value) => *value,
             Operand::Register(register) => *interpreter.get_register(register),
@@ -132,60 +488,490 @@ impl Operand {
     }
 }
 
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    /// reads `width` bytes, little endian. bytes of instructions read as zero
+    pub fn read(&self, adress: u32, width: Width) -> Result<u32, TrapKind> {
+        let mut value = 0;
+        for index in 0..width.bytes() {
+            let adress = adress.wrapping_add(index);
+            self.heap.check(adress)?;
+            let byte = match self.memory.get(adress) {
+                Some(MemoryNode::Data(byte)) => *byte,
+                None | Some(MemoryNode::None) => return Err(TrapKind::UninitialisedRead(adress)),
+                _ => 0,
+            };
+            value |= (byte as u32) << (8 * index);
+        }
+        Ok(value)
+    }
+
+    /// the `length` bytes from `start` on
+    pub fn read_bytes(&self, start: u32, length: u32) -> Result<Vec<u8>, TrapKind> {
+        (0..length)
+            .map(|index| Ok(self.read(start.wrapping_add(index), Width::Byte)? as u8))
+            .collect()
+    }
+
+    /// hands `bytes` to `io`
+    pub fn output(&mut self, bytes: &[u8]) -> Result<(), TrapKind> {
+        self.io.write(bytes).map_err(|_| TrapKind::IoError)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, TrapKind> {
+        self.io.peek().map_err(|_| TrapKind::IoError)
+    }
+
+    /// the next byte of input, `None` at the end
+    pub fn input_char(&mut self) -> Result<Option<u8>, TrapKind> {
+        let byte = self.peek()?;
+        if byte.is_some() {
+            self.io.consume();
+        }
+        Ok(byte)
+    }
+
+    /// the decimal number after any whitespace, wrapped to 32 bits, and whether there was
+    /// none or it did not fit. `None` when the input ends first
+    pub fn input_int(&mut self) -> Result<Option<(u32, bool)>, TrapKind> {
+        while self.peek()?.is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.io.consume();
+        }
+        let negative = match self.peek()? {
+            None => return Ok(None),
+            Some(sign @ (b'-' | b'+')) => {
+                self.io.consume();
+                sign == b'-'
+            }
+            Some(_) => false,
+        };
+        let (mut value, mut magnitude, mut digits) = (0u32, 0u64, 0);
+        while let Some(byte @ b'0'..=b'9') = self.peek()? {
+            self.io.consume();
+            let digit = (byte - b'0') as u32;
+            value = value.wrapping_mul(10).wrapping_add(digit);
+            magnitude = magnitude.saturating_mul(10).saturating_add(digit as u64);
+            digits += 1;
+        }
+        let (value, limit) = match negative {
+            true => (value.wrapping_neg(), 1 << 31),
+            false => (value, u32::MAX as u64),
+        };
+        Ok(Some((value, digits == 0 || magnitude > limit)))
+    }
+
+    /// stores up to `max_len` bytes of the next line from `start` on and consumes its newline,
+    /// the rest of a longer line is left for the next read. the number of bytes stored, `None`
+    /// when the input ends first. nothing is taken from the input when not all `max_len` bytes
+    /// could be written
+    pub fn input_line(&mut self, start: u32, max_len: u32) -> Result<Option<u32>, TrapKind> {
+        if self.peek()?.is_none() {
+            return Ok(None);
+        }
+        self.writable(start, max_len)?;
+        let mut length = 0;
+        while length < max_len {
+            match self.peek()? {
+                None => break,
+                Some(b'\n') => {
+                    self.io.consume();
+                    break;
+                }
+                Some(byte) => {
+                    self.write(start.wrapping_add(length), byte as u32, Width::Byte)?;
+                    self.io.consume();
+                    length += 1;
+                }
+            }
+        }
+        Ok(Some(length))
+    }
+
+    /// the file descriptor of the file named by the `path_len` bytes at `path`, or `FAILED`
+    pub fn file_open(&mut self, path: u32, path_len: u32, mode: u32) -> Result<u32, TrapKind> {
+        let path = self.read_bytes(path, path_len)?;
+        Ok(self.files.open(&path, mode).unwrap_or(FAILED))
+    }
+
+    /// stores up to `length` bytes of a file from `start` on, their number or `FAILED`.
+    /// file descriptor 0 reads the input of `io` and stops after a newline. nothing is read
+    /// when not all `length` bytes could be written
+    pub fn file_read(&mut self, fd: u32, start: u32, length: u32) -> Result<u32, TrapKind> {
+        self.writable(start, length)?;
+        let bytes = match fd {
+            0 => {
+                let mut bytes = Vec::new();
+                while (bytes.len() as u32) < length && bytes.last() != Some(&b'\n') {
+                    match self.input_char()? {
+                        Some(byte) => bytes.push(byte),
+                        None => break,
+                    }
+                }
+                bytes
+            }
+            _ => match self.files.read(fd, length) {
+                Some(bytes) => bytes,
+                None => return Ok(FAILED),
+            },
+        };
+        for (index, byte) in bytes.iter().enumerate() {
+            self.write(start.wrapping_add(index as u32), *byte as u32, Width::Byte)?;
+        }
+        Ok(bytes.len() as u32)
+    }
+
+    /// writes the `length` bytes from `start` on to a file, their number or `FAILED`.
+    /// file descriptors 1 and 2 write to `io`
+    pub fn file_write(&mut self, fd: u32, start: u32, length: u32) -> Result<u32, TrapKind> {
+        let bytes = self.read_bytes(start, length)?;
+        match fd {
+            1 | 2 => {
+                self.output(&bytes)?;
+                Ok(length)
+            }
+            _ => Ok(self.files.write(fd, &bytes).unwrap_or(FAILED)),
+        }
+    }
+
+    /// 0, or `FAILED` when `fd` was not open
+    pub fn file_close(&mut self, fd: u32) -> u32 {
+        match self.files.close(fd) {
+            true => 0,
+            false => FAILED,
+        }
+    }
+
+    /// fails the way `write` would when one of the `length` bytes from `start` on can not be
+    /// written, without writing anything
+    pub fn writable(&self, start: u32, length: u32) -> Result<(), TrapKind> {
+        let Some(last) = length.checked_sub(1) else {
+            return Ok(());
+        };
+        let outside = match self.memory.end() {
+            Some(end) if start >= end => Some(start),
+            Some(end) if start.checked_add(last).is_none_or(|last| last >= end) => Some(end),
+            _ => None,
+        };
+        let inside = outside.map_or(length, |adress| adress - start);
+        self.heap.check_range(start, inside)?;
+        if let Some(adress) = outside {
+            return Err(TrapKind::OutOfMemory(adress));
+        }
+        if let Some(limit) = self.limits.memory {
+            let mut fresh = 0;
+            for index in 0..length {
+                if *self.get(start.wrapping_add(index)) == MemoryNode::None {
+                    fresh += 1;
+                    if self.usage.memory + fresh > limit {
+                        return Err(TrapKind::Exhausted(Resource::Memory, self.usage));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// writes the low `width` bytes of `value`, little endian. nothing is written when one of
+    /// the bytes can not be
+    pub fn write(&mut self, adress: u32, value: u32, width: Width) -> Result<(), TrapKind> {
+        self.writable(adress, width.bytes())?;
+        for (index, byte) in value.to_le_bytes()[..width.bytes() as usize]
+            .iter()
+            .enumerate()
+        {
+            let adress = adress.wrapping_add(index as u32);
+            let old = self.get(adress).clone();
+            self.memory.insert(adress, MemoryNode::Data(*byte))?;
+            if old == MemoryNode::None {
+                self.usage.memory += 1;
+            }
+            if let Some(history) = &mut self.history {
+                history.writes.push((adress, old, MemoryNode::Data(*byte)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    fn call(&mut self, target: &Value) -> Result<(), TrapKind> {
+        let target = target.aquire(self)?;
+        let pc = *self.get_register(&Register::ProgramCounter);
+        self.push(pc)?;
+        self.set_register(Register::ProgramCounter, target);
+        Ok(())
+    }
+}
+
 impl Value {
-    pub fn aquire(&self, interpreter: &mut Interpreter) -> u32 {
-        match self {
+    pub fn aquire<M: Memory, I: Io>(
+        &self,
+        interpreter: &mut Interpreter<M, I>,
+    ) -> Result<u32, TrapKind> {
+        Ok(match self {
             Value::Direct(operand) => operand.aquire(interpreter),
             Value::Indirect(operand) => {
                 let adress = operand.aquire(interpreter);
-                match interpreter.get(adress) {
-                    MemoryNode::Data(value) => *value,
-                    _ => 0,
-                }
+                interpreter.read(adress, Width::Word)?
             }
             Value::OperationalDirect(operand1, operand2) => {
                 let value1 = operand1.aquire(interpreter);
                 let value2 = operand2.aquire(interpreter);
-                value1 + value2
+                value1.wrapping_add(value2)
             }
             Value::OperationalIndirect(operand1, operand2) => {
                 let adress = operand1.aquire(interpreter);
-                let value2 = operand2.aquire(interpreter);
-                match interpreter.get(adress) {
-                    MemoryNode::Data(value) => *value + value2,
-                    _ => 0,
-                }
+                let offset = operand2.aquire(interpreter);
+                interpreter.read(adress.wrapping_add(offset), Width::Word)?
             }
-        }
+        })
     }
 }
 
-impl Executable for SysCall {
-    fn execute(&self, interpreter: &mut Interpreter) -> bool {
-        match self {
+/// what `Interpreter` and `Engine` keep in their own way. the traps, the stack and the
+/// syscalls are built on it, so both run them the same
+pub(super) trait Machine<M: Memory, I: Io> {
+    fn interpreter(&mut self) -> &mut Interpreter<M, I>;
+    fn register(&mut self, register: Register) -> u32;
+    fn set_register(&mut self, register: Register, value: u32);
+    fn assign_flag(&mut self, flag: Flag, set: bool);
+    /// what `value` aquires
+    fn value(&mut self, value: &Value) -> Result<u32, TrapKind>;
+    /// told that the syscall or push that ran last may have written the `length` bytes from
+    /// `start` on
+    fn written(&mut self, _start: u32, _length: u32) {}
+
+    fn deliver(&mut self, trap: Trap) -> Result<(), Trap> {
+        match self.interpreter().handler(&trap) {
+            Some(handler) => {
+                self.set_register(TRAP_CODE, trap.kind.code());
+                self.set_register(TRAP_PC, trap.pc);
+                self.set_register(Register::ProgramCounter, handler);
+                self.interpreter().in_handler = true;
+                Ok(())
+            }
+            None => Err(trap),
+        }
+    }
+
+    fn push(&mut self, value: u32) -> Result<(), TrapKind> {
+        let sp = self.register(Register::StackPointer);
+        let interpreter = self.interpreter();
+        let sp = interpreter.below(sp)?;
+        interpreter.write(sp, value, Width::Word)?;
+        interpreter.usage.stack = interpreter.usage.stack.max(interpreter.stack.end - sp);
+        self.written(sp, Width::Word.bytes());
+        self.set_register(Register::StackPointer, sp);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<u32, TrapKind> {
+        let sp = self.register(Register::StackPointer);
+        let interpreter = self.interpreter();
+        let word = Width::Word.bytes();
+        if sp < interpreter.stack.start || sp > interpreter.stack.end.saturating_sub(word) {
+            return Err(TrapKind::StackOutOfBounds(sp));
+        }
+        let value = interpreter.read(sp, Width::Word)?;
+        self.set_register(Register::StackPointer, sp + word);
+        Ok(value)
+    }
+
+    fn syscall(&mut self, syscall: &SysCall) -> Result<(), TrapKind> {
+        match syscall {
             SysCall::Print(value) => {
-                let value = value.aquire(interpreter);
-                println!("{}", value);
+                let value = self.value(value)?;
+                self.interpreter()
+                    .output(format!("{}\n", value).as_bytes())?;
             }
             SysCall::PrintChar(value) => {
-                let value = value.aquire(interpreter);
-                print!("{}", value as u8 as char);
+                let value = self.value(value)?;
+                self.interpreter().output(&[value as u8])?;
             }
             SysCall::PrintString { start, length } => {
-                let start = start.aquire(interpreter);
-                let length = length.aquire(interpreter);
-                for i in 0..length {
-                    let adress = start + i;
-                    if let MemoryNode::Data(value) = interpreter.get(adress) {
-                        print!("{}", *value as u8 as char)
-                    }
-                }
+                let start = self.value(start)?;
+                let length = self.value(length)?;
+                let interpreter = self.interpreter();
+                let bytes = interpreter.read_bytes(start, length)?;
+                interpreter.output(&bytes)?;
+            }
+            SysCall::ReadInt(register) => {
+                let number = self.interpreter().input_int()?;
+                let (value, overflow) = number.unwrap_or((0, false));
+                self.assign_flag(Flag::EndOfInput, number.is_none());
+                self.assign_flag(Flag::Overflow, overflow);
+                self.set_register(*register, value);
             }
+            SysCall::ReadChar(register) => {
+                let byte = self.interpreter().input_char()?;
+                self.assign_flag(Flag::EndOfInput, byte.is_none());
+                self.set_register(*register, byte.unwrap_or(0) as u32);
+            }
+            SysCall::ReadLine {
+                length,
+                start,
+                max_len,
+            } => {
+                let start = self.value(start)?;
+                let max_len = self.value(max_len)?;
+                let stored = self.interpreter().input_line(start, max_len);
+                self.written(start, max_len);
+                let stored = stored?;
+                self.assign_flag(Flag::EndOfInput, stored.is_none());
+                self.set_register(*length, stored.unwrap_or(0));
+            }
+            SysCall::Open {
+                fd,
+                path,
+                path_len,
+                mode,
+            } => {
+                let path = self.value(path)?;
+                let path_len = self.value(path_len)?;
+                let mode = self.value(mode)?;
+                let result = self.interpreter().file_open(path, path_len, mode)?;
+                self.set_register(*fd, result);
+            }
+            SysCall::Read {
+                count,
+                fd,
+                start,
+                length,
+            } => {
+                let fd = self.value(fd)?;
+                let start = self.value(start)?;
+                let length = self.value(length)?;
+                let result = self.interpreter().file_read(fd, start, length);
+                self.written(start, length);
+                self.set_register(*count, result?);
+            }
+            SysCall::Write {
+                count,
+                fd,
+                start,
+                length,
+            } => {
+                let fd = self.value(fd)?;
+                let start = self.value(start)?;
+                let length = self.value(length)?;
+                let result = self.interpreter().file_write(fd, start, length)?;
+                self.set_register(*count, result);
+            }
+            SysCall::Close { result, fd } => {
+                let fd = self.value(fd)?;
+                let closed = self.interpreter().file_close(fd);
+                self.set_register(*result, closed);
+            }
+            SysCall::Alloc { pointer, size } => {
+                let size = self.value(size)?;
+                let adress = self.interpreter().heap.alloc(size).unwrap_or(0);
+                self.set_register(*pointer, adress);
+            }
+            SysCall::Free(pointer) => {
+                let adress = self.value(pointer)?;
+                self.interpreter().heap.free(adress)?;
+            }
+            SysCall::Exit(code) => {
+                return Err(TrapKind::Halt(self.value(code)?));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: Memory, I: Io> Machine<M, I> for Interpreter<M, I> {
+    fn interpreter(&mut self) -> &mut Interpreter<M, I> {
+        self
+    }
+
+    fn register(&mut self, register: Register) -> u32 {
+        *self.get_register(&register)
+    }
+
+    fn set_register(&mut self, register: Register, value: u32) {
+        Interpreter::set_register(self, register, value);
+    }
+
+    fn assign_flag(&mut self, flag: Flag, set: bool) {
+        Interpreter::assign_flag(self, flag, set);
+    }
+
+    fn value(&mut self, value: &Value) -> Result<u32, TrapKind> {
+        value.aquire(self)
+    }
+}
+
+/// whether a jump to `value` leaves the trap handler: it has to add to `TRAP_PC` or be it.
+/// other jumps, calls and returns stay inside the handler
+pub(super) fn resumes(value: &Value) -> bool {
+    let trap_pc = Operand::Register(TRAP_PC);
+    match value {
+        Value::Direct(operand) => *operand == trap_pc,
+        Value::OperationalDirect(operand1, operand2) => {
+            *operand1 == trap_pc || *operand2 == trap_pc
         }
-        true
+        Value::Indirect(_) | Value::OperationalIndirect(..) => false,
     }
 }
 
+/// how `result` compares to zero
+pub(super) fn sign(result: u32, math_type: MathType) -> Option<Ordering> {
+    match math_type {
+        MathType::Unsigned => Some(result.cmp(&0)),
+        MathType::Signed => Some(transmute::<u32, i32>(result).cmp(&0)),
+        MathType::Float => transmute::<u32, f32>(result).partial_cmp(&0.0),
+    }
+}
+
+/// how `value` compares to `value2` as `math_type`, `None` when either is a NaN float
+pub(super) fn compare(value: u32, value2: u32, math_type: MathType) -> Option<Ordering> {
+    match math_type {
+        MathType::Unsigned => Some(value.cmp(&value2)),
+        MathType::Signed => Some(transmute::<u32, i32>(value).cmp(&transmute(value2))),
+        MathType::Float => transmute::<u32, f32>(value).partial_cmp(&transmute(value2)),
+    }
+}
+
+/// `value` as `from` converted to `to`, and whether it did not fit
+pub(super) fn convert(value: u32, from: MathType, to: MathType) -> (u32, bool) {
+    let float = transmute::<u32, f32>(value);
+    match (from, to) {
+        (MathType::Unsigned, MathType::Signed) => {
+            (value.min(i32::MAX as u32), value > i32::MAX as u32)
+        }
+        (MathType::Signed, MathType::Unsigned) => {
+            let value = transmute::<u32, i32>(value);
+            (value.max(0) as u32, value < 0)
+        }
+        (MathType::Unsigned, MathType::Float) => (transmute(value as f32), false),
+        (MathType::Signed, MathType::Float) => {
+            (transmute(transmute::<u32, i32>(value) as f32), false)
+        }
+        // `as` rounds toward zero and saturates, with NaN as 0. every `u32`, `i32` and `f32`
+        // is exact as a `f64`, so the result fits when it equals the rounded float
+        (MathType::Float, MathType::Unsigned) => {
+            let result = float as u32;
+            (result, result as f64 != float.trunc() as f64)
+        }
+        (MathType::Float, MathType::Signed) => {
+            let result = float as i32;
+            (transmute(result), result as f64 != float.trunc() as f64)
+        }
+        _ => (value, false),
+    }
+}
+
+/// the low `width` bytes of `value`, sign extended
+pub(super) fn sign_extend(value: u32, width: Width) -> u32 {
+    let shift = 32 - width.bytes() * 8;
+    (transmute::<u32, i32>(value << shift) >> shift) as u32
+}
+
+/// the low `width` bytes of `value`, zero extended
+pub(super) fn zero_extend(value: u32, width: Width) -> u32 {
+    let shift = 32 - width.bytes() * 8;
+    (value << shift) >> shift
+}
+
 fn transmute<S, D>(source: S) -> D {
     unsafe {
         let ptr = &source as *const S as *const D;
@@ -194,13 +980,88 @@ fn transmute<S, D>(source: S) -> D {
     }
 }
 
-fn with<S, T, R>(source: (S, S), f: impl FnOnce(T, T) -> T) -> R {
-    let (s1, s2) = source;
-    transmute(f(transmute(s1), transmute(s2)))
+pub(super) struct Arithmetic {
+    unsigned: fn(u32, u32) -> (u32, bool),
+    signed: fn(i32, i32) -> (i32, bool),
+    float: fn(f32, f32) -> f32,
+    /// raised when the unsigned operation overflows, it wraps without one
+    pub(super) trap: Option<TrapKind>,
+    /// whether an unsigned or signed zero divisor raises `DivisionByZero`
+    divides: bool,
+}
+
+impl Arithmetic {
+    /// the result and whether it overflowed, the caller decides what the overflow means
+    pub(super) fn apply(
+        &self,
+        value: u32,
+        value2: u32,
+        math_type: MathType,
+    ) -> Result<(u32, bool), TrapKind> {
+        if self.divides && value2 == 0 && math_type != MathType::Float {
+            return Err(TrapKind::DivisionByZero);
+        }
+        Ok(match math_type {
+            MathType::Unsigned => (self.unsigned)(value, value2),
+            MathType::Signed => {
+                let (result, overflow) = (self.signed)(transmute(value), transmute(value2));
+                (transmute(result), overflow)
+            }
+            MathType::Float => {
+                let (value, value2): (f32, f32) = (transmute(value), transmute(value2));
+                let result = (self.float)(value, value2);
+                let overflow = value.is_finite() && value2.is_finite() && result.is_infinite();
+                (transmute(result), overflow)
+            }
+        })
+    }
 }
 
+pub(super) const ADD: Arithmetic = Arithmetic {
+    unsigned: u32::overflowing_add,
+    signed: i32::overflowing_add,
+    float: |v1, v2| v1 + v2,
+    trap: None,
+    divides: false,
+};
+
+pub(super) const SUB: Arithmetic = Arithmetic {
+    unsigned: u32::overflowing_sub,
+    signed: i32::overflowing_sub,
+    float: |v1, v2| v1 - v2,
+    trap: Some(TrapKind::Underflow),
+    divides: false,
+};
+
+pub(super) const MUL: Arithmetic = Arithmetic {
+    unsigned: u32::overflowing_mul,
+    signed: i32::overflowing_mul,
+    float: |v1, v2| v1 * v2,
+    trap: Some(TrapKind::Overflow),
+    divides: false,
+};
+
+pub(super) const DIV: Arithmetic = Arithmetic {
+    unsigned: u32::overflowing_div,
+    signed: i32::overflowing_div,
+    float: |v1, v2| v1 / v2,
+    trap: None,
+    divides: true,
+};
+
+pub(super) const MOD: Arithmetic = Arithmetic {
+    unsigned: u32::overflowing_rem,
+    signed: i32::overflowing_rem,
+    float: |v1, v2| v1 % v2,
+    trap: None,
+    divides: true,
+};
+
 impl Executable for Instruction {
-    fn execute(&self, interpreter: &mut Interpreter) -> bool {
+    fn execute<M: Memory, I: Io>(
+        &self,
+        interpreter: &mut Interpreter<M, I>,
+    ) -> Result<(), TrapKind> {
         match self {
             Instruction::Swap(reg1, reg2) => {
                 let value1 = *interpreter.get_register(reg1);
@@ -208,146 +1069,163 @@ impl Executable for Instruction {
                 interpreter.set_register(*reg1, value2);
                 interpreter.set_register(*reg2, value1);
             }
-            Instruction::SysCall(print) => {
-                print.execute(interpreter);
+            Instruction::SysCall(syscall) => {
+                interpreter.syscall(syscall)?;
             }
             Instruction::Add(register, value1, value2, math_type) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(
-                    *register,
-                    match math_type {
-                        MathType::Unsigned => value.wrapping_add(value2),
-                        MathType::Signed => {
-                            with::<u32, i32, u32>((value, value2), |v1, v2| v1.wrapping_add(v2))
-                        }
-                        MathType::Float => with::<u32, f32, u32>((value, value2), |v1, v2| v1 + v2),
-                    },
-                );
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = interpreter.arithmetic(&ADD, value, value2, *math_type)?;
+                interpreter.set_register(*register, result);
             }
             Instruction::Sub(register, value1, value2, math_type) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(
-                    *register,
-                    match math_type {
-                        MathType::Unsigned => value - value2,
-                        MathType::Signed => {
-                            with::<u32, i32, u32>((value, value2), |v1, v2| v1.wrapping_sub(v2))
-                        }
-                        MathType::Float => with::<u32, f32, u32>((value, value2), |v1, v2| v1 - v2),
-                    },
-                );
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = interpreter.arithmetic(&SUB, value, value2, *math_type)?;
+                interpreter.set_register(*register, result);
             }
             Instruction::Mul(register, value1, value2, math_type) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(
-                    *register,
-                    match math_type {
-                        MathType::Unsigned => value * value2,
-                        MathType::Signed => {
-                            with::<u32, i32, u32>((value, value2), |v1, v2| v1.wrapping_mul(v2))
-                        }
-                        MathType::Float => with::<u32, f32, u32>((value, value2), |v1, v2| v1 * v2),
-                    },
-                );
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = interpreter.arithmetic(&MUL, value, value2, *math_type)?;
+                interpreter.set_register(*register, result);
             }
             Instruction::Div(register, value1, value2, math_type) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(
-                    *register,
-                    match math_type {
-                        MathType::Unsigned => value / value2,
-                        MathType::Signed => {
-                            with::<u32, i32, u32>((value, value2), |v1, v2| v1.wrapping_div(v2))
-                        }
-                        MathType::Float => with::<u32, f32, u32>((value, value2), |v1, v2| v1 / v2),
-                    },
-                );
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = interpreter.arithmetic(&DIV, value, value2, *math_type)?;
+                interpreter.set_register(*register, result);
             }
             Instruction::Mod(register, value1, value2, math_type) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(
-                    *register,
-                    match math_type {
-                        MathType::Unsigned => value % value2,
-                        MathType::Signed => {
-                            with::<u32, i32, u32>((value, value2), |v1, v2| v1.wrapping_rem(v2))
-                        }
-                        MathType::Float => with::<u32, f32, u32>((value, value2), |v1, v2| v1 % v2),
-                    },
-                );
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = interpreter.arithmetic(&MOD, value, value2, *math_type)?;
+                interpreter.set_register(*register, result);
             }
             Instruction::And(register, value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(*register, value & value2);
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = value & value2;
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
             }
             Instruction::Or(register, value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(*register, value | value2);
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = value | value2;
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
             }
             Instruction::Xor(register, value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(*register, value ^ value2);
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = value ^ value2;
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
             }
             Instruction::Not(register, value) => {
-                let value = value.aquire(interpreter);
+                let value = value.aquire(interpreter)?;
+                interpreter.set_result(!value, MathType::Unsigned, false);
                 interpreter.set_register(*register, !value);
             }
             Instruction::ShiftLeft(register, value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(*register, value << value2);
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = value.checked_shl(value2).unwrap_or(0);
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
             }
             Instruction::ShiftRight(register, value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.set_register(*register, value >> value2);
-            }
-            Instruction::Compare(value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                #[allow(clippy::comparison_chain)]
-                let flag = if value > value2 {
-                    Flag::Greater
-                } else if value < value2 {
-                    Flag::Lesser
-                } else {
-                    Flag::Equal
-                };
-                interpreter.set_flag(flag);
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                let result = value.checked_shr(value2).unwrap_or(0);
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
+            }
+            Instruction::Compare(value1, value2, math_type) => {
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                interpreter.set_ordering(compare(value, value2, *math_type));
             }
             Instruction::Jump(flag, value) => {
                 if interpreter.flags.contains(flag) {
-                    let value = value.aquire(interpreter);
-                    interpreter.set_register(Register::ProgramCounter, value);
+                    let adress = value.aquire(interpreter)?;
+                    interpreter.set_register(Register::ProgramCounter, adress);
+                    if resumes(value) {
+                        interpreter.in_handler = false;
+                    }
                 }
             }
             Instruction::Load(register, value) => {
-                let value = value.aquire(interpreter);
+                let value = value.aquire(interpreter)?;
                 interpreter.set_register(*register, value);
             }
             Instruction::Move(value1, value2) => {
-                let value = value1.aquire(interpreter);
-                let value2 = value2.aquire(interpreter);
-                interpreter.memory.insert(value, MemoryNode::Data(value2));
+                let value = value1.aquire(interpreter)?;
+                let value2 = value2.aquire(interpreter)?;
+                interpreter.write(value, value2, Width::Word)?;
             }
             Instruction::JumpNot(flag, value) => {
                 if !interpreter.flags.contains(flag) {
-                    let value = value.aquire(interpreter);
-                    interpreter.set_register(Register::ProgramCounter, value);
+                    let adress = value.aquire(interpreter)?;
+                    interpreter.set_register(Register::ProgramCounter, adress);
+                    if resumes(value) {
+                        interpreter.in_handler = false;
+                    }
                 }
             }
+            Instruction::LoadMemory(register, value, width) => {
+                let adress = value.aquire(interpreter)?;
+                let value = interpreter.read(adress, *width)?;
+                interpreter.set_register(*register, value);
+            }
+            Instruction::Store(value1, value2, width) => {
+                let adress = value1.aquire(interpreter)?;
+                let value = value2.aquire(interpreter)?;
+                interpreter.write(adress, value, *width)?;
+            }
+            Instruction::Convert(register, value, from, to) => {
+                let value = value.aquire(interpreter)?;
+                let (result, overflow) = convert(value, *from, *to);
+                interpreter.set_result(result, *to, overflow);
+                interpreter.set_register(*register, result);
+            }
+            Instruction::SignExtend(register, value, width) => {
+                let result = sign_extend(value.aquire(interpreter)?, *width);
+                interpreter.set_result(result, MathType::Signed, false);
+                interpreter.set_register(*register, result);
+            }
+            Instruction::ZeroExtend(register, value, width) => {
+                let result = zero_extend(value.aquire(interpreter)?, *width);
+                interpreter.set_result(result, MathType::Unsigned, false);
+                interpreter.set_register(*register, result);
+            }
+            Instruction::Push(value) => {
+                let value = value.aquire(interpreter)?;
+                interpreter.push(value)?;
+            }
+            Instruction::Pop(register) => {
+                let value = interpreter.pop()?;
+                interpreter.set_register(*register, value);
+            }
+            Instruction::Call(flag, value) => {
+                if interpreter.flags.contains(flag) {
+                    interpreter.call(value)?;
+                }
+            }
+            Instruction::CallNot(flag, value) => {
+                if !interpreter.flags.contains(flag) {
+                    interpreter.call(value)?;
+                }
+            }
+            Instruction::Ret => {
+                let adress = interpreter.pop()?;
+                interpreter.set_register(Register::ProgramCounter, adress);
+            }
             Instruction::Halt => {
-                return false;
+                return Err(TrapKind::Halt(*interpreter.get_register(&Register::R0)));
             }
         }
-        true
+        Ok(())
     }
 }
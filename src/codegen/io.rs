@@ -0,0 +1,112 @@
+//! where the syscalls of a guest program go. `Console` is the default, `Buffer` keeps
+//! everything in memory for tests, a `File` receives the output of the program and has
+//! no input
+
+use std::fs::File;
+use std::io::{BufRead, Write};
+
+pub trait Io {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()>;
+
+    /// the next byte of input without taking it, `None` at the end of the input
+    fn peek(&mut self) -> std::io::Result<Option<u8>> {
+        Ok(None)
+    }
+
+    /// takes the byte `peek` returned
+    fn consume(&mut self) {}
+
+    /// how many bytes of the input were taken, `None` when that is not known
+    fn position(&self) -> Option<u64> {
+        None
+    }
+
+    /// continues the input after the first `position` bytes
+    fn seek(&mut self, position: u64) -> std::io::Result<()> {
+        let _ = position;
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// the standard input and output of the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Console;
+
+impl Io for Console {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()
+    }
+
+    fn peek(&mut self) -> std::io::Result<Option<u8>> {
+        Ok(std::io::stdin().lock().fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self) {
+        std::io::stdin().lock().consume(1);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Buffer {
+    /// what the program reads
+    pub input: Vec<u8>,
+    /// how much of `input` was read
+    pub position: usize,
+    /// everything the program wrote
+    pub output: Vec<u8>,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer::default()
+    }
+
+    pub fn with_input(input: impl Into<Vec<u8>>) -> Buffer {
+        Buffer {
+            input: input.into(),
+            ..Buffer::default()
+        }
+    }
+
+    /// the output as text, invalid UTF-8 is replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+}
+
+impl Io for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.output.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn peek(&mut self) -> std::io::Result<Option<u8>> {
+        Ok(self.input.get(self.position).copied())
+    }
+
+    fn consume(&mut self) {
+        self.position += 1;
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.position as u64)
+    }
+
+    fn seek(&mut self, position: u64) -> std::io::Result<()> {
+        match usize::try_from(position) {
+            Ok(position) if position <= self.input.len() => {
+                self.position = position;
+                Ok(())
+            }
+            _ => Err(std::io::ErrorKind::InvalidInput.into()),
+        }
+    }
+}
+
+impl Io for File {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.write_all(bytes)
+    }
+}
@@ -0,0 +1,210 @@
+use super::object::*;
+use super::INSTRUCTION_SIZE;
+use crate::error::{Error, Info, Level, Note};
+use crate::Name;
+use std::collections::{BTreeSet, HashMap};
+
+pub const DUPLICATE_SYMBOL: u64 = 201;
+pub const UNDEFINED_SYMBOL: u64 = 202;
+pub const INVALID_ADRESS: u64 = 203;
+pub const INVALID_RELOCATION: u64 = 204;
+
+#[derive(Default)]
+pub struct Linker {
+    objects: Vec<(Name, Object)>,
+}
+
+struct Base {
+    data: u32,
+    code: u32,
+    index: u32,
+}
+
+impl Base {
+    /// code adresses of an object come after its data, `link` checks that before relocating
+    fn relocate(&self, object: &Object, symbol: &Symbol) -> u32 {
+        match symbol.section {
+            Section::Data => self.data + symbol.adress,
+            Section::Code => self.code + code_offset(object, symbol.adress).unwrap(),
+            Section::Absolute => symbol.adress,
+        }
+    }
+}
+
+/// how far into the code of `object` the adress is, `None` when it points into its data
+fn code_offset(object: &Object, adress: u32) -> Option<u32> {
+    adress.checked_sub(object.data.len() as u32)
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self { objects: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: Name, object: Object) {
+        self.objects.push((name, object));
+    }
+
+    /// places the data of every object, in the order they were added, followed by all of
+    /// their code. relocations first look for a symbol in their own object and then for a
+    /// global one. the entry of the first object becomes the entry of the result, which
+    /// is fully resolved and carries no relocations
+    pub fn link(self) -> Result<Object, Error> {
+        let mut error = Error::new();
+        for (name, object) in &self.objects {
+            let code = object
+                .symbols
+                .iter()
+                .filter(|symbol| symbol.section == Section::Code)
+                .map(|symbol| (format!("code symbol `{}`", symbol.name), symbol.adress));
+            let entry = ("the entry".to_string(), object.entry);
+            for (what, adress) in code.chain([entry]) {
+                if code_offset(object, adress).is_none() {
+                    error.push(Note {
+                        info: Info {
+                            name: "invalid-adress".to_string(),
+                            code: INVALID_ADRESS,
+                            level: Level::Error,
+                        },
+                        message: format!("{} of `{}` points before its code", what, name),
+                        notes: vec![format!(
+                            "it is at {}, the code starts at {}",
+                            adress,
+                            object.data.len()
+                        )],
+                    });
+                }
+            }
+            for relocation in &object.relocations {
+                if !relocation.fits(&object.code) {
+                    error.push(Note {
+                        info: Info {
+                            name: "invalid-relocation".to_string(),
+                            code: INVALID_RELOCATION,
+                            level: Level::Error,
+                        },
+                        message: format!(
+                            "relocation of `{}` in `{}` names an operand its code does not have",
+                            relocation.symbol, name
+                        ),
+                        notes: vec![format!(
+                            "it names operand {} of instruction {}, the code has {} instructions",
+                            relocation.operand,
+                            relocation.instruction,
+                            object.code.len()
+                        )],
+                    });
+                }
+            }
+        }
+        if !error.is_empty() {
+            return Err(error);
+        }
+        let data_len: u32 = self.objects.iter().map(|(_, object)| object.data.len() as u32).sum();
+        let mut bases = Vec::new();
+        let mut base = Base {
+            data: 0,
+            code: data_len,
+            index: 0,
+        };
+        for (_, object) in &self.objects {
+            bases.push(Base { ..base });
+            base.data += object.data.len() as u32;
+            base.code += (object.code.len() * INSTRUCTION_SIZE) as u32;
+            base.index += object.code.len() as u32;
+        }
+
+        let mut globals: HashMap<&Name, (usize, u32)> = HashMap::new();
+        for (index, (name, object)) in self.objects.iter().enumerate() {
+            for symbol in &object.symbols {
+                if symbol.binding != Binding::Global {
+                    continue;
+                }
+                let adress = bases[index].relocate(object, symbol);
+                match globals.get(&symbol.name) {
+                    Some((other, _)) => error.push(Note {
+                        info: Info {
+                            name: "duplicate-symbol".to_string(),
+                            code: DUPLICATE_SYMBOL,
+                            level: Level::Error,
+                        },
+                        message: format!("symbol `{}` is defined more than once", symbol.name),
+                        notes: vec![format!(
+                            "defined in `{}` and `{}`",
+                            self.objects[*other].0, name
+                        )],
+                    }),
+                    None => {
+                        globals.insert(&symbol.name, (index, adress));
+                    }
+                }
+            }
+        }
+
+        let mut data = Vec::new();
+        let mut code = Vec::new();
+        let mut symbols = Vec::new();
+        for (index, (_, object)) in self.objects.iter().enumerate() {
+            data.extend(&object.data);
+            code.extend(&object.code);
+            for symbol in &object.symbols {
+                symbols.push(Symbol {
+                    adress: bases[index].relocate(object, symbol),
+                    ..symbol.clone()
+                });
+            }
+        }
+
+        let mut undefined = BTreeSet::new();
+        for (index, (name, object)) in self.objects.iter().enumerate() {
+            let locals: HashMap<&Name, u32> = object
+                .symbols
+                .iter()
+                .rev()
+                .map(|symbol| (&symbol.name, bases[index].relocate(object, symbol)))
+                .collect();
+            for relocation in &object.relocations {
+                let adress = locals
+                    .get(&relocation.symbol)
+                    .copied()
+                    .or_else(|| globals.get(&relocation.symbol).map(|(_, adress)| *adress));
+                match adress {
+                    Some(adress) => Relocation {
+                        instruction: bases[index].index + relocation.instruction,
+                        ..relocation.clone()
+                    }
+                    .apply(&mut code, adress),
+                    None => {
+                        undefined.insert((relocation.symbol.clone(), name.clone()));
+                    }
+                }
+            }
+        }
+        for (symbol, name) in undefined {
+            error.push(Note {
+                info: Info {
+                    name: "undefined-symbol".to_string(),
+                    code: UNDEFINED_SYMBOL,
+                    level: Level::Error,
+                },
+                message: format!("cannot find symbol `{}`", symbol),
+                notes: vec![format!("referenced in `{}`", name)],
+            });
+        }
+
+        if !error.is_empty() {
+            return Err(error);
+        }
+        let entry = match self.objects.first() {
+            Some((_, object)) => bases[0].code + code_offset(object, object.entry).unwrap(),
+            None => data_len,
+        };
+        Ok(Object {
+            data,
+            code,
+            entry,
+            symbols,
+            relocations: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,207 @@
+//! the memories the interpreter can run on. `BTreeMap` only stores what was written and is
+//! the default, `FlatMemory` keeps the bytes of the adress space in `Vec` pages and is faster
+//! for programs that stay within its limit
+
+use super::asm::Instruction;
+use super::interpreter::TrapKind;
+use std::collections::BTreeMap;
+
+/// memory is byte addressed, every instruction occupies `INSTRUCTION_SIZE` bytes
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryNode {
+    None,
+    Data(u8),
+    Instruction(Instruction),
+    FilledByInstruction,
+}
+
+pub trait Memory {
+    /// `None` and `Some(MemoryNode::None)` both mean nothing was written to `adress`
+    fn get(&self, adress: u32) -> Option<&MemoryNode>;
+    /// `insert` fails from this adress on, `None` when it takes every adress
+    fn end(&self) -> Option<u32> {
+        None
+    }
+    /// fails with `TrapKind::OutOfMemory` when `adress` lies outside of the memory
+    fn insert(&mut self, adress: u32, node: MemoryNode) -> Result<(), TrapKind>;
+    /// every written node, in adress order
+    fn nodes(&self) -> Box<dyn Iterator<Item = (u32, &MemoryNode)> + '_>;
+    /// forgets what was written to `adress`
+    fn remove(&mut self, adress: u32) {
+        let _ = self.insert(adress, MemoryNode::None);
+    }
+}
+
+impl Memory for BTreeMap<u32, MemoryNode> {
+    fn get(&self, adress: u32) -> Option<&MemoryNode> {
+        BTreeMap::get(self, &adress)
+    }
+
+    fn insert(&mut self, adress: u32, node: MemoryNode) -> Result<(), TrapKind> {
+        BTreeMap::insert(self, adress, node);
+        Ok(())
+    }
+
+    fn nodes(&self) -> Box<dyn Iterator<Item = (u32, &MemoryNode)> + '_> {
+        Box::new(
+            self.iter()
+                .filter(|(_, node)| **node != MemoryNode::None)
+                .map(|(adress, node)| (*adress, node)),
+        )
+    }
+
+    fn remove(&mut self, adress: u32) {
+        BTreeMap::remove(self, &adress);
+    }
+}
+
+/// the number of bytes in a page of `FlatMemory`, a multiple of 64
+pub const PAGE_SIZE: u32 = 1024;
+
+/// `MemoryNode::Data` of every byte, so `FlatMemory` can lend out the bytes it stores as nodes
+static DATA: [MemoryNode; 256] = {
+    const NONE: MemoryNode = MemoryNode::None;
+    let mut nodes = [NONE; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        nodes[byte] = MemoryNode::Data(byte as u8);
+        byte += 1;
+    }
+    nodes
+};
+
+/// the data of `PAGE_SIZE` adresses and a bit for each that is set when it holds data
+#[derive(Debug, Clone, PartialEq)]
+struct Page {
+    bytes: Box<[u8]>,
+    written: Box<[u64]>,
+}
+
+impl Page {
+    fn new() -> Page {
+        Page {
+            bytes: vec![0; PAGE_SIZE as usize].into_boxed_slice(),
+            written: vec![0; PAGE_SIZE as usize / 64].into_boxed_slice(),
+        }
+    }
+
+    fn get(&self, offset: usize) -> Option<u8> {
+        let written = self.written[offset / 64] & (1 << (offset % 64)) != 0;
+        written.then_some(self.bytes[offset])
+    }
+
+    fn set(&mut self, offset: usize, byte: Option<u8>) {
+        match byte {
+            Some(byte) => {
+                self.bytes[offset] = byte;
+                self.written[offset / 64] |= 1 << (offset % 64);
+            }
+            None => self.written[offset / 64] &= !(1 << (offset % 64)),
+        }
+    }
+}
+
+/// the adresses `0..limit`. data is stored a byte per adress in pages that are allocated on
+/// the first write to them, instructions and the adresses they fill are kept aside
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatMemory {
+    pages: Vec<Option<Page>>,
+    /// every node that is neither data nor `MemoryNode::None`
+    instructions: BTreeMap<u32, MemoryNode>,
+    limit: u32,
+}
+
+impl FlatMemory {
+    /// `limit` is the size of the memory in bytes
+    pub fn new(limit: u32) -> FlatMemory {
+        FlatMemory {
+            pages: Vec::new(),
+            instructions: BTreeMap::new(),
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// every adress that holds data and its byte, in adress order
+    fn data(&self) -> impl Iterator<Item = (u32, u8)> + '_ {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| Some((index as u32 * PAGE_SIZE, page.as_ref()?)))
+            .flat_map(|(base, page)| {
+                (0..PAGE_SIZE as usize)
+                    .filter_map(move |offset| Some((base + offset as u32, page.get(offset)?)))
+            })
+    }
+}
+
+impl Default for FlatMemory {
+    /// a mebibyte
+    fn default() -> FlatMemory {
+        FlatMemory::new(1 << 20)
+    }
+}
+
+impl Memory for FlatMemory {
+    fn get(&self, adress: u32) -> Option<&MemoryNode> {
+        let page = self.pages.get((adress / PAGE_SIZE) as usize);
+        match page.and_then(|page| page.as_ref()?.get((adress % PAGE_SIZE) as usize)) {
+            Some(byte) => Some(&DATA[byte as usize]),
+            None => self.instructions.get(&adress),
+        }
+    }
+
+    fn end(&self) -> Option<u32> {
+        Some(self.limit)
+    }
+
+    fn insert(&mut self, adress: u32, node: MemoryNode) -> Result<(), TrapKind> {
+        if adress >= self.limit {
+            return Err(TrapKind::OutOfMemory(adress));
+        }
+        let (index, offset) = ((adress / PAGE_SIZE) as usize, (adress % PAGE_SIZE) as usize);
+        let byte = match node {
+            MemoryNode::Data(byte) => {
+                self.instructions.remove(&adress);
+                Some(byte)
+            }
+            MemoryNode::None => {
+                self.instructions.remove(&adress);
+                None
+            }
+            node => {
+                self.instructions.insert(adress, node);
+                None
+            }
+        };
+        if byte.is_some() && index >= self.pages.len() {
+            self.pages.resize_with(index + 1, || None);
+        }
+        match self.pages.get_mut(index) {
+            Some(Some(page)) => page.set(offset, byte),
+            Some(page @ None) if byte.is_some() => page.insert(Page::new()).set(offset, byte),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn nodes(&self) -> Box<dyn Iterator<Item = (u32, &MemoryNode)> + '_> {
+        let mut data = self.data().peekable();
+        let mut instructions = self.instructions.iter().peekable();
+        Box::new(std::iter::from_fn(move || {
+            let instruction = instructions.peek().map(|(adress, _)| **adress);
+            match (data.peek(), instruction) {
+                (Some((adress, _)), Some(instruction)) if instruction < *adress => {
+                    instructions.next().map(|(adress, node)| (*adress, node))
+                }
+                (Some(_), _) => data
+                    .next()
+                    .map(|(adress, byte)| (adress, &DATA[byte as usize])),
+                (None, _) => instructions.next().map(|(adress, node)| (*adress, node)),
+            }
+        }))
+    }
+}
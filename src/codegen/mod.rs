@@ -1,33 +1,521 @@
 use std::collections::HashMap;
+use std::marker::PhantomData;
 
+use crate::error::{Error, Info, Level, Note};
 use crate::StackFrame;
 
-use crate::{Type, Variable, Ref, Children};
-use asm::{Flag, Instruction};
+use crate::{Name, Ref, Children};
+use abi::{Frame, CALLEE_SAVED};
+use asm::{Flag, Instruction, Operand, Value};
+use linker::Linker;
+use object::{Binding, Object, Relocation, Section, Symbol};
 
+pub mod abi;
 pub mod asm;
+pub mod assembly;
+pub mod debugger;
+pub mod disassembler;
+pub mod engine;
+pub mod files;
+pub mod heap;
+pub mod history;
 pub mod interpreter;
+pub mod io;
+pub mod linker;
+pub mod memory;
+pub mod object;
+pub mod profiler;
+pub mod snapshot;
+pub mod trace;
 
 pub const INSTRUCTION_SIZE: usize = 4;
 
 pub struct Assembler {
-    statics: HashMap<Variable, (Type, u64)>,
+    statics: Vec<Data>,
     blocks: HashMap<Ref<Block>, Block>,
     stackframes: HashMap<Ref<StackFrame>, StackFrame>,
     init: Ref<Block>,
+    entry: Option<Name>,
+    globals: Vec<Name>,
+    next: u64,
 }
 
 pub struct Block {
     instructions: Vec<Ir>,
     stackframe: Ref<StackFrame>,
+    /// functions get a prologue and an epilogue at every `Ir::Return`, see `abi`
+    function: bool,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Ir {
     Instruction(Instruction),
+    Label(Name),
+    Labeled(Name, Box<Ir>),
+    Jump(Flag, Name),
+    JumpNot(Flag, Name),
+    /// an instruction whose operands at the given indices are replaced by the adress of a label
+    Symbolic(Instruction, Vec<(usize, Name)>),
     Call(Flag, Ref<Block>),
     CallNot(Flag, Ref<Block>),
+    /// the epilogue of the function followed by `ret`, only `ret` outside of functions
+    Return,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    Label(Name),
+    /// laid out little endian, four bytes each
+    Words(Vec<u32>),
+    Bytes(Vec<u8>),
+    Align(u32),
+}
+
+impl Data {
+    /// the bytes laid out for `Words` and `Bytes`, labels and alignment have none
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            Data::Words(words) => words.iter().flat_map(|word| word.to_le_bytes()).collect(),
+            Data::Bytes(bytes) => bytes.clone(),
+            Data::Label(_) | Data::Align(_) => Vec::new(),
+        }
+    }
 }
 
 pub trait Compilable {
     fn compile(&mut self, children: &mut Children, assembler: &mut Assembler, stackframe: Ref<StackFrame>) -> Ref<Block>;
 }
+
+impl Block {
+    pub fn new(stackframe: Ref<StackFrame>) -> Self {
+        Self { instructions: Vec::new(), stackframe, function: false }
+    }
+
+    pub fn instructions(&self) -> &[Ir] {
+        &self.instructions
+    }
+
+    pub fn stackframe(&self) -> Ref<StackFrame> {
+        self.stackframe
+    }
+
+    pub fn is_function(&self) -> bool {
+        self.function
+    }
+}
+
+impl TryFrom<String> for Ir {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, String> {
+        let value = value.trim();
+        let (head, rest) = value.split_once(' ').unwrap_or((value, ""));
+        let rest = rest.trim();
+        match head {
+            "return" if rest.is_empty() => Ok(Ir::Return),
+            "jmp:" | "jmpn:" => {
+                let (flag, label) = rest
+                    .split_once(' ')
+                    .ok_or_else(|| format!("expected flag and label in `{}`", value))?;
+                let flag = Flag::try_from(flag.to_string())
+                    .map_err(|_| format!("invalid flag `{}`", flag))?;
+                let label = label.trim().to_string();
+                Ok(match head {
+                    "jmp:" => Ir::Jump(flag, label),
+                    _ => Ir::JumpNot(flag, label),
+                })
+            }
+            _ if head.ends_with(':') => {
+                let label = head.trim_end_matches(':').to_string();
+                if rest.is_empty() {
+                    Ok(Ir::Label(label))
+                } else {
+                    Ok(Ir::Labeled(label, Box::new(Ir::try_from(rest.to_string())?)))
+                }
+            }
+            _ => Instruction::try_from(value.to_string())
+                .map(Ir::Instruction)
+                .map_err(|_| "invalid instruction".to_string()),
+        }
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        let init = Ref(0, PhantomData);
+        let mut blocks = HashMap::new();
+        blocks.insert(init, Block::new(Ref(0, PhantomData)));
+        Self {
+            statics: Vec::new(),
+            blocks,
+            stackframes: HashMap::new(),
+            init,
+            entry: None,
+            globals: Vec::new(),
+            next: 1,
+        }
+    }
+
+    pub fn init(&self) -> Ref<Block> {
+        self.init
+    }
+
+    /// starts execution at `label` in the init block instead of at its first instruction
+    pub fn set_entry(&mut self, label: Name) {
+        self.entry = Some(label);
+    }
+
+    /// exports `label` (a label of the init block or a data label) to other objects when linking
+    pub fn set_global(&mut self, label: Name) {
+        self.globals.push(label);
+    }
+
+    pub fn add_stackframe(&mut self, stackframe: StackFrame) -> Ref<StackFrame> {
+        let reference = Ref(self.next, PhantomData);
+        self.next += 1;
+        self.stackframes.insert(reference, stackframe);
+        reference
+    }
+
+    pub fn stackframe(&self, stackframe: Ref<StackFrame>) -> Option<&StackFrame> {
+        self.stackframes.get(&stackframe)
+    }
+
+    pub fn add_block(&mut self, stackframe: Ref<StackFrame>) -> Ref<Block> {
+        let reference = Ref(self.next, PhantomData);
+        self.next += 1;
+        self.blocks.insert(reference, Block::new(stackframe));
+        reference
+    }
+
+    /// a block that is called as a function, following the calling convention in `abi`
+    pub fn add_function(&mut self, stackframe: Ref<StackFrame>) -> Ref<Block> {
+        let reference = self.add_block(stackframe);
+        self.blocks.get_mut(&reference).unwrap().function = true;
+        reference
+    }
+
+    /// the frame of a function: one local per variable of its stackframe, sorted by name,
+    /// and the callee saved registers its instructions name
+    pub fn frame(&self, block: Ref<Block>) -> Frame {
+        let block = &self.blocks[&block];
+        let mut locals: Vec<Name> = match self.stackframes.get(&block.stackframe) {
+            Some(stackframe) => stackframe.variables.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+        locals.sort();
+        let used: Vec<_> = block
+            .instructions
+            .iter()
+            .filter_map(Ir::instruction)
+            .flat_map(Instruction::registers)
+            .collect();
+        let saved = CALLEE_SAVED
+            .into_iter()
+            .filter(|register| used.contains(register))
+            .collect();
+        Frame { locals, saved }
+    }
+
+    /// the instructions of a block with its prologue and epilogues
+    fn expand(&self, reference: Ref<Block>) -> Vec<Ir> {
+        let block = &self.blocks[&reference];
+        if !block.function {
+            return block.instructions.clone();
+        }
+        let frame = self.frame(reference);
+        let epilogue = frame.epilogue();
+        let mut output: Vec<Ir> = frame.prologue().into_iter().map(Ir::Instruction).collect();
+        for ir in &block.instructions {
+            ir.expand(&epilogue, &mut output);
+        }
+        output
+    }
+
+    pub fn block(&self, block: Ref<Block>) -> Option<&Block> {
+        self.blocks.get(&block)
+    }
+
+    pub fn add_static(&mut self, name: Name, data: Vec<u32>) {
+        self.statics.push(Data::Label(name));
+        self.statics.push(Data::Words(data));
+    }
+
+    pub fn add_data(&mut self, data: Data) {
+        self.statics.push(data);
+    }
+
+    pub fn add_ir(&mut self, ir: Ir) {
+        self.add_ir_to(self.init, ir);
+    }
+
+    pub fn add_ir_to(&mut self, block: Ref<Block>, ir: Ir) {
+        self.blocks
+            .get_mut(&block)
+            .expect("unknown block")
+            .instructions
+            .push(ir);
+    }
+
+    fn order(&self) -> Vec<Ref<Block>> {
+        let mut order: Vec<Ref<Block>> = self
+            .blocks
+            .keys()
+            .copied()
+            .filter(|block| *block != self.init)
+            .collect();
+        order.sort();
+        order.insert(0, self.init);
+        order
+    }
+
+    /// assembles and links the program on its own, failing on undefined labels
+    pub fn compile(&mut self, reserved: u32) -> Result<(Vec<u8>, Vec<Instruction>, u32), Error> {
+        if let Some(label) = self.entry.as_ref().filter(|label| !self.defines(label)) {
+            let mut error = Error::new();
+            error.push(Note {
+                info: Info {
+                    name: "undefined-symbol".to_string(),
+                    code: linker::UNDEFINED_SYMBOL,
+                    level: Level::Error,
+                },
+                message: format!("cannot find entry label `{}`", label),
+                notes: vec!["the entry has to be a label of the init block".to_string()],
+            });
+            return Err(error);
+        }
+        let mut linker = Linker::new();
+        linker.add("main".to_string(), self.assemble(reserved));
+        let object = linker.link()?;
+        Ok((object.data, object.code, object.entry))
+    }
+
+    /// whether `label` is a label of the init block
+    fn defines(&self, label: &Name) -> bool {
+        let mut labels = HashMap::new();
+        for ir in self.expand(self.init) {
+            ir.collect_labels(0, &mut labels);
+        }
+        labels.contains_key(label)
+    }
+
+    fn symbol(&self, block: Ref<Block>, label: &Name) -> Name {
+        if block == self.init {
+            label.clone()
+        } else {
+            format!(".block{}.{}", block.0, label)
+        }
+    }
+
+    fn binding(&self, name: &Name) -> Binding {
+        let exported = |name: &str| self.globals.iter().any(|global| global == name);
+        match name.strip_suffix("_len") {
+            _ if exported(name) => Binding::Global,
+            Some(name) if exported(name) => Binding::Global,
+            _ => Binding::Local,
+        }
+    }
+
+    /// lays out the statics after `reserved` zeroed bytes, followed by every block
+    /// with the init block first. every data label `name` also defines `name_len`,
+    /// the number of bytes emitted under it.
+    /// every symbol reference becomes a relocation; the ones defined here are already applied.
+    /// panics when the entry is not a label of the init block, `compile` reports it instead
+    pub fn assemble(&mut self, reserved: u32) -> Object {
+        let mut data = vec![0; reserved as usize];
+        let mut symbols: Vec<Symbol> = Vec::new();
+        let mut label: Option<usize> = None;
+        for item in &self.statics {
+            match item {
+                Data::Label(name) => {
+                    symbols.push(Symbol {
+                        name: name.clone(),
+                        adress: data.len() as u32,
+                        section: Section::Data,
+                        binding: self.binding(name),
+                    });
+                    let length = format!("{}_len", name);
+                    symbols.push(Symbol {
+                        binding: self.binding(&length),
+                        name: length,
+                        adress: 0,
+                        section: Section::Absolute,
+                    });
+                    label = Some(symbols.len() - 1);
+                }
+                Data::Words(_) | Data::Bytes(_) => {
+                    let bytes = item.bytes();
+                    if let Some(length) = label {
+                        symbols[length].adress += bytes.len() as u32;
+                    }
+                    data.extend(bytes);
+                }
+                Data::Align(alignment) => {
+                    while !(data.len() as u32).is_multiple_of(*alignment.max(&1)) {
+                        data.push(0);
+                    }
+                }
+            }
+        }
+
+        let order = self.order();
+        let bodies: HashMap<Ref<Block>, Vec<Ir>> = order
+            .iter()
+            .map(|reference| (*reference, self.expand(*reference)))
+            .collect();
+        let mut adress = data.len() as u32;
+        let mut labels = HashMap::new();
+        for reference in &order {
+            symbols.push(Symbol {
+                name: format!(".block{}", reference.0),
+                adress,
+                section: Section::Code,
+                binding: Binding::Local,
+            });
+            let mut local = HashMap::new();
+            for ir in &bodies[reference] {
+                ir.collect_labels(adress, &mut local);
+                adress += ir.len() * INSTRUCTION_SIZE as u32;
+            }
+            let mut sorted: Vec<(&Name, &u32)> = local.iter().collect();
+            sorted.sort_by_key(|(name, adress)| (**adress, *name));
+            for (name, adress) in sorted {
+                let name = self.symbol(*reference, name);
+                symbols.push(Symbol {
+                    binding: self.binding(&name),
+                    name,
+                    adress: *adress,
+                    section: Section::Code,
+                });
+            }
+            labels.insert(*reference, local);
+        }
+
+        let mut code = Vec::new();
+        let mut relocations = Vec::new();
+        for reference in &order {
+            let local = &labels[reference];
+            let name = |label: &Name| -> Name {
+                if local.contains_key(label) {
+                    self.symbol(*reference, label)
+                } else {
+                    label.clone()
+                }
+            };
+            for ir in &bodies[reference] {
+                ir.lower(&name, &mut code, &mut relocations);
+            }
+        }
+
+        let defined: HashMap<&Name, u32> = symbols
+            .iter()
+            .map(|symbol| (&symbol.name, symbol.adress))
+            .collect();
+        for relocation in &relocations {
+            if let Some(adress) = defined.get(&relocation.symbol) {
+                relocation.apply(&mut code, *adress);
+            }
+        }
+
+        let entry = match &self.entry {
+            Some(label) => match labels[&self.init].get(label) {
+                Some(adress) => *adress,
+                None => panic!("undefined entry label `{}`", label),
+            },
+            None => defined[&format!(".block{}", self.init.0)],
+        };
+        Object {
+            data,
+            code,
+            entry,
+            symbols,
+            relocations,
+        }
+    }
+}
+
+impl Ir {
+    fn instruction(&self) -> Option<&Instruction> {
+        match self {
+            Ir::Instruction(instruction) | Ir::Symbolic(instruction, _) => Some(instruction),
+            Ir::Labeled(_, ir) => ir.instruction(),
+            _ => None,
+        }
+    }
+
+    fn expand(&self, epilogue: &[Instruction], output: &mut Vec<Ir>) {
+        match self {
+            Ir::Return => output.extend(epilogue.iter().copied().map(Ir::Instruction)),
+            Ir::Labeled(label, ir) => {
+                output.push(Ir::Label(label.clone()));
+                ir.expand(epilogue, output);
+            }
+            ir => output.push(ir.clone()),
+        }
+    }
+
+    fn len(&self) -> u32 {
+        match self {
+            Ir::Label(_) => 0,
+            Ir::Labeled(_, ir) => ir.len(),
+            _ => 1,
+        }
+    }
+
+    fn collect_labels(&self, adress: u32, labels: &mut HashMap<Name, u32>) {
+        match self {
+            Ir::Label(label) => {
+                labels.insert(label.clone(), adress);
+            }
+            Ir::Labeled(label, ir) => {
+                labels.insert(label.clone(), adress);
+                ir.collect_labels(adress, labels);
+            }
+            _ => (),
+        }
+    }
+
+    fn lower(
+        &self,
+        name: &impl Fn(&Name) -> Name,
+        output: &mut Vec<Instruction>,
+        relocations: &mut Vec<Relocation>,
+    ) {
+        let (instruction, symbols) = match self {
+            Ir::Instruction(instruction) => (*instruction, vec![]),
+            Ir::Label(_) => return,
+            Ir::Labeled(_, ir) => return ir.lower(name, output, relocations),
+            Ir::Jump(flag, label) => (
+                Instruction::Jump(*flag, Value::Direct(Operand::U32(0))),
+                vec![(0, label.clone())],
+            ),
+            Ir::JumpNot(flag, label) => (
+                Instruction::JumpNot(*flag, Value::Direct(Operand::U32(0))),
+                vec![(0, label.clone())],
+            ),
+            Ir::Symbolic(instruction, symbols) => (*instruction, symbols.clone()),
+            Ir::Return => (Instruction::Ret, vec![]),
+            Ir::Call(flag, block) => (
+                Instruction::Call(*flag, Value::Direct(Operand::U32(0))),
+                vec![(0, format!(".block{}", block.0))],
+            ),
+            Ir::CallNot(flag, block) => (
+                Instruction::CallNot(*flag, Value::Direct(Operand::U32(0))),
+                vec![(0, format!(".block{}", block.0))],
+            ),
+        };
+        for (operand, label) in symbols {
+            relocations.push(Relocation {
+                instruction: output.len() as u32,
+                operand: operand as u32,
+                symbol: name(&label),
+            });
+        }
+        output.push(instruction);
+    }
+}
@@ -0,0 +1,584 @@
+use super::asm::*;
+use super::INSTRUCTION_SIZE;
+use crate::Name;
+
+pub const MAGIC: [u8; 4] = *b"PLAC";
+pub const VERSION: u16 = 5;
+
+/// every instruction is encoded in the same number of bytes, 10 per byte it occupies in memory,
+/// enough for a header and three values
+pub const ENCODED_INSTRUCTION_SIZE: usize = INSTRUCTION_SIZE * 10;
+pub const ENCODED_VALUE_SIZE: usize = 1 + 2 * ENCODED_OPERAND_SIZE;
+pub const ENCODED_OPERAND_SIZE: usize = 5;
+
+/// the assembled form of a program, laid out the same way `Interpreter::load` expects it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub data: Vec<u8>,
+    pub code: Vec<Instruction>,
+    pub entry: u32,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: Name,
+    pub adress: u32,
+    pub section: Section,
+    pub binding: Binding,
+}
+
+/// where a symbol points to, which decides how it moves when objects are linked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Data,
+    Code,
+    Absolute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Local,
+    Global,
+}
+
+/// the operand at index `operand` of instruction `instruction` holds the adress of `symbol`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub instruction: u32,
+    pub operand: u32,
+    pub symbol: Name,
+}
+
+impl Relocation {
+    /// whether the instruction and operand it names exist in `code`
+    pub fn fits(&self, code: &[Instruction]) -> bool {
+        code.get(self.instruction as usize)
+            .copied()
+            .is_some_and(|mut instruction| instruction.operands_mut().len() > self.operand as usize)
+    }
+
+    pub fn apply(&self, code: &mut [Instruction], adress: u32) {
+        let mut instruction = code[self.instruction as usize];
+        *instruction.operands_mut()[self.operand as usize] = Operand::U32(adress);
+        code[self.instruction as usize] = instruction;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    TrailingBytes,
+    InvalidOpcode(u8),
+    InvalidSysCall(u8),
+    /// carries the whole encoded index, which can be wider than a byte in an operand
+    InvalidRegister(u32),
+    InvalidFlag(u8),
+    InvalidMathType(u8),
+    InvalidWidth(u8),
+    InvalidValue(u8),
+    InvalidOperand(u8),
+    /// a byte the decoded instruction or value does not use was not zero
+    InvalidPadding,
+    InvalidSymbol,
+    InvalidSection(u8),
+    InvalidBinding(u8),
+    InvalidRelocation,
+    /// carries the length of a symbol name too long for the two bytes its length is written in
+    NameTooLong(usize),
+}
+
+pub(super) struct Reader<'a> {
+    pub(super) bytes: &'a [u8],
+    pub(super) index: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn take(&mut self, count: usize) -> Result<&'a [u8], ObjectError> {
+        let end = self.index.checked_add(count).ok_or(ObjectError::UnexpectedEnd)?;
+        let bytes = self.bytes.get(self.index..end).ok_or(ObjectError::UnexpectedEnd)?;
+        self.index = end;
+        Ok(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, ObjectError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn u16(&mut self) -> Result<u16, ObjectError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, ObjectError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64, ObjectError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+fn opcode(instruction: &Instruction) -> u8 {
+    match instruction {
+        Instruction::Add(..) => 0,
+        Instruction::Sub(..) => 1,
+        Instruction::Mul(..) => 2,
+        Instruction::Div(..) => 3,
+        Instruction::Mod(..) => 4,
+        Instruction::And(..) => 5,
+        Instruction::Or(..) => 6,
+        Instruction::Xor(..) => 7,
+        Instruction::Not(..) => 8,
+        Instruction::ShiftLeft(..) => 9,
+        Instruction::ShiftRight(..) => 10,
+        Instruction::Compare(..) => 11,
+        Instruction::Jump(..) => 12,
+        Instruction::JumpNot(..) => 13,
+        Instruction::Load(..) => 14,
+        Instruction::Move(..) => 15,
+        Instruction::Swap(..) => 16,
+        Instruction::Halt => 17,
+        Instruction::SysCall(..) => 18,
+        Instruction::Push(..) => 19,
+        Instruction::Pop(..) => 20,
+        Instruction::Call(..) => 21,
+        Instruction::CallNot(..) => 22,
+        Instruction::Ret => 23,
+        Instruction::LoadMemory(..) => 24,
+        Instruction::Store(..) => 25,
+        Instruction::Convert(..) => 26,
+        Instruction::SignExtend(..) => 27,
+        Instruction::ZeroExtend(..) => 28,
+    }
+}
+
+pub fn encode_operand(operand: &Operand) -> [u8; ENCODED_OPERAND_SIZE] {
+    let (tag, payload) = match operand {
+        Operand::U32(value) => (0, *value),
+        Operand::Register(register) => (1, register.index() as u32),
+    };
+    let mut bytes = [tag, 0, 0, 0, 0];
+    bytes[1..].copy_from_slice(&payload.to_le_bytes());
+    bytes
+}
+
+pub fn decode_operand(bytes: &[u8; ENCODED_OPERAND_SIZE]) -> Result<Operand, ObjectError> {
+    let payload = u32::from_le_bytes(bytes[1..].try_into().unwrap());
+    match bytes[0] {
+        0 => Ok(Operand::U32(payload)),
+        1 => Register::from_index(payload as usize)
+            .map(Operand::Register)
+            .ok_or(ObjectError::InvalidRegister(payload)),
+        tag => Err(ObjectError::InvalidOperand(tag)),
+    }
+}
+
+pub fn encode_value(value: &Value) -> [u8; ENCODED_VALUE_SIZE] {
+    let (tag, first, second) = match value {
+        Value::Direct(operand) => (0, operand, None),
+        Value::Indirect(operand) => (1, operand, None),
+        Value::OperationalDirect(operand1, operand2) => (2, operand1, Some(operand2)),
+        Value::OperationalIndirect(operand1, operand2) => (3, operand1, Some(operand2)),
+    };
+    let mut bytes = [0; ENCODED_VALUE_SIZE];
+    bytes[0] = tag;
+    bytes[1..1 + ENCODED_OPERAND_SIZE].copy_from_slice(&encode_operand(first));
+    if let Some(second) = second {
+        bytes[1 + ENCODED_OPERAND_SIZE..].copy_from_slice(&encode_operand(second));
+    }
+    bytes
+}
+
+pub fn decode_value(bytes: &[u8; ENCODED_VALUE_SIZE]) -> Result<Value, ObjectError> {
+    let first = decode_operand(bytes[1..1 + ENCODED_OPERAND_SIZE].try_into().unwrap());
+    let second = decode_operand(bytes[1 + ENCODED_OPERAND_SIZE..].try_into().unwrap());
+    let value = match bytes[0] {
+        0 => Value::Direct(first?),
+        1 => Value::Indirect(first?),
+        2 => Value::OperationalDirect(first?, second?),
+        3 => Value::OperationalIndirect(first?, second?),
+        tag => return Err(ObjectError::InvalidValue(tag)),
+    };
+    // the unused operand is all zeros, the same as `encode_value` writes it
+    if encode_value(&value) != *bytes {
+        return Err(ObjectError::InvalidPadding);
+    }
+    Ok(value)
+}
+
+fn flag_index(flag: Flag) -> u8 {
+    Flag::ALL.iter().position(|other| *other == flag).unwrap() as u8
+}
+
+fn width_index(width: Width) -> u8 {
+    Width::ALL.iter().position(|other| *other == width).unwrap() as u8
+}
+
+fn math_type_index(math_type: MathType) -> u8 {
+    MathType::ALL
+        .iter()
+        .position(|other| *other == math_type)
+        .unwrap() as u8
+}
+
+/// layout: opcode, register/flag/syscall, second register/math type, math type/width, first value, second value
+pub fn encode_instruction(instruction: &Instruction) -> [u8; ENCODED_INSTRUCTION_SIZE] {
+    let mut bytes = [0; ENCODED_INSTRUCTION_SIZE];
+    bytes[0] = opcode(instruction);
+    let mut values = Vec::new();
+    match instruction {
+        Instruction::Add(register, value1, value2, math_type)
+        | Instruction::Sub(register, value1, value2, math_type)
+        | Instruction::Mul(register, value1, value2, math_type)
+        | Instruction::Div(register, value1, value2, math_type)
+        | Instruction::Mod(register, value1, value2, math_type) => {
+            bytes[1] = register.index() as u8;
+            bytes[3] = math_type_index(*math_type);
+            values = vec![value1, value2];
+        }
+        Instruction::And(register, value1, value2)
+        | Instruction::Or(register, value1, value2)
+        | Instruction::Xor(register, value1, value2)
+        | Instruction::ShiftLeft(register, value1, value2)
+        | Instruction::ShiftRight(register, value1, value2) => {
+            bytes[1] = register.index() as u8;
+            values = vec![value1, value2];
+        }
+        Instruction::Not(register, value) | Instruction::Load(register, value) => {
+            bytes[1] = register.index() as u8;
+            values = vec![value];
+        }
+        Instruction::Compare(value1, value2, math_type) => {
+            bytes[3] = math_type_index(*math_type);
+            values = vec![value1, value2];
+        }
+        Instruction::Move(value1, value2) => values = vec![value1, value2],
+        Instruction::Jump(flag, value)
+        | Instruction::JumpNot(flag, value)
+        | Instruction::Call(flag, value)
+        | Instruction::CallNot(flag, value) => {
+            bytes[1] = flag_index(*flag);
+            values = vec![value];
+        }
+        Instruction::Swap(register1, register2) => {
+            bytes[1] = register1.index() as u8;
+            bytes[2] = register2.index() as u8;
+        }
+        Instruction::Push(value) => values = vec![value],
+        Instruction::LoadMemory(register, value, width) => {
+            bytes[1] = register.index() as u8;
+            bytes[3] = width_index(*width);
+            values = vec![value];
+        }
+        Instruction::Store(value1, value2, width) => {
+            bytes[3] = width_index(*width);
+            values = vec![value1, value2];
+        }
+        Instruction::Convert(register, value, from, to) => {
+            bytes[1] = register.index() as u8;
+            bytes[2] = math_type_index(*from);
+            bytes[3] = math_type_index(*to);
+            values = vec![value];
+        }
+        Instruction::SignExtend(register, value, width)
+        | Instruction::ZeroExtend(register, value, width) => {
+            bytes[1] = register.index() as u8;
+            bytes[3] = width_index(*width);
+            values = vec![value];
+        }
+        Instruction::Pop(register) => bytes[1] = register.index() as u8,
+        Instruction::Halt | Instruction::Ret => (),
+        Instruction::SysCall(syscall) => match syscall {
+            SysCall::Print(value) => values = vec![value],
+            SysCall::PrintChar(value) => {
+                bytes[1] = 1;
+                values = vec![value];
+            }
+            SysCall::PrintString { start, length } => {
+                bytes[1] = 2;
+                values = vec![start, length];
+            }
+            SysCall::ReadInt(register) => {
+                bytes[1] = 3;
+                bytes[2] = register.index() as u8;
+            }
+            SysCall::ReadChar(register) => {
+                bytes[1] = 4;
+                bytes[2] = register.index() as u8;
+            }
+            SysCall::ReadLine { length, start, max_len } => {
+                bytes[1] = 5;
+                bytes[2] = length.index() as u8;
+                values = vec![start, max_len];
+            }
+            SysCall::Open { fd, path, path_len, mode } => {
+                bytes[1] = 6;
+                bytes[2] = fd.index() as u8;
+                values = vec![path, path_len, mode];
+            }
+            SysCall::Read { count, fd, start, length } => {
+                bytes[1] = 7;
+                bytes[2] = count.index() as u8;
+                values = vec![fd, start, length];
+            }
+            SysCall::Write { count, fd, start, length } => {
+                bytes[1] = 8;
+                bytes[2] = count.index() as u8;
+                values = vec![fd, start, length];
+            }
+            SysCall::Close { result, fd } => {
+                bytes[1] = 9;
+                bytes[2] = result.index() as u8;
+                values = vec![fd];
+            }
+            SysCall::Exit(value) => {
+                bytes[1] = 10;
+                values = vec![value];
+            }
+            SysCall::Alloc { pointer, size } => {
+                bytes[1] = 11;
+                bytes[2] = pointer.index() as u8;
+                values = vec![size];
+            }
+            SysCall::Free(value) => {
+                bytes[1] = 12;
+                values = vec![value];
+            }
+        },
+    }
+    for (index, value) in values.into_iter().enumerate() {
+        let offset = 4 + index * ENCODED_VALUE_SIZE;
+        bytes[offset..offset + ENCODED_VALUE_SIZE].copy_from_slice(&encode_value(value));
+    }
+    bytes
+}
+
+pub fn decode_instruction(bytes: &[u8; ENCODED_INSTRUCTION_SIZE]) -> Result<Instruction, ObjectError> {
+    let register = |byte: u8| Register::from_index(byte as usize).ok_or(ObjectError::InvalidRegister(byte as u32));
+    let flag = Flag::ALL
+        .get(bytes[1] as usize)
+        .copied()
+        .ok_or(ObjectError::InvalidFlag(bytes[1]));
+    let math_type = |byte: u8| {
+        MathType::ALL
+            .get(byte as usize)
+            .copied()
+            .ok_or(ObjectError::InvalidMathType(byte))
+    };
+    let width = Width::ALL
+        .get(bytes[3] as usize)
+        .copied()
+        .ok_or(ObjectError::InvalidWidth(bytes[3]));
+    let value = |index: usize| {
+        let offset = 4 + index * ENCODED_VALUE_SIZE;
+        decode_value(bytes[offset..offset + ENCODED_VALUE_SIZE].try_into().unwrap())
+    };
+    let instruction = match bytes[0] {
+        0 => Instruction::Add(register(bytes[1])?, value(0)?, value(1)?, math_type(bytes[3])?),
+        1 => Instruction::Sub(register(bytes[1])?, value(0)?, value(1)?, math_type(bytes[3])?),
+        2 => Instruction::Mul(register(bytes[1])?, value(0)?, value(1)?, math_type(bytes[3])?),
+        3 => Instruction::Div(register(bytes[1])?, value(0)?, value(1)?, math_type(bytes[3])?),
+        4 => Instruction::Mod(register(bytes[1])?, value(0)?, value(1)?, math_type(bytes[3])?),
+        5 => Instruction::And(register(bytes[1])?, value(0)?, value(1)?),
+        6 => Instruction::Or(register(bytes[1])?, value(0)?, value(1)?),
+        7 => Instruction::Xor(register(bytes[1])?, value(0)?, value(1)?),
+        8 => Instruction::Not(register(bytes[1])?, value(0)?),
+        9 => Instruction::ShiftLeft(register(bytes[1])?, value(0)?, value(1)?),
+        10 => Instruction::ShiftRight(register(bytes[1])?, value(0)?, value(1)?),
+        11 => Instruction::Compare(value(0)?, value(1)?, math_type(bytes[3])?),
+        12 => Instruction::Jump(flag?, value(0)?),
+        13 => Instruction::JumpNot(flag?, value(0)?),
+        14 => Instruction::Load(register(bytes[1])?, value(0)?),
+        15 => Instruction::Move(value(0)?, value(1)?),
+        16 => Instruction::Swap(register(bytes[1])?, register(bytes[2])?),
+        17 => Instruction::Halt,
+        18 => Instruction::SysCall(match bytes[1] {
+            0 => SysCall::Print(value(0)?),
+            1 => SysCall::PrintChar(value(0)?),
+            2 => SysCall::PrintString {
+                start: value(0)?,
+                length: value(1)?,
+            },
+            3 => SysCall::ReadInt(register(bytes[2])?),
+            4 => SysCall::ReadChar(register(bytes[2])?),
+            5 => SysCall::ReadLine {
+                length: register(bytes[2])?,
+                start: value(0)?,
+                max_len: value(1)?,
+            },
+            6 => SysCall::Open {
+                fd: register(bytes[2])?,
+                path: value(0)?,
+                path_len: value(1)?,
+                mode: value(2)?,
+            },
+            7 => SysCall::Read {
+                count: register(bytes[2])?,
+                fd: value(0)?,
+                start: value(1)?,
+                length: value(2)?,
+            },
+            8 => SysCall::Write {
+                count: register(bytes[2])?,
+                fd: value(0)?,
+                start: value(1)?,
+                length: value(2)?,
+            },
+            9 => SysCall::Close {
+                result: register(bytes[2])?,
+                fd: value(0)?,
+            },
+            10 => SysCall::Exit(value(0)?),
+            11 => SysCall::Alloc {
+                pointer: register(bytes[2])?,
+                size: value(0)?,
+            },
+            12 => SysCall::Free(value(0)?),
+            kind => return Err(ObjectError::InvalidSysCall(kind)),
+        }),
+        19 => Instruction::Push(value(0)?),
+        20 => Instruction::Pop(register(bytes[1])?),
+        21 => Instruction::Call(flag?, value(0)?),
+        22 => Instruction::CallNot(flag?, value(0)?),
+        23 => Instruction::Ret,
+        24 => Instruction::LoadMemory(register(bytes[1])?, value(0)?, width?),
+        25 => Instruction::Store(value(0)?, value(1)?, width?),
+        26 => Instruction::Convert(
+            register(bytes[1])?,
+            value(0)?,
+            math_type(bytes[2])?,
+            math_type(bytes[3])?,
+        ),
+        27 => Instruction::SignExtend(register(bytes[1])?, value(0)?, width?),
+        28 => Instruction::ZeroExtend(register(bytes[1])?, value(0)?, width?),
+        opcode => return Err(ObjectError::InvalidOpcode(opcode)),
+    };
+    // every byte the instruction does not use is zero, the same as `encode_instruction` writes it
+    if encode_instruction(&instruction) != *bytes {
+        return Err(ObjectError::InvalidPadding);
+    }
+    Ok(instruction)
+}
+
+impl Object {
+    /// header: magic, version, entry and the length of each section, followed by
+    /// the data bytes, the code, the symbol table and the relocations. everything is little endian.
+    /// fails when a symbol name is longer than `u16::MAX` bytes
+    pub fn encode(&self) -> Result<Vec<u8>, ObjectError> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(self.entry.to_le_bytes());
+        bytes.extend((self.data.len() as u32).to_le_bytes());
+        bytes.extend((self.code.len() as u32).to_le_bytes());
+        bytes.extend((self.symbols.len() as u32).to_le_bytes());
+        bytes.extend((self.relocations.len() as u32).to_le_bytes());
+        bytes.extend(&self.data);
+        for instruction in &self.code {
+            bytes.extend(encode_instruction(instruction));
+        }
+        for symbol in &self.symbols {
+            bytes.extend(symbol.adress.to_le_bytes());
+            bytes.push(match symbol.section {
+                Section::Data => 0,
+                Section::Code => 1,
+                Section::Absolute => 2,
+            });
+            bytes.push(match symbol.binding {
+                Binding::Local => 0,
+                Binding::Global => 1,
+            });
+            encode_name(&mut bytes, &symbol.name)?;
+        }
+        for relocation in &self.relocations {
+            bytes.extend(relocation.instruction.to_le_bytes());
+            bytes.extend(relocation.operand.to_le_bytes());
+            encode_name(&mut bytes, &relocation.symbol)?;
+        }
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Object, ObjectError> {
+        let mut reader = Reader { bytes, index: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(ObjectError::InvalidMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(ObjectError::UnsupportedVersion(version));
+        }
+        let entry = reader.u32()?;
+        let data = reader.u32()?;
+        let code = reader.u32()?;
+        let symbols = reader.u32()?;
+        let relocations = reader.u32()?;
+        let data = reader.take(data as usize)?.to_vec();
+        let code = (0..code)
+            .map(|_| decode_instruction(reader.take(ENCODED_INSTRUCTION_SIZE)?.try_into().unwrap()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let symbols = (0..symbols)
+            .map(|_| {
+                let adress = reader.u32()?;
+                let section = match reader.u8()? {
+                    0 => Section::Data,
+                    1 => Section::Code,
+                    2 => Section::Absolute,
+                    section => return Err(ObjectError::InvalidSection(section)),
+                };
+                let binding = match reader.u8()? {
+                    0 => Binding::Local,
+                    1 => Binding::Global,
+                    binding => return Err(ObjectError::InvalidBinding(binding)),
+                };
+                let name = decode_name(&mut reader)?;
+                Ok(Symbol {
+                    name,
+                    adress,
+                    section,
+                    binding,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let relocations = (0..relocations)
+            .map(|_| {
+                let instruction = reader.u32()?;
+                let operand = reader.u32()?;
+                let symbol = decode_name(&mut reader)?;
+                let relocation = Relocation {
+                    instruction,
+                    operand,
+                    symbol,
+                };
+                if !relocation.fits(&code) {
+                    return Err(ObjectError::InvalidRelocation);
+                }
+                Ok(relocation)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if reader.index != bytes.len() {
+            return Err(ObjectError::TrailingBytes);
+        }
+        Ok(Object {
+            data,
+            code,
+            entry,
+            symbols,
+            relocations,
+        })
+    }
+}
+
+fn encode_name(bytes: &mut Vec<u8>, name: &str) -> Result<(), ObjectError> {
+    let length = u16::try_from(name.len()).map_err(|_| ObjectError::NameTooLong(name.len()))?;
+    bytes.extend(length.to_le_bytes());
+    bytes.extend(name.as_bytes());
+    Ok(())
+}
+
+fn decode_name(reader: &mut Reader) -> Result<Name, ObjectError> {
+    let length = reader.u16()?;
+    String::from_utf8(reader.take(length as usize)?.to_vec()).map_err(|_| ObjectError::InvalidSymbol)
+}
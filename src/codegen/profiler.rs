@@ -0,0 +1,167 @@
+//! a `Tracer` counting the instructions a program executes, per adress, per label and per
+//! call stack. labels are the code symbols of the program, so the instructions of a `Block`
+//! count under its `.blockN` symbol unless a label of its own is nearer. `report` lists the
+//! hot spots, `folded` the call stacks in the folded format flamegraph tools read
+
+use super::asm::*;
+use super::object::{Section, Symbol};
+use super::trace::{Executed, Tracer};
+use crate::Name;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Profiler {
+    /// the code labels by adress
+    labels: BTreeMap<u32, Name>,
+    /// every instruction that ran with how often it did, by adress
+    counts: BTreeMap<u32, (Instruction, u64)>,
+    /// the instructions that ran under each call stack, see `stack`
+    stacks: BTreeMap<Vec<u32>, u64>,
+    /// where the program started followed by the adresses of the calls that did not return,
+    /// outermost first
+    stack: Vec<u32>,
+    /// `SP` after each call in `stack`, a call returned once `SP` is above it
+    frames: Vec<u32>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// names adresses after the code symbols, preferring ones the assembler did not make up
+    pub fn add_symbols(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            if symbol.section != Section::Code {
+                continue;
+            }
+            match self.labels.get(&symbol.adress) {
+                Some(label) if !label.starts_with('.') => (),
+                _ => {
+                    self.labels.insert(symbol.adress, symbol.name.clone());
+                }
+            }
+        }
+    }
+
+    /// the number of instructions that ran
+    pub fn total(&self) -> u64 {
+        self.counts.values().map(|(_, count)| count).sum()
+    }
+
+    /// every adress that ran with its instruction and how often, by adress
+    pub fn counts(&self) -> impl Iterator<Item = (u32, Instruction, u64)> + '_ {
+        self.counts
+            .iter()
+            .map(|(adress, (instruction, count))| (*adress, *instruction, *count))
+    }
+
+    /// the label at or before `adress`
+    pub fn label(&self, adress: u32) -> Option<(u32, &str)> {
+        self.labels
+            .range(..=adress)
+            .next_back()
+            .map(|(adress, label)| (*adress, label.as_str()))
+    }
+
+    /// the label at `adress`, with the offset from the one before it or the adress itself
+    pub fn name(&self, adress: u32) -> String {
+        match self.label(adress) {
+            Some((at, label)) if at == adress => label.to_string(),
+            Some((at, label)) => format!("{}+{}", label, adress - at),
+            None => format!("{:#x}", adress),
+        }
+    }
+
+    /// the instructions that ran under each label, the most first
+    pub fn by_label(&self) -> Vec<(String, u64)> {
+        let mut labels: BTreeMap<String, u64> = BTreeMap::new();
+        for (adress, (_, count)) in &self.counts {
+            let label = match self.label(*adress) {
+                Some((_, label)) => label.to_string(),
+                None => "?".to_string(),
+            };
+            *labels.entry(label).or_insert(0) += count;
+        }
+        let mut labels: Vec<(String, u64)> = labels.into_iter().collect();
+        labels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        labels
+    }
+
+    /// the `top` labels and adresses that ran the most instructions
+    pub fn report(&self, top: usize) -> String {
+        let total = self.total();
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let mut output = format!("{} instructions\n\n   count       %  label\n", total);
+        for (label, count) in self.by_label().into_iter().take(top) {
+            output.push_str(&format!(
+                "{:>8} {:>6.2}%  {}\n",
+                count,
+                percent(count),
+                label
+            ));
+        }
+        output.push_str("\n   count       %    adress  instruction\n");
+        let mut adresses: Vec<_> = self.counts().collect();
+        adresses.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        for (adress, instruction, count) in adresses.into_iter().take(top) {
+            output.push_str(&format!(
+                "{:>8} {:>6.2}%  {:>8}  {}\n",
+                count,
+                percent(count),
+                adress,
+                String::from(instruction)
+            ));
+        }
+        output
+    }
+
+    /// a line per call stack: the names of its functions outermost first, separated by `;`,
+    /// and the instructions that ran in the innermost
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| {
+                let names: Vec<String> = stack.iter().map(|adress| self.name(*adress)).collect();
+                format!("{} {}\n", names.join(";"), count)
+            })
+            .collect()
+    }
+
+    pub fn write_folded(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.folded())
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, executed: &Executed) {
+        let Some(instruction) = executed.instruction.filter(|_| executed.ran()) else {
+            return;
+        };
+        if self.stack.is_empty() {
+            self.stack.push(executed.pc);
+        }
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+        self.counts.entry(executed.pc).or_insert((instruction, 0)).1 += 1;
+
+        let sp = executed.register(Register::StackPointer);
+        while self.frames.last().is_some_and(|called| *called < sp) {
+            self.frames.pop();
+            self.stack.pop();
+        }
+        let call = matches!(
+            instruction,
+            Instruction::Call(..) | Instruction::CallNot(..)
+        );
+        if call && executed.result.is_ok() && sp < executed.before(Register::StackPointer) {
+            self.frames.push(sp);
+            self.stack.push(executed.register(Register::ProgramCounter));
+        }
+    }
+}
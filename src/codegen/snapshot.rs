@@ -0,0 +1,398 @@
+//! the state of an interpreter as bytes, to continue a program later or on another host:
+//! the memory, registers, flags, stack, heap, trap handler, limits and usage, the open files
+//! with their paths in the root and positions and how much of the input was read. the root
+//! itself, the history, the tracer and the output written so far are not part of it
+
+use super::asm::*;
+use super::files::{Files, Mode};
+use super::heap::Heap;
+use super::interpreter::*;
+use super::object::{
+    decode_instruction, encode_instruction, ObjectError, Reader, ENCODED_INSTRUCTION_SIZE,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"PLSS";
+pub const SNAPSHOT_VERSION: u16 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// every written node, in adress order
+    pub memory: Vec<(u32, MemoryNode)>,
+    pub registers: BTreeMap<Register, u32>,
+    pub flags: BTreeSet<Flag>,
+    pub stack: Range<u32>,
+    pub trap_handler: Option<u32>,
+    pub in_handler: bool,
+    pub limits: Limits,
+    pub usage: Usage,
+    pub heap: Heap,
+    /// every open file by descriptor, with its path relative to the root, its mode and position
+    pub files: Vec<(u32, PathBuf, Mode, u64)>,
+    /// how many bytes of the input were taken, `None` when the `Io` could not tell
+    pub input: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    TrailingBytes,
+    InvalidNode(u8),
+    InvalidRegister(u8),
+    InvalidFlags(u8),
+    InvalidMode(u8),
+    InvalidPath,
+    /// the stack or a space of the heap reaches past the end of the adress space
+    InvalidRange,
+    /// an instruction in the memory did not decode
+    InvalidInstruction(ObjectError),
+    /// carries the adress of a node that does not fit into the memory
+    OutOfMemory(u32),
+    /// the input or a file could not be continued where it was, carries what the host said
+    Io(String),
+}
+
+impl From<ObjectError> for SnapshotError {
+    fn from(error: ObjectError) -> SnapshotError {
+        match error {
+            ObjectError::UnexpectedEnd => SnapshotError::UnexpectedEnd,
+            error => SnapshotError::InvalidInstruction(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(error: std::io::Error) -> SnapshotError {
+        SnapshotError::Io(error.to_string())
+    }
+}
+
+fn encode_option<const N: usize>(bytes: &mut Vec<u8>, value: Option<[u8; N]>) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            bytes.extend(value);
+        }
+        None => bytes.push(0),
+    }
+}
+
+fn encode_path(bytes: &mut Vec<u8>, path: &Path) {
+    let path = path.to_string_lossy();
+    bytes.extend((path.len() as u32).to_le_bytes());
+    bytes.extend(path.as_bytes());
+}
+
+fn decode_path(reader: &mut Reader) -> Result<PathBuf, SnapshotError> {
+    let length = reader.u32()?;
+    let path = reader.take(length as usize)?.to_vec();
+    String::from_utf8(path)
+        .map(PathBuf::from)
+        .map_err(|_| SnapshotError::InvalidPath)
+}
+
+fn encode_spaces(bytes: &mut Vec<u8>, spaces: &BTreeMap<u32, u32>) {
+    bytes.extend((spaces.len() as u32).to_le_bytes());
+    for (adress, size) in spaces {
+        bytes.extend(adress.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+    }
+}
+
+fn decode_spaces(reader: &mut Reader) -> Result<BTreeMap<u32, u32>, SnapshotError> {
+    (0..reader.u32()?)
+        .map(|_| Ok((reader.u32()?, reader.u32()?)))
+        .collect()
+}
+
+impl Snapshot {
+    /// header: magic and version, followed by the memory as runs of nodes, the registers, the
+    /// flags as bits in the order of `Flag::ALL`, the stack, the trap handler, the limits and
+    /// usage, the heap, the root and the open files and the input. everything is little endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(SNAPSHOT_MAGIC);
+        bytes.extend(SNAPSHOT_VERSION.to_le_bytes());
+
+        // consecutive data bytes share a run: adress, tag 0, length and the bytes
+        let mut runs: Vec<(u32, &MemoryNode, Vec<u8>)> = Vec::new();
+        for (adress, node) in &self.memory {
+            match (runs.last_mut(), node) {
+                (Some((start, MemoryNode::Data(_), data)), MemoryNode::Data(byte))
+                    if *start as u64 + data.len() as u64 == *adress as u64 =>
+                {
+                    data.push(*byte)
+                }
+                (_, MemoryNode::Data(byte)) => runs.push((*adress, node, vec![*byte])),
+                _ => runs.push((*adress, node, Vec::new())),
+            }
+        }
+        bytes.extend((runs.len() as u32).to_le_bytes());
+        for (adress, node, data) in runs {
+            bytes.extend(adress.to_le_bytes());
+            match node {
+                MemoryNode::Data(_) => {
+                    bytes.push(0);
+                    bytes.extend((data.len() as u32).to_le_bytes());
+                    bytes.extend(data);
+                }
+                MemoryNode::Instruction(instruction) => {
+                    bytes.push(1);
+                    bytes.extend(encode_instruction(instruction));
+                }
+                MemoryNode::FilledByInstruction => bytes.push(2),
+                MemoryNode::None => bytes.push(3),
+            }
+        }
+
+        bytes.push(self.registers.len() as u8);
+        for (register, value) in &self.registers {
+            bytes.push(register.index() as u8);
+            bytes.extend(value.to_le_bytes());
+        }
+        let flags = Flag::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, flag)| self.flags.contains(flag))
+            .fold(0u8, |bits, (index, _)| bits | 1 << index);
+        bytes.push(flags);
+        bytes.extend(self.stack.start.to_le_bytes());
+        bytes.extend(self.stack.end.to_le_bytes());
+        encode_option(&mut bytes, self.trap_handler.map(u32::to_le_bytes));
+        bytes.push(self.in_handler as u8);
+        encode_option(&mut bytes, self.limits.instructions.map(u64::to_le_bytes));
+        encode_option(&mut bytes, self.limits.memory.map(u32::to_le_bytes));
+        encode_option(&mut bytes, self.limits.stack.map(u32::to_le_bytes));
+        bytes.extend(self.usage.instructions.to_le_bytes());
+        bytes.extend(self.usage.memory.to_le_bytes());
+        bytes.extend(self.usage.stack.to_le_bytes());
+
+        bytes.extend(self.heap.range.start.to_le_bytes());
+        bytes.extend(self.heap.range.end.to_le_bytes());
+        bytes.push(self.heap.checked as u8);
+        encode_spaces(&mut bytes, &self.heap.blocks);
+        encode_spaces(&mut bytes, &self.heap.free);
+        encode_spaces(&mut bytes, &self.heap.freed);
+
+        bytes.extend((self.files.len() as u32).to_le_bytes());
+        for (fd, path, mode, position) in &self.files {
+            bytes.extend(fd.to_le_bytes());
+            bytes.push(*mode as u8);
+            bytes.extend(position.to_le_bytes());
+            encode_path(&mut bytes, path);
+        }
+        encode_option(&mut bytes, self.input.map(u64::to_le_bytes));
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader { bytes, index: 0 };
+        if reader.take(4)? != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut memory = Vec::new();
+        for _ in 0..reader.u32()? {
+            let adress = reader.u32()?;
+            match reader.u8()? {
+                0 => {
+                    let length = reader.u32()?;
+                    let data = reader.take(length as usize)?;
+                    for (offset, byte) in data.iter().enumerate() {
+                        let adress = adress
+                            .checked_add(offset as u32)
+                            .ok_or(SnapshotError::InvalidNode(0))?;
+                        memory.push((adress, MemoryNode::Data(*byte)));
+                    }
+                }
+                1 => {
+                    let encoded = reader.take(ENCODED_INSTRUCTION_SIZE)?.try_into().unwrap();
+                    let instruction =
+                        decode_instruction(encoded).map_err(SnapshotError::InvalidInstruction)?;
+                    memory.push((adress, MemoryNode::Instruction(instruction)));
+                }
+                2 => memory.push((adress, MemoryNode::FilledByInstruction)),
+                3 => memory.push((adress, MemoryNode::None)),
+                tag => return Err(SnapshotError::InvalidNode(tag)),
+            }
+        }
+
+        let mut registers = BTreeMap::new();
+        for _ in 0..reader.u8()? {
+            let index = reader.u8()?;
+            let register = Register::from_index(index as usize)
+                .ok_or(SnapshotError::InvalidRegister(index))?;
+            registers.insert(register, reader.u32()?);
+        }
+        let bits = reader.u8()?;
+        if bits >> Flag::ALL.len() != 0 {
+            return Err(SnapshotError::InvalidFlags(bits));
+        }
+        let flags = Flag::ALL
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| bits & 1 << index != 0)
+            .map(|(_, flag)| *flag)
+            .collect();
+        let stack = reader.u32()?..reader.u32()?;
+        if stack.start > stack.end || stack.start.checked_add(Width::Word.bytes()).is_none() {
+            return Err(SnapshotError::InvalidRange);
+        }
+        let trap_handler = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.u32()?),
+        };
+        let in_handler = reader.u8()? != 0;
+        let limits = Limits {
+            instructions: match reader.u8()? {
+                0 => None,
+                _ => Some(reader.u64()?),
+            },
+            memory: match reader.u8()? {
+                0 => None,
+                _ => Some(reader.u32()?),
+            },
+            stack: match reader.u8()? {
+                0 => None,
+                _ => Some(reader.u32()?),
+            },
+        };
+        let usage = Usage {
+            instructions: reader.u64()?,
+            memory: reader.u32()?,
+            stack: reader.u32()?,
+        };
+
+        let heap = Heap {
+            range: reader.u32()?..reader.u32()?,
+            checked: reader.u8()? != 0,
+            blocks: decode_spaces(&mut reader)?,
+            free: decode_spaces(&mut reader)?,
+            freed: decode_spaces(&mut reader)?,
+        };
+        let spaces = [&heap.blocks, &heap.free, &heap.freed];
+        if heap.range.start > heap.range.end
+            || spaces
+                .into_iter()
+                .flatten()
+                .any(|(adress, size)| adress.checked_add(*size).is_none())
+        {
+            return Err(SnapshotError::InvalidRange);
+        }
+
+        let files = (0..reader.u32()?)
+            .map(|_| {
+                let fd = reader.u32()?;
+                let mode = reader.u8()?;
+                let mode =
+                    Mode::try_from(mode as u32).map_err(|_| SnapshotError::InvalidMode(mode))?;
+                let position = reader.u64()?;
+                Ok((fd, decode_path(&mut reader)?, mode, position))
+            })
+            .collect::<Result<Vec<_>, SnapshotError>>()?;
+        let input = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.u64()?),
+        };
+        if reader.index != bytes.len() {
+            return Err(SnapshotError::TrailingBytes);
+        }
+        Ok(Snapshot {
+            memory,
+            registers,
+            flags,
+            stack,
+            trap_handler,
+            in_handler,
+            limits,
+            usage,
+            heap,
+            files,
+            input,
+        })
+    }
+
+    /// writes the encoded snapshot to the file at `path`, creating or emptying it
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.encode())
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Snapshot, SnapshotError> {
+        Snapshot::decode(&std::fs::read(path)?)
+    }
+}
+
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    /// the current state, fails when the position of an open file cannot be told
+    pub fn snapshot(&self) -> std::io::Result<Snapshot> {
+        Ok(Snapshot {
+            memory: self
+                .memory
+                .nodes()
+                .map(|(adress, node)| (adress, node.clone()))
+                .collect(),
+            registers: self.registers.clone(),
+            flags: self.flags.clone(),
+            stack: self.stack.clone(),
+            trap_handler: self.trap_handler,
+            in_handler: self.in_handler,
+            limits: self.limits,
+            usage: self.usage,
+            heap: self.heap.clone(),
+            files: self.files.handles()?,
+            input: self.io.position(),
+        })
+    }
+
+    /// continues from `snapshot`. the memory is replaced, the files it had open are opened
+    /// again in the current root and the input continues where it was unless the snapshot does
+    /// not know where. fails without changing anything when a path leads out of the root or a
+    /// file or the input cannot be continued, and with
+    /// the memory holding part of the snapshot when a node does not fit.
+    /// the history is cleared, the tracer and the output stay
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let mut files = Files::default();
+        if let Some(root) = self.files.root() {
+            files.set_root(root)?;
+        }
+        for (fd, path, mode, position) in &snapshot.files {
+            let path = path.to_str().ok_or(SnapshotError::InvalidPath)?;
+            files.resolve(path).ok_or(SnapshotError::InvalidPath)?;
+            files.reopen(*fd, path, *mode, *position)?;
+        }
+        if let Some(position) = snapshot.input {
+            self.io.seek(position)?;
+        }
+        self.files = files;
+
+        let written: Vec<u32> = self.memory.nodes().map(|(adress, _)| adress).collect();
+        for adress in written {
+            self.memory.remove(adress);
+        }
+        for (adress, node) in &snapshot.memory {
+            self.memory
+                .insert(*adress, node.clone())
+                .map_err(|_| SnapshotError::OutOfMemory(*adress))?;
+        }
+        self.registers = snapshot.registers.clone();
+        self.flags = snapshot.flags.clone();
+        self.stack = snapshot.stack.clone();
+        self.trap_handler = snapshot.trap_handler;
+        self.in_handler = snapshot.in_handler;
+        self.limits = snapshot.limits;
+        self.usage = snapshot.usage;
+        self.heap = snapshot.heap.clone();
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! a hook into `Interpreter::tick`. a `Tracer` is told about every tick after it ran: the
+//! instruction, the registers it changed and what it returned. `Trace` writes one line per
+//! tick, `Profiler` counts them. `Engine` does not trace
+
+use super::asm::*;
+use super::interpreter::*;
+use super::INSTRUCTION_SIZE;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// one tick, as a `Tracer` sees it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Executed<'a> {
+    /// the adress of the instruction
+    pub pc: u32,
+    /// `None` when there was no instruction at `pc`
+    pub instruction: Option<Instruction>,
+    /// every register that changed, before and after. `None` when it was never set
+    pub changed: &'a [(Register, Option<u32>, Option<u32>)],
+    /// the registers after the tick
+    pub registers: &'a BTreeMap<Register, u32>,
+    pub result: Result<(), Trap>,
+}
+
+impl Executed<'_> {
+    /// whether the instruction ran, which it did unless it was missing or a limit stopped it
+    pub fn ran(&self) -> bool {
+        self.instruction.is_some()
+            && !matches!(
+                self.result,
+                Err(Trap {
+                    kind: TrapKind::Exhausted(..),
+                    ..
+                })
+            )
+    }
+
+    /// the value of `register` after the tick
+    pub fn register(&self, register: Register) -> u32 {
+        self.registers.get(&register).copied().unwrap_or(0)
+    }
+
+    /// the value of `register` before the tick
+    pub fn before(&self, register: Register) -> u32 {
+        match self
+            .changed
+            .iter()
+            .find(|(changed, ..)| *changed == register)
+        {
+            Some((_, old, _)) => old.unwrap_or(0),
+            None => self.register(register),
+        }
+    }
+}
+
+/// a line: the adress, the instruction and the registers it changed, except `PC` when it
+/// only moved to the next instruction. a trap follows after `!`
+impl fmt::Display for Executed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>8}  ", self.pc)?;
+        match self.instruction {
+            Some(instruction) => f.write_str(&String::from(instruction))?,
+            None => f.write_str("?")?,
+        }
+        let next = self.pc.wrapping_add(INSTRUCTION_SIZE as u32);
+        for (register, _, new) in self.changed {
+            if *register == Register::ProgramCounter && *new == Some(next) {
+                continue;
+            }
+            match new {
+                Some(value) => write!(f, " {}={}", String::from(*register), value)?,
+                None => write!(f, " {}=?", String::from(*register))?,
+            }
+        }
+        if let Err(trap) = self.result {
+            write!(f, " ! {:?}", trap.kind)?;
+        }
+        writeln!(f)
+    }
+}
+
+pub trait Tracer {
+    fn trace(&mut self, executed: &Executed);
+}
+
+/// a `Tracer` the interpreter shares with its embedder and its clones
+#[derive(Clone)]
+pub struct SharedTracer(pub Arc<Mutex<dyn Tracer + Send>>);
+
+impl fmt::Debug for SharedTracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SharedTracer")
+    }
+}
+
+impl PartialEq for SharedTracer {
+    fn eq(&self, other: &SharedTracer) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// the registers that differ between `before` and `after`
+pub(super) fn changed_registers(
+    before: &BTreeMap<Register, u32>,
+    after: &BTreeMap<Register, u32>,
+) -> Vec<(Register, Option<u32>, Option<u32>)> {
+    Register::ALL
+        .into_iter()
+        .map(|register| {
+            let old = before.get(&register).copied();
+            (register, old, after.get(&register).copied())
+        })
+        .filter(|(_, old, new)| old != new)
+        .collect()
+}
+
+impl<M: Memory, I: Io> Interpreter<M, I> {
+    /// `tracer` is told about every following tick
+    pub fn set_tracer(&mut self, tracer: Option<Arc<Mutex<dyn Tracer + Send>>>) {
+        self.tracer = tracer.map(SharedTracer);
+    }
+
+    /// `tick` while a tracer is set
+    pub(super) fn traced_tick(&mut self) -> Result<(), Trap> {
+        let pc = *self.get_register(&Register::ProgramCounter);
+        let instruction = match self.get(pc) {
+            MemoryNode::Instruction(instruction) => Some(*instruction),
+            _ => None,
+        };
+        let registers = self.registers.clone();
+        let result = self.untraced_tick();
+        let executed = Executed {
+            pc,
+            instruction,
+            changed: &changed_registers(&registers, &self.registers),
+            registers: &self.registers,
+            result,
+        };
+        let tracer = self.tracer.as_ref().unwrap();
+        tracer
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .trace(&executed);
+        result
+    }
+}
+
+/// writes every tick as a line, see `Executed`
+#[derive(Debug)]
+pub struct Trace<W: Write> {
+    writer: W,
+    /// the first error writing, nothing is written after it
+    error: Option<std::io::Error>,
+}
+
+impl Trace<BufWriter<std::fs::File>> {
+    /// traces into the file at `path`, creating or emptying it
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Trace::new(BufWriter::new(std::fs::File::create(path)?)))
+    }
+}
+
+impl<W: Write> Trace<W> {
+    pub fn new(writer: W) -> Trace<W> {
+        Trace {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// flushes the writer, or returns the error that stopped the trace
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl<W: Write> Tracer for Trace<W> {
+    fn trace(&mut self, executed: &Executed) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = self.writer.write_all(executed.to_string().as_bytes()) {
+            self.error = Some(error);
+        }
+    }
+}
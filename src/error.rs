@@ -14,6 +14,12 @@ pub enum Level {
     Info,
 }
 
+impl Default for Error {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Error {
     pub fn new() -> Self {
         Self(Vec::new())
@@ -23,11 +29,25 @@ impl Error {
         self.0.push(Box::new(component));
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn render(self) -> Vec<String> {
         self.0.into_iter().flat_map(|component| component.render()).collect()
     }
 }
 
+/// the rendered diagnostics, so a result carrying them can be unwrapped
+impl std::fmt::Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for line in self.0.iter().flat_map(|component| component.render()) {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
 pub trait Component {
     fn render(&self) -> Vec<String>;
 }
@@ -47,7 +67,7 @@ impl Basic {
         for i in &messages {
             if !position.contains(&i.1) { return None }
         }
-        let data = parser.aquire(&position.expand_to_line());
+        let data = parser.aquire(&position.expand_to_line()).trim_end_matches('\n').to_string();
         Some(Self { position, data, info, messages, message })
     }
 }
@@ -61,25 +81,57 @@ impl Component for Basic {
     }
 }
 
+/// a diagnostic that has no place in a source file, e.g. from the linker
+pub struct Note {
+    pub info: Info,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl Component for Note {
+    fn render(&self) -> Vec<String> {
+        self.notes
+            .iter()
+            .fold(ErrorBuilder::new().header(&self.message, &self.info), |builder, note| builder.note(note))
+            .build()
+    }
+}
+
 pub struct ErrorBuilder {
     pub result: Vec<String>,
 }
 
+impl Default for ErrorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ErrorBuilder {
     pub fn new() -> Self {
         Self { result: Vec::new() }
     }
 
-    pub fn init(mut self, message: &str, info: &Info, position: &Position) -> Self {
+    pub fn init(self, message: &str, info: &Info, position: &Position) -> Self {
+        let mut builder = self.header(message, info);
+        builder.result.push(format!("  {} {}","-->".blue(),String::from(position.clone())));
+        builder
+    }
+
+    pub fn header(mut self, message: &str, info: &Info) -> Self {
         let mut first = String::new();
         match info.level {
             Level::Error => first.push_str(&format!("error[{}]: ", info.code).red().bold().to_string()),
             Level::Warning => first.push_str(&format!("warning[{}]: ", info.code).yellow().bold().to_string()),
             Level::Info => first.push_str(&format!("info[{}]: ", info.code).green().bold().to_string()),
         }
-        first.push_str(&message);
+        first.push_str(message);
         self.result.push(first);
-        self.result.push(format!("  {} {}","-->".blue().to_string(),String::from(position.clone())));
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Self {
+        self.result.push(format!("  {} {}", "= note:".blue(), note));
         self
     }
 
@@ -90,24 +142,27 @@ impl ErrorBuilder {
         messages: &[(String, Position)],
     ) -> Self {
         if position.is_multi_line() { todo!() }
-        self.result.push(format!("{}{}", " ".repeat(4), "|".blue().to_string()));
-        self.result.push(format!("{:4}{}{}{}",position.line.1,"|".blue().to_string(), " ".repeat(4), line));
-        let mut str = format!("{}{}{}", " ".repeat(4), "|".blue().to_string(), " ".repeat(4));
+        self.result.push(format!("{}{}", " ".repeat(4), "|".blue()));
+        self.result.push(format!("{:4}{}{}{}",position.line.1,"|".blue(), " ".repeat(4), line));
+        let mut str = format!("{}{}{}", " ".repeat(4), "|".blue(), " ".repeat(4));
         let mut index = 0;
         for (message, position) in messages {
-            let mut offset = position.column.0 - index;
-            if offset < 0 {
-                self.result.push(str);
-                str = format!("{}{}{}", " ".repeat(4), "|".blue().to_string(), " ".repeat(4));
-                offset = position.column.0;
-            }
+            let column = position.column.0.saturating_sub(1);
+            let offset = match column.checked_sub(index) {
+                Some(offset) => offset,
+                None => {
+                    self.result.push(str);
+                    str = format!("{}{}{}", " ".repeat(4), "|".blue(), " ".repeat(4));
+                    column
+                }
+            };
             let len = str.len();
             str.push_str(&" ".repeat(offset));
             str.push_str(&"^".red().to_string().repeat(position.column.1 - position.column.0));
-            str.push_str(&format!(" {}", message.red().bold().to_string()));
+            str.push_str(&format!(" {}", message.red().bold()));
             index += str.len() - len;
         }
-        if str != format!("{}{}{}", " ".repeat(4), "|".blue().to_string(), " ".repeat(4)) {
+        if str != format!("{}{}{}", " ".repeat(4), "|".blue(), " ".repeat(4)) {
             self.result.push(str);
         }
         self
@@ -1,4 +1,3 @@
-#![feature(tuple_trait)]
 #![warn(clippy::all)]
 
 pub mod codegen;
@@ -11,6 +10,7 @@ pub mod limits;
 use std::collections::HashMap;
 use crate::limits::LimitGenerator;
 pub use utils::Ref;
+use ast::Node;
 use limits::Limit;
 
 pub type Name = String;
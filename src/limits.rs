@@ -29,7 +29,7 @@ impl Limit {
             type_id: std::any::TypeId::of::<T>(),
             id: |ptr| unsafe { (*(ptr as *mut T)).id() },
             string: |ptr| unsafe { (*(ptr as *mut T)).string() },
-            drop: |ptr| unsafe { Box::from_raw(ptr as *mut T); },
+            drop: |ptr| unsafe { drop(Box::from_raw(ptr as *mut T)); },
             partial_cmp: |ptr1, ptr2| unsafe { (*(ptr1 as *mut T)).partial_cmp(&*(ptr2 as *mut T)) },
             clone: |ptr| unsafe { Box::into_raw(Box::new((*(ptr as *mut T)).clone())) as *mut u8 },
         }
@@ -1,8 +1,136 @@
-use plac::codegen::asm::*;
+use plac::codegen::debugger::Debugger;
+use plac::codegen::history::History;
 use plac::codegen::interpreter::*;
+use plac::codegen::linker::Linker;
+use plac::codegen::object::Symbol;
+use plac::codegen::profiler::Profiler;
+use plac::codegen::trace::Trace;
 use plac::codegen::*;
+use plac::parser::Parser;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// halting passes the exit code of the program on, faults get codes of their own
+fn exit_code(trap: &Trap) -> i32 {
+    match trap.kind {
+        TrapKind::Halt(code) => code as i32,
+        TrapKind::InvalidInstruction => 101,
+        TrapKind::DivisionByZero => 102,
+        TrapKind::Underflow => 103,
+        TrapKind::Overflow => 104,
+        TrapKind::StackOutOfBounds(_) => 105,
+        TrapKind::UninitialisedRead(_) => 106,
+        TrapKind::OutOfMemory(_) => 107,
+        TrapKind::Exhausted(..) => 108,
+        TrapKind::IoError => 109,
+        TrapKind::DoubleFree(_) => 110,
+        TrapKind::InvalidFree(_) => 111,
+        TrapKind::UseAfterFree(_) => 112,
+    }
+}
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, path] if command == "debug" => debug(path),
+        [command, path, output] if command == "trace" => trace(path, output),
+        [command, path, output] if command == "profile" => profile(path, output),
+        _ => demo(),
+    }
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// assembles, links and loads the assembly in the file at `path`
+fn load(path: &str) -> (Interpreter, Vec<Symbol>) {
+    let source =
+        std::fs::read_to_string(path).unwrap_or_else(|error| fail(format!("{}: {}", path, error)));
+    let mut parser = Parser::new(path.to_string(), source);
+    let mut assembler = Assembler::new();
+    if let Err(error) = assembly::parse(&mut parser, &mut assembler) {
+        fail(error.render().join("\n"));
+    }
+    let mut linker = Linker::new();
+    linker.add(path.to_string(), assembler.assemble(0));
+    let object = linker
+        .link()
+        .unwrap_or_else(|error| fail(error.render().join("\n")));
+    let symbols = object.symbols.clone();
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load_object(object)
+        .unwrap_or_else(|trap| fail(format!("the program does not fit into memory: {:?}", trap)));
+    (interpreter, symbols)
+}
+
+/// `plac trace <file> <trace>` runs the assembly in the file and writes a line per executed
+/// instruction to the trace
+fn trace(path: &str, output: &str) {
+    let (mut interpreter, _) = load(path);
+    let trace =
+        Trace::create(output).unwrap_or_else(|error| fail(format!("{}: {}", output, error)));
+    let trace = Arc::new(Mutex::new(trace));
+    interpreter.set_tracer(Some(trace.clone()));
+    let trap = interpreter.execute();
+    if let Err(error) = trace.lock().unwrap().flush() {
+        fail(format!("{}: {}", output, error));
+    }
+    std::process::exit(exit_code(&trap));
+}
+
+/// the number of labels and adresses `plac profile` reports
+const HOT_SPOTS: usize = 10;
+
+/// `plac profile <file> <folded>` runs the assembly in the file, reports where it spent its
+/// instructions on stderr and writes its call stacks to the folded file
+fn profile(path: &str, output: &str) {
+    let (mut interpreter, symbols) = load(path);
+    let mut profiler = Profiler::new();
+    profiler.add_symbols(&symbols);
+    let profiler = Arc::new(Mutex::new(profiler));
+    interpreter.set_tracer(Some(profiler.clone()));
+    let trap = interpreter.execute();
+    let profiler = profiler.lock().unwrap();
+    eprint!("{}", profiler.report(HOT_SPOTS));
+    if let Err(error) = profiler.write_folded(output) {
+        fail(format!("{}: {}", output, error));
+    }
+    std::process::exit(exit_code(&trap));
+}
+
+/// the number of instructions `plac debug` can step back over
+const HISTORY: usize = 100_000;
+
+/// `plac debug <file>` loads the assembly in the file and reads debugger commands from stdin,
+/// which the program shares for its input
+fn debug(path: &str) {
+    let (mut interpreter, symbols) = load(path);
+    interpreter.set_history(Some(History::new(HISTORY)));
+    let mut debugger = Debugger::new(interpreter);
+    debugger.add_symbols(&symbols);
+    print!("{}", debugger.list(0));
+    loop {
+        print!("(plac) ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        match line.trim() {
+            "q" | "quit" => break,
+            line => match debugger.command(line) {
+                Ok(output) => print!("{}", output),
+                Err(error) => println!("{}", error),
+            },
+        }
+    }
+}
+
+/// assembles and runs a small program
+fn demo() {
     let instructions: Vec<Ir> = vec![
         "load r0 15",
         "load r1 1",
@@ -24,14 +152,18 @@ fn main() {
     for i in instructions {
         assembler.add_ir(i);
     }
-    let (data, instructions, start) = assembler.compile(1);
+    let (data, instructions, start) = assembler
+        .compile(1)
+        .unwrap_or_else(|error| fail(error.render().join("\n")));
     println!("{:?}", instructions);
     println!("====================");
     let mut interpreter = Interpreter::new();
-    interpreter.load(data, instructions, start);
-    interpreter.set_register(Register::StackPointer, 1000);
-    let err = interpreter.execute();
+    interpreter
+        .load(data, instructions, start)
+        .unwrap_or_else(|trap| fail(format!("the program does not fit into memory: {:?}", trap)));
+    let trap = interpreter.execute();
     println!("====================");
-    println!("{:?}", err);
+    println!("{:?}", trap);
     println!("{}", interpreter.debug());
+    std::process::exit(exit_code(&trap));
 }
@@ -11,6 +11,7 @@ pub struct Parser {
     pub column: usize,
 }
 
+#[allow(clippy::derive_ord_xor_partial_ord)]
 #[derive(Debug, Clone, PartialEq, Eq, Ord)]
 pub struct Position {
     pub line: (usize, usize),
@@ -36,6 +37,7 @@ impl Position {
     }
 }
 
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Position {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         if self.file != other.file {
@@ -76,6 +78,15 @@ impl Parser {
         let mut line = 1;
         for c in self.input.chars() {
             match (c, in_str, position.contains(&Position { file: self.file_name.clone(), line: (line, line), column: (column, column) })) {
+                ('\n', _, true) => {
+                    line += 1;
+                    column = 0;
+                    result.push(c);
+                }
+                ('\n', _, false) => {
+                    line += 1;
+                    column = 0;
+                }
                 ('"', _, true) => {
                     in_str = !in_str;
                     result.push(c);
@@ -84,15 +95,6 @@ impl Parser {
                     in_str = !in_str;
                 }
                 (_, true, true) => result.push(c),
-                ('\n', false, true) => {
-                    line += 1;
-                    column = 0;
-                    result.push(c);
-                }
-                ('\n', false, false) => {
-                    line += 1;
-                    column = 0;
-                }
                 (_, false, true) => result.push(c),
                 (_, _, false) => (),
             }
@@ -1,10 +1,12 @@
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 
 pub struct Ref<T:?Sized>(pub u64, pub PhantomData<T>);
 
 impl<T:?Sized> Clone for Ref<T> {
     fn clone(&self) -> Self {
-        Self(self.0, PhantomData)
+        *self
     }
 }
 
@@ -20,7 +22,7 @@ impl<T:?Sized> Eq for Ref<T> {}
 
 impl<T:?Sized> PartialOrd for Ref<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -30,3 +32,14 @@ impl<T:?Sized> Ord for Ref<T> {
     }
 }
 
+impl<T:?Sized> Hash for Ref<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<T:?Sized> Debug for Ref<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Ref({})", self.0)
+    }
+}
@@ -0,0 +1,188 @@
+use plac::codegen::abi::*;
+use plac::codegen::asm::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::*;
+use plac::{Ref, StackFrame, Type, Variable};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+fn ir(line: &str) -> Ir {
+    line.to_string().try_into().unwrap()
+}
+
+fn add(assembler: &mut Assembler, block: Ref<Block>, lines: &[&str]) {
+    for line in lines {
+        assembler.add_ir_to(block, ir(line));
+    }
+}
+
+fn run(assembler: &mut Assembler) -> Interpreter {
+    let (data, code, entry) = assembler.compile(0).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.load(data, code, entry).unwrap();
+    let trap = interpreter.execute();
+    assert!(trap.is_halt(), "{:?}", trap);
+    interpreter
+}
+
+fn stackframe(assembler: &mut Assembler, variables: &[&str]) -> Ref<StackFrame> {
+    assembler.add_stackframe(StackFrame {
+        module: Ref(0, PhantomData),
+        variables: variables
+            .iter()
+            .map(|name| (name.to_string(), Variable(Type::Type(vec![]), None)))
+            .collect::<HashMap<_, _>>(),
+    })
+}
+
+#[test]
+fn frame_layout() {
+    let frame = Frame {
+        locals: vec!["a".to_string(), "b".to_string()],
+        saved: vec![Register::R8],
+    };
+    assert_eq!(frame.padding(), 4);
+    assert_eq!(frame.size(), 20);
+    assert_eq!((frame.size() + 4) % STACK_ALIGNMENT, 0);
+    assert_eq!(frame.local("b"), Some(4));
+    assert_eq!(frame.local("c"), None);
+    assert_eq!(
+        frame.argument(2),
+        Value::Direct(Operand::Register(Register::R2))
+    );
+    assert_eq!(
+        frame.argument(9),
+        Value::OperationalIndirect(Operand::Register(FRAME_POINTER), Operand::U32(28))
+    );
+    let text: Vec<String> = frame.prologue().into_iter().map(String::from).collect();
+    assert_eq!(
+        text,
+        ["push r13", "push r8", "sub SP SP 12 U", "load r13 SP"]
+    );
+    let text: Vec<String> = frame.epilogue().into_iter().map(String::from).collect();
+    assert_eq!(text, ["add SP r13 12 U", "pop r8", "pop r13", "ret"]);
+}
+
+#[test]
+fn frame_saves_only_used_callee_saved_registers() {
+    let mut assembler = Assembler::new();
+    let stackframe = stackframe(&mut assembler, &["y", "x"]);
+    let function = assembler.add_function(stackframe);
+    add(
+        &mut assembler,
+        function,
+        &["load r10 r0", "add r0 r1 r9 U", "return"],
+    );
+    let frame = assembler.frame(function);
+    assert_eq!(frame.locals, ["x", "y"]);
+    assert_eq!(frame.saved, [Register::R9, Register::R10]);
+}
+
+#[test]
+fn nested_calls_preserve_callee_saved_registers() {
+    let mut assembler = Assembler::new();
+    let init = assembler.init();
+    let frame = assembler.block(init).unwrap().stackframe();
+    let square = assembler.add_function(frame);
+    let sum_of_squares = assembler.add_function(frame);
+    add(
+        &mut assembler,
+        square,
+        &["load r8 r0", "mul r0 r8 r8 U", "return"],
+    );
+    add(&mut assembler, sum_of_squares, &["load r8 r1"]);
+    assembler.add_ir_to(sum_of_squares, Ir::Call(Flag::Always, square));
+    add(&mut assembler, sum_of_squares, &["swap r0 r8"]);
+    assembler.add_ir_to(sum_of_squares, Ir::Call(Flag::Always, square));
+    add(
+        &mut assembler,
+        sum_of_squares,
+        &["add r0 r0 r8 U", "return"],
+    );
+
+    add(
+        &mut assembler,
+        init,
+        &["load r0 3", "load r1 4", "load r8 99"],
+    );
+    assembler.add_ir(Ir::Call(Flag::Always, sum_of_squares));
+    assembler.add_ir(ir("halt"));
+    let mut interpreter = run(&mut assembler);
+    assert_eq!(*interpreter.get_register(&RETURN_VALUE), 25);
+    assert_eq!(*interpreter.get_register(&Register::R8), 99);
+    let top = interpreter.stack.end;
+    assert_eq!(*interpreter.get_register(&Register::StackPointer), top);
+}
+
+#[test]
+fn recursive_calls() {
+    let mut assembler = Assembler::new();
+    let init = assembler.init();
+    let frame = assembler.block(init).unwrap().stackframe();
+    let factorial = assembler.add_function(frame);
+    add(
+        &mut assembler,
+        factorial,
+        &["cmp r0 2", "jmp: L base", "load r8 r0", "sub r0 r0 1 U"],
+    );
+    assembler.add_ir_to(factorial, Ir::Call(Flag::Always, factorial));
+    add(
+        &mut assembler,
+        factorial,
+        &["mul r0 r0 r8 U", "return", "base: load r0 1", "return"],
+    );
+
+    add(&mut assembler, init, &["load r0 10", "load r13 7"]);
+    assembler.add_ir(Ir::Call(Flag::Always, factorial));
+    assembler.add_ir(ir("halt"));
+    let mut interpreter = run(&mut assembler);
+    assert_eq!(*interpreter.get_register(&RETURN_VALUE), 3628800);
+    assert_eq!(*interpreter.get_register(&FRAME_POINTER), 7);
+}
+
+#[test]
+fn locals_and_stack_arguments() {
+    let mut assembler = Assembler::new();
+    let init = assembler.init();
+    let stackframe = stackframe(&mut assembler, &["sum"]);
+    let function = assembler.add_function(stackframe);
+    let frame = Frame {
+        locals: vec!["sum".to_string()],
+        saved: vec![],
+    };
+    let sum = frame.local("sum").unwrap();
+    let ninth = String::from(frame.argument(8));
+    let tenth = String::from(frame.argument(9));
+    add(
+        &mut assembler,
+        function,
+        &[
+            &format!("mov r13+{} r0", sum),
+            &format!("add r0 [r13+{}] {} U", sum, ninth),
+            &format!("add r0 r0 {} U", tenth),
+            "return",
+        ],
+    );
+    assert_eq!(assembler.frame(function), frame);
+
+    add(&mut assembler, init, &["load r0 1", "push 100", "push 10"]);
+    assembler.add_ir(Ir::Call(Flag::Always, function));
+    add(&mut assembler, init, &["add SP SP 8 U", "halt"]);
+    let mut interpreter = run(&mut assembler);
+    assert_eq!(*interpreter.get_register(&RETURN_VALUE), 111);
+    let top = interpreter.stack.end;
+    assert_eq!(*interpreter.get_register(&Register::StackPointer), top);
+}
+
+#[test]
+fn return_outside_of_functions_is_ret() {
+    let mut assembler = Assembler::new();
+    let init = assembler.init();
+    let frame = assembler.block(init).unwrap().stackframe();
+    let block = assembler.add_block(frame);
+    add(&mut assembler, block, &["load r8 5", "return"]);
+    assembler.add_ir(Ir::Call(Flag::Always, block));
+    assembler.add_ir(ir("halt"));
+    let mut interpreter = run(&mut assembler);
+    assert_eq!(*interpreter.get_register(&Register::R8), 5);
+}
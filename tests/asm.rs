@@ -0,0 +1,375 @@
+use plac::codegen::asm::*;
+use plac::codegen::{assembly, Assembler};
+use plac::parser::Parser;
+use std::collections::BTreeSet;
+
+fn operands() -> Vec<Operand> {
+    let mut operands: Vec<Operand> = Register::ALL.into_iter().map(Operand::Register).collect();
+    operands.extend([0, 1, 1234, u32::MAX].map(Operand::U32));
+    operands
+}
+
+/// every operand in every position of every kind of value
+fn values() -> Vec<Value> {
+    let operands = operands();
+    let mut values = Vec::new();
+    for (index, operand) in operands.iter().enumerate() {
+        let other = operands[(index * 7 + 3) % operands.len()];
+        values.push(Value::Direct(*operand));
+        values.push(Value::Indirect(*operand));
+        values.push(Value::OperationalDirect(*operand, other));
+        values.push(Value::OperationalDirect(other, *operand));
+        values.push(Value::OperationalIndirect(*operand, other));
+        values.push(Value::OperationalIndirect(other, *operand));
+    }
+    values
+}
+
+/// every variant, with every register, flag, math type, width and value in each of its
+/// fields
+fn instructions() -> Vec<Instruction> {
+    let values = values();
+    let mut instructions = Vec::new();
+    let mut push = |instruction| instructions.push(instruction);
+    for (index, value) in values.iter().copied().enumerate() {
+        let other = values[(index * 5 + 1) % values.len()];
+        let register = Register::ALL[index % Register::ALL.len()];
+        for (value1, value2) in [(value, other), (other, value)] {
+            for math_type in MathType::ALL {
+                push(Instruction::Add(register, value1, value2, math_type));
+                push(Instruction::Sub(register, value1, value2, math_type));
+                push(Instruction::Mul(register, value1, value2, math_type));
+                push(Instruction::Div(register, value1, value2, math_type));
+                push(Instruction::Mod(register, value1, value2, math_type));
+                push(Instruction::Compare(value1, value2, math_type));
+            }
+            push(Instruction::And(register, value1, value2));
+            push(Instruction::Or(register, value1, value2));
+            push(Instruction::Xor(register, value1, value2));
+            push(Instruction::ShiftLeft(register, value1, value2));
+            push(Instruction::ShiftRight(register, value1, value2));
+            push(Instruction::Move(value1, value2));
+            push(Instruction::SysCall(SysCall::PrintString {
+                start: value1,
+                length: value2,
+            }));
+            push(Instruction::SysCall(SysCall::ReadLine {
+                length: register,
+                start: value1,
+                max_len: value2,
+            }));
+            for width in Width::ALL {
+                push(Instruction::Store(value1, value2, width));
+            }
+        }
+        for (first, second, third) in [
+            (value, other, other),
+            (other, value, other),
+            (other, other, value),
+        ] {
+            push(Instruction::SysCall(SysCall::Open {
+                fd: register,
+                path: first,
+                path_len: second,
+                mode: third,
+            }));
+            push(Instruction::SysCall(SysCall::Read {
+                count: register,
+                fd: first,
+                start: second,
+                length: third,
+            }));
+            push(Instruction::SysCall(SysCall::Write {
+                count: register,
+                fd: first,
+                start: second,
+                length: third,
+            }));
+        }
+        for flag in Flag::ALL {
+            push(Instruction::Jump(flag, value));
+            push(Instruction::JumpNot(flag, value));
+            push(Instruction::Call(flag, value));
+            push(Instruction::CallNot(flag, value));
+        }
+        for from in MathType::ALL {
+            for to in MathType::ALL {
+                push(Instruction::Convert(register, value, from, to));
+            }
+        }
+        push(Instruction::Not(register, value));
+        push(Instruction::Load(register, value));
+        push(Instruction::Push(value));
+        for width in Width::ALL {
+            push(Instruction::LoadMemory(register, value, width));
+            push(Instruction::SignExtend(register, value, width));
+            push(Instruction::ZeroExtend(register, value, width));
+        }
+        push(Instruction::SysCall(SysCall::Print(value)));
+        push(Instruction::SysCall(SysCall::PrintChar(value)));
+        push(Instruction::SysCall(SysCall::Exit(value)));
+        push(Instruction::SysCall(SysCall::Free(value)));
+        push(Instruction::SysCall(SysCall::Close {
+            result: register,
+            fd: value,
+        }));
+        push(Instruction::SysCall(SysCall::Alloc {
+            pointer: register,
+            size: value,
+        }));
+    }
+    for register1 in Register::ALL {
+        for register2 in Register::ALL {
+            push(Instruction::Swap(register1, register2));
+        }
+        push(Instruction::Pop(register1));
+        push(Instruction::SysCall(SysCall::ReadInt(register1)));
+        push(Instruction::SysCall(SysCall::ReadChar(register1)));
+    }
+    push(Instruction::Halt);
+    push(Instruction::Ret);
+    instructions
+}
+
+/// names every variant, so a new one does not compile until `instructions` covers it
+fn variant(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Add(..) => "add",
+        Instruction::Sub(..) => "sub",
+        Instruction::Mul(..) => "mul",
+        Instruction::Div(..) => "div",
+        Instruction::Mod(..) => "mod",
+        Instruction::And(..) => "and",
+        Instruction::Or(..) => "or",
+        Instruction::Xor(..) => "xor",
+        Instruction::Not(..) => "not",
+        Instruction::ShiftLeft(..) => "shl",
+        Instruction::ShiftRight(..) => "shr",
+        Instruction::Compare(..) => "cmp",
+        Instruction::Jump(..) => "jmp",
+        Instruction::JumpNot(..) => "jmpn",
+        Instruction::Load(..) => "load",
+        Instruction::Move(..) => "mov",
+        Instruction::Swap(..) => "swap",
+        Instruction::Halt => "halt",
+        Instruction::Push(..) => "push",
+        Instruction::Pop(..) => "pop",
+        Instruction::Call(..) => "call",
+        Instruction::CallNot(..) => "calln",
+        Instruction::Ret => "ret",
+        Instruction::LoadMemory(..) => "loadN",
+        Instruction::Store(..) => "storeN",
+        Instruction::Convert(..) => "cvt",
+        Instruction::SignExtend(..) => "sextN",
+        Instruction::ZeroExtend(..) => "zextN",
+        Instruction::SysCall(syscall) => match syscall {
+            SysCall::Print(..) => "print",
+            SysCall::PrintChar(..) => "printc",
+            SysCall::PrintString { .. } => "printstr",
+            SysCall::ReadInt(..) => "readint",
+            SysCall::ReadChar(..) => "readc",
+            SysCall::ReadLine { .. } => "readline",
+            SysCall::Open { .. } => "open",
+            SysCall::Read { .. } => "read",
+            SysCall::Write { .. } => "write",
+            SysCall::Close { .. } => "close",
+            SysCall::Exit(..) => "exit",
+            SysCall::Alloc { .. } => "alloc",
+            SysCall::Free(..) => "free",
+        },
+    }
+}
+
+#[test]
+fn every_name_round_trips() {
+    for register in Register::ALL {
+        assert_eq!(Register::try_from(String::from(register)), Ok(register));
+        assert_eq!(
+            Operand::try_from(String::from(register)),
+            Ok(Operand::Register(register))
+        );
+    }
+    for flag in Flag::ALL {
+        assert_eq!(Flag::try_from(String::from(flag)), Ok(flag));
+    }
+    for math_type in MathType::ALL {
+        assert_eq!(MathType::try_from(String::from(math_type)), Ok(math_type));
+    }
+    for width in Width::ALL {
+        assert_eq!(Width::try_from(String::from(width)), Ok(width));
+    }
+    for value in values() {
+        assert_eq!(Value::try_from(String::from(value)), Ok(value));
+    }
+    assert_eq!(Register::try_from("r5".to_string()), Ok(Register::R5));
+    assert_eq!(String::from(Register::R5), "r5");
+}
+
+#[test]
+fn names_are_distinct() {
+    let registers: BTreeSet<_> = Register::ALL.map(Register::text).into();
+    assert_eq!(registers.len(), Register::ALL.len());
+    let flags: BTreeSet<_> = Flag::ALL.map(Flag::text).into();
+    assert_eq!(flags.len(), Flag::ALL.len());
+    for (index, register) in Register::ALL.into_iter().enumerate() {
+        assert_eq!(register.index(), index);
+        assert_eq!(Register::from_index(index), Some(register));
+    }
+}
+
+#[test]
+fn every_instruction_round_trips() {
+    let instructions = instructions();
+    let variants: BTreeSet<_> = instructions.iter().map(variant).collect();
+    assert_eq!(variants.len(), 41);
+    for instruction in instructions {
+        let text = String::from(instruction);
+        assert_eq!(
+            Instruction::try_from(text.clone()),
+            Ok(instruction),
+            "{}",
+            text
+        );
+    }
+}
+
+/// the assembler checks operands against a table of its own, which has to accept every
+/// instruction as it is written
+#[test]
+fn every_instruction_assembles() {
+    let instructions = instructions();
+    let source: Vec<String> = instructions.iter().copied().map(String::from).collect();
+    let mut parser = Parser::new("test".to_string(), source.join("\n"));
+    let mut assembler = Assembler::new();
+    if let Err(error) = assembly::parse(&mut parser, &mut assembler) {
+        panic!("{}", error.render().join("\n"));
+    }
+    let (_, code, _) = assembler.compile(0).unwrap();
+    assert_eq!(code.len(), instructions.len());
+    for ((assembled, instruction), text) in code.into_iter().zip(instructions).zip(source) {
+        assert_eq!(assembled, instruction, "{}", text);
+    }
+}
+
+#[test]
+fn instructions_are_written_as_before() {
+    let r5 = Value::Direct(Operand::Register(Register::R5));
+    let cases = [
+        (
+            Instruction::Add(
+                Register::R5,
+                r5,
+                Value::Direct(Operand::U32(1)),
+                MathType::Signed,
+            ),
+            "add r5 r5 1 S",
+        ),
+        (
+            Instruction::LoadMemory(Register::R0, Value::Indirect(Operand::U32(8)), Width::Half),
+            "load16 r0 [8]",
+        ),
+        (Instruction::Store(r5, r5, Width::Byte), "store8 r5 r5"),
+        (
+            Instruction::CallNot(
+                Flag::EndOfInput,
+                Value::OperationalDirect(
+                    Operand::U32(4),
+                    Operand::Register(Register::ProgramPointer),
+                ),
+            ),
+            "calln I 4+PP",
+        ),
+        (
+            Instruction::SysCall(SysCall::ReadLine {
+                length: Register::R5,
+                start: r5,
+                max_len: Value::Direct(Operand::U32(16)),
+            }),
+            "readline r5 r5 16",
+        ),
+        (
+            Instruction::SysCall(SysCall::ReadChar(Register::R5)),
+            "readc r5",
+        ),
+        (Instruction::Halt, "halt"),
+    ];
+    for (instruction, text) in cases {
+        assert_eq!(String::from(instruction), text);
+        assert_eq!(Instruction::try_from(text.to_string()), Ok(instruction));
+    }
+    // whitespace between words does not matter
+    assert_eq!(
+        Instruction::try_from("  readc\tr5  ".to_string()),
+        Ok(Instruction::SysCall(SysCall::ReadChar(Register::R5)))
+    );
+}
+
+#[test]
+fn signatures_follow_how_instructions_are_read() {
+    use Field::*;
+    let cases = [
+        ("add", vec![Register, Value, Value, MathType], 4),
+        ("cmp", vec![Value, Value, MathType], 2),
+        ("store16", vec![Value, Value], 2),
+        ("jmpn", vec![Flag, Value], 2),
+        ("open", vec![Register, Value, Value, Value], 4),
+        ("halt", vec![], 0),
+    ];
+    for (mnemonic, fields, required) in cases {
+        assert_eq!(
+            Instruction::signature(mnemonic),
+            Some((fields, required)),
+            "{}",
+            mnemonic
+        );
+    }
+    for mnemonic in ["jmp:", "load64", "sext", "addx", ""] {
+        assert_eq!(Instruction::signature(mnemonic), None, "{}", mnemonic);
+    }
+}
+
+#[test]
+fn malformed_instructions_are_rejected() {
+    for text in [
+        "",
+        "r16",
+        "add r16 1 2 U",
+        "add r1 1 2",
+        "add r1 1 2 U U",
+        "addx r1 1 2 U",
+        "ad r1 1 2 U",
+        "halt now",
+        "ret 4",
+        "printx 4",
+        "print",
+        "print 4 4",
+        "readc 4",
+        "readcx r1",
+        "readline r1 0",
+        "load r1",
+        "load64 r1 0",
+        "load8",
+        "store8 0",
+        "store r1 0",
+        "loadr r1 0",
+        "jmp X 0",
+        "call A",
+        "calln A 0 0",
+        "swap r1 0",
+        "exit",
+        "load r0 [",
+        "load r0 [+",
+        "load r0 [+]",
+        "load r0 [r1+r2",
+        "load r0 r1+",
+        "load r0 ]",
+    ] {
+        assert_eq!(Instruction::try_from(text.to_string()), Err(()), "{}", text);
+    }
+    for name in ["R5", "r 5", "pc", "r05", ""] {
+        assert_eq!(Register::try_from(name.to_string()), Err(()), "{}", name);
+    }
+    assert_eq!(Flag::try_from("EE".to_string()), Err(()));
+    assert_eq!(MathType::try_from("u".to_string()), Err(()));
+    assert_eq!(Width::try_from("64".to_string()), Err(()));
+}
@@ -0,0 +1,119 @@
+use plac::codegen::asm::*;
+use plac::codegen::*;
+use plac::StackFrame;
+use std::collections::HashMap;
+
+fn ir(line: &str) -> Ir {
+    line.to_string().try_into().unwrap()
+}
+
+fn direct(value: u32) -> Value {
+    Value::Direct(Operand::U32(value))
+}
+
+fn load(value: u32) -> Instruction {
+    Instruction::Load(Register::R0, direct(value))
+}
+
+fn errors(assembler: &mut Assembler) -> String {
+    match assembler.compile(0) {
+        Ok(_) => panic!("compiled"),
+        Err(error) => error.render().join("\n"),
+    }
+}
+
+#[test]
+fn labels_resolve_to_their_adress() {
+    let mut assembler = Assembler::new();
+    for line in [
+        "load r0 0",
+        "jmp: A end",
+        "back: load r0 1",
+        "end:",
+        "jmpn: E back",
+        "halt",
+    ] {
+        assembler.add_ir(ir(line));
+    }
+    let (data, code, entry) = assembler.compile(0).unwrap();
+    assert!(data.is_empty());
+    assert_eq!(entry, 0);
+    assert_eq!(
+        code,
+        [
+            load(0),
+            Instruction::Jump(Flag::Always, direct(12)),
+            load(1),
+            Instruction::JumpNot(Flag::Equal, direct(8)),
+            Instruction::Halt,
+        ]
+    );
+}
+
+#[test]
+fn init_block_comes_first() {
+    let mut assembler = Assembler::new();
+    let stackframe = assembler.add_stackframe(StackFrame {
+        module: plac::Ref(0, std::marker::PhantomData),
+        variables: HashMap::new(),
+    });
+    let block = assembler.add_block(stackframe);
+    assembler.add_ir_to(block, ir("ret"));
+    assembler.add_ir(Ir::Call(Flag::Always, block));
+    assembler.add_ir(ir("halt"));
+    let (data, code, entry) = assembler.compile(1).unwrap();
+    assert_eq!(data, [0]);
+    assert_eq!(entry, 1);
+    assert_eq!(
+        code,
+        [
+            Instruction::Call(Flag::Always, direct(9)),
+            Instruction::Halt,
+            Instruction::Ret,
+        ]
+    );
+}
+
+#[test]
+fn statics_are_laid_out_before_the_code() {
+    let mut assembler = Assembler::new();
+    assembler.add_static("a".to_string(), vec![1, 0x0203]);
+    assembler.add_data(Data::Label("s".to_string()));
+    assembler.add_data(Data::Bytes(b"hi!".to_vec()));
+    assembler.add_data(Data::Align(4));
+    for symbol in ["a", "a_len", "s", "s_len"] {
+        let instruction = load(0);
+        assembler.add_ir(Ir::Symbolic(instruction, vec![(0, symbol.to_string())]));
+    }
+    assembler.add_ir(ir("halt"));
+    let (data, code, entry) = assembler.compile(2).unwrap();
+    assert_eq!(
+        data,
+        [0, 0, 1, 0, 0, 0, 3, 2, 0, 0, b'h', b'i', b'!', 0, 0, 0]
+    );
+    assert_eq!(entry, 16);
+    assert_eq!(code[..4], [load(2), load(8), load(10), load(3)]);
+}
+
+#[test]
+fn entry_starts_at_its_label() {
+    let mut assembler = Assembler::new();
+    for line in ["halt", "start: load r0 1", "halt"] {
+        assembler.add_ir(ir(line));
+    }
+    assembler.set_entry("start".to_string());
+    let (_, _, entry) = assembler.compile(0).unwrap();
+    assert_eq!(entry, 4);
+}
+
+#[test]
+fn undefined_labels_are_errors() {
+    let mut assembler = Assembler::new();
+    assembler.add_ir(ir("jmp: A nowhere"));
+    assert!(errors(&mut assembler).contains("cannot find symbol `nowhere`"));
+
+    let mut assembler = Assembler::new();
+    assembler.add_ir(ir("halt"));
+    assembler.set_entry("start".to_string());
+    assert!(errors(&mut assembler).contains("cannot find entry label `start`"));
+}
@@ -0,0 +1,253 @@
+use plac::codegen::asm::*;
+use plac::codegen::assembly::*;
+use plac::codegen::Assembler;
+use plac::parser::Parser;
+
+/// the rendered diagnostics of `source`, which must not assemble
+fn diagnostics(source: &str) -> String {
+    let mut parser = Parser::new("test".to_string(), source.to_string());
+    let mut assembler = Assembler::new();
+    match parse(&mut parser, &mut assembler) {
+        Ok(()) => panic!("assembled\n{}", source),
+        Err(error) => error.render().join("\n"),
+    }
+}
+
+fn reports(source: &str, code: u64, messages: &[&str]) {
+    let rendered = diagnostics(source);
+    assert!(rendered.contains(&format!("[{}]", code)), "{}", rendered);
+    for message in messages {
+        assert!(rendered.contains(message), "{}\n{}", message, rendered);
+    }
+}
+
+#[test]
+fn unknown_mnemonic() {
+    reports(
+        "addx r1 1 2 U",
+        UNKNOWN_MNEMONIC,
+        &["unknown instruction `addx`", "not an instruction"],
+    );
+}
+
+#[test]
+fn expected_operand() {
+    reports(
+        "add r16 1 2 U",
+        EXPECTED_OPERAND,
+        &["expected register, found `r16`"],
+    );
+    reports("jmp X 0", EXPECTED_OPERAND, &["expected flag, found `X`"]);
+    reports(
+        "add r1 1 2",
+        EXPECTED_OPERAND,
+        &[
+            "expected math type, found end of line",
+            "`add` takes 4 operands",
+        ],
+    );
+    reports(".word", EXPECTED_OPERAND, &["`.word` expects 1 operands"]);
+    reports(
+        "cmp r1",
+        EXPECTED_OPERAND,
+        &[
+            "expected value, found end of line",
+            "`cmp` takes 2 operands",
+        ],
+    );
+    reports(
+        ".align 0",
+        EXPECTED_OPERAND,
+        &["expected positive alignment, found `0`"],
+    );
+}
+
+#[test]
+fn unexpected_operand() {
+    reports(
+        "halt now",
+        UNEXPECTED_OPERAND,
+        &["unexpected operand `now`", "`halt` takes 0 operands"],
+    );
+    reports(
+        ".entry a b",
+        UNEXPECTED_OPERAND,
+        &["unexpected operand `b`"],
+    );
+}
+
+#[test]
+fn unknown_directive() {
+    reports(
+        ".words 1",
+        UNKNOWN_DIRECTIVE,
+        &["unknown directive `.words`", "not a directive"],
+    );
+}
+
+#[test]
+fn invalid_label() {
+    reports("a-b: halt", INVALID_LABEL, &["invalid label `a-b`"]);
+}
+
+#[test]
+fn duplicate_label() {
+    reports(
+        "a: halt\na: halt",
+        DUPLICATE_LABEL,
+        &["label `a` is defined more than once", "redefined here"],
+    );
+    reports(
+        "s: .word 1\ns_len: halt",
+        DUPLICATE_LABEL,
+        &["label `s_len` is defined more than once"],
+    );
+    reports(
+        "a: halt\n.extern a",
+        DUPLICATE_LABEL,
+        &["declared as external here"],
+    );
+}
+
+#[test]
+fn undefined_label() {
+    reports(
+        "jmp: A nowhere",
+        UNDEFINED_LABEL,
+        &["cannot find label `nowhere`", "not defined in this file"],
+    );
+    reports(
+        "load r0 [table+r1]",
+        UNDEFINED_LABEL,
+        &["cannot find label `table`"],
+    );
+    reports(
+        ".entry start\nhalt",
+        UNDEFINED_LABEL,
+        &["cannot find label `start`"],
+    );
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let rendered = diagnostics("addx 1\nhalt now\n.words\njmp: A nowhere");
+    for code in [
+        UNKNOWN_MNEMONIC,
+        UNEXPECTED_OPERAND,
+        UNKNOWN_DIRECTIVE,
+        UNDEFINED_LABEL,
+    ] {
+        assert!(rendered.contains(&format!("[{}]", code)), "{}", rendered);
+    }
+}
+
+#[test]
+fn register_like_words_are_not_labels() {
+    for source in ["load r0 r16", "load r0 [r16+4]", "mov r99 1"] {
+        reports(source, EXPECTED_OPERAND, &["expected value, found `"]);
+        assert!(
+            !diagnostics(source).contains("cannot find label"),
+            "{}",
+            source
+        );
+    }
+    let mut parser = Parser::new("test".to_string(), "r1x: halt\nload r0 r1x".to_string());
+    assert!(parse(&mut parser, &mut Assembler::new()).is_ok());
+}
+
+/// the data and code of `source`, assembled and linked on its own
+fn compile(source: &str) -> (Vec<u8>, Vec<Instruction>) {
+    let mut parser = Parser::new("test".to_string(), source.to_string());
+    let mut assembler = Assembler::new();
+    if let Err(error) = parse(&mut parser, &mut assembler) {
+        panic!("{}\n{}", error.render().join("\n"), source);
+    }
+    let (data, code, _) = assembler.compile(0).unwrap();
+    (data, code)
+}
+
+#[test]
+fn data_directives_lay_out_bytes() {
+    let (data, code) = compile(
+        "
+words: .word 1 -1
+bytes: .byte 255 -2 7
+text: .string \"a\\\"b\\n\"
+.zero 2
+.align 4
+end:
+    .word 7
+load r0 words_len
+load r1 text_len
+load r2 end
+load r3 [bytes+1]
+load r4 end_len
+halt
+",
+    );
+    assert_eq!(
+        data,
+        [
+            1, 0, 0, 0, 255, 255, 255, 255, 255, 254, 7, b'a', b'"', b'b', b'\n', 0, 0, 0, 0, 0, 7,
+            0, 0, 0
+        ]
+    );
+    let load = |register, value| Instruction::Load(register, value);
+    let direct = |value| Value::Direct(Operand::U32(value));
+    assert_eq!(
+        code[..5],
+        [
+            load(Register::R0, direct(8)),
+            load(Register::R1, direct(6)),
+            load(Register::R2, direct(20)),
+            load(
+                Register::R3,
+                Value::OperationalIndirect(Operand::U32(8), Operand::U32(1))
+            ),
+            load(Register::R4, direct(4)),
+        ]
+    );
+}
+
+#[test]
+fn malformed_data_is_rejected() {
+    reports(".word 1 x", EXPECTED_OPERAND, &["expected word, found `x`"]);
+    reports(
+        ".byte 256",
+        EXPECTED_OPERAND,
+        &["expected byte, found `256`"],
+    );
+    reports(
+        ".string abc",
+        EXPECTED_OPERAND,
+        &["expected string literal"],
+    );
+    reports(
+        ".string \"\\q\"",
+        EXPECTED_OPERAND,
+        &["expected string literal"],
+    );
+    reports(
+        ".zero -1",
+        EXPECTED_OPERAND,
+        &["expected count, found `-1`"],
+    );
+    for count in ["4294967296", "18446744073709551615"] {
+        reports(
+            &format!(".zero {}", count),
+            EXPECTED_OPERAND,
+            &[&format!("expected count, found `{}`", count)],
+        );
+    }
+    reports(
+        ".string \"a\" \"b\"",
+        UNEXPECTED_OPERAND,
+        &["unexpected operand"],
+    );
+}
+
+#[test]
+fn diagnostics_show_their_line_after_a_quote_in_a_string() {
+    let rendered = diagnostics("s: .string \"a\\\"b\"\nhalt now");
+    assert!(rendered.contains("halt now"), "{}", rendered);
+}
@@ -0,0 +1,43 @@
+//! the fixtures the integration tests share
+
+// every test uses only some of them
+#![allow(dead_code)]
+
+use plac::codegen::assembly;
+use plac::codegen::interpreter::Interpreter;
+use plac::codegen::linker::Linker;
+use plac::codegen::object::Object;
+use plac::codegen::Assembler;
+use plac::parser::Parser;
+
+/// `source` parsed, assembled and linked on its own, panicking with the diagnostics when it
+/// does not assemble
+pub fn object(source: &str) -> Object {
+    let mut parser = Parser::new("test".to_string(), source.to_string());
+    let mut assembler = Assembler::new();
+    if let Err(error) = assembly::parse(&mut parser, &mut assembler) {
+        panic!("{}\n{}", error.render().join("\n"), source);
+    }
+    let mut linker = Linker::new();
+    linker.add("test".to_string(), assembler.assemble(0));
+    linker.link().ok().unwrap()
+}
+
+/// an interpreter with `source` loaded
+pub fn assemble(source: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(object(source)).unwrap();
+    interpreter
+}
+
+/// an interpreter with the ir `lines` loaded, compiled after one reserved byte
+pub fn compile(lines: &[&str]) -> Interpreter {
+    let mut assembler = Assembler::new();
+    for line in lines {
+        assembler.add_ir(line.to_string().try_into().unwrap());
+    }
+    let (data, instructions, start) = assembler.compile(1).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.load(data, instructions, start).unwrap();
+    interpreter
+}
@@ -0,0 +1,302 @@
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// `R0` and the flags after `instruction`, which the engine leaves the same
+fn run(instruction: Instruction) -> (u32, BTreeSet<Flag>) {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load(vec![], vec![instruction, Instruction::Halt], 0)
+        .unwrap();
+    let mut engine = interpreter.clone();
+    let trap = interpreter.execute();
+    assert_eq!(Engine::new(&mut engine).execute(), trap);
+    let r0 = *interpreter.get_register(&Register::R0);
+    assert_eq!(*engine.get_register(&Register::R0), r0, "{:?}", instruction);
+    assert_eq!(engine.flags, interpreter.flags, "{:?}", instruction);
+    interpreter.flags.remove(&Flag::Always);
+    (r0, interpreter.flags)
+}
+
+fn direct(value: u32) -> Value {
+    Value::Direct(Operand::U32(value))
+}
+
+fn float(value: f32) -> u32 {
+    value.to_bits()
+}
+
+fn signed(value: i32) -> u32 {
+    value as u32
+}
+
+fn ordering(ordering: Option<Ordering>) -> BTreeSet<Flag> {
+    match ordering {
+        Some(Ordering::Less) => BTreeSet::from([Flag::Lesser]),
+        Some(Ordering::Equal) => BTreeSet::from([Flag::Equal]),
+        Some(Ordering::Greater) => BTreeSet::from([Flag::Greater]),
+        None => BTreeSet::new(),
+    }
+}
+
+#[test]
+fn cmp_compares_as_the_math_type() {
+    use MathType::*;
+    let nan = f32::NAN;
+    let table = [
+        (signed(-1), 0, Unsigned, Some(Ordering::Greater)),
+        (signed(-1), 0, Signed, Some(Ordering::Less)),
+        (
+            signed(i32::MIN),
+            signed(i32::MAX),
+            Unsigned,
+            Some(Ordering::Greater),
+        ),
+        (
+            signed(i32::MIN),
+            signed(i32::MAX),
+            Signed,
+            Some(Ordering::Less),
+        ),
+        (signed(-7), signed(-7), Signed, Some(Ordering::Equal)),
+        (float(-1.0), float(0.0), Float, Some(Ordering::Less)),
+        (float(-1.0), float(0.0), Unsigned, Some(Ordering::Greater)),
+        (float(1.5), float(1.5), Float, Some(Ordering::Equal)),
+        (float(0.0), float(-0.0), Float, Some(Ordering::Equal)),
+        (
+            float(f32::INFINITY),
+            float(f32::MAX),
+            Float,
+            Some(Ordering::Greater),
+        ),
+        (
+            float(f32::NEG_INFINITY),
+            float(-f32::MAX),
+            Float,
+            Some(Ordering::Less),
+        ),
+        (float(nan), float(1.0), Float, None),
+        (float(1.0), float(nan), Float, None),
+        (float(nan), float(nan), Float, None),
+        (float(nan), float(nan), Unsigned, Some(Ordering::Equal)),
+    ];
+    for (a, b, math_type, expected) in table {
+        let cmp = Instruction::Compare(direct(a), direct(b), math_type);
+        assert_eq!(run(cmp).1, ordering(expected), "{}", String::from(cmp));
+    }
+}
+
+#[test]
+fn cmp_with_nan_clears_the_comparison() {
+    let mut interpreter = Interpreter::new();
+    let instructions = vec![
+        Instruction::Compare(direct(1), direct(1), MathType::Unsigned),
+        Instruction::Compare(direct(float(f32::NAN)), direct(0), MathType::Float),
+        Instruction::Halt,
+    ];
+    interpreter.load(vec![], instructions, 0).unwrap();
+    interpreter.execute();
+    assert_eq!(interpreter.flags, BTreeSet::from([Flag::Always]));
+}
+
+#[test]
+fn cvt_converts_and_saturates() {
+    use MathType::*;
+    let nan = float(f32::NAN);
+    let table = [
+        // integers to floats round to the nearest float
+        (0, Unsigned, Float, float(0.0), false),
+        (16_777_217, Unsigned, Float, float(16_777_216.0), false),
+        (u32::MAX, Unsigned, Float, float(4_294_967_296.0), false),
+        (signed(-1), Signed, Float, float(-1.0), false),
+        (
+            signed(i32::MIN),
+            Signed,
+            Float,
+            float(-2_147_483_648.0),
+            false,
+        ),
+        (signed(-1), Unsigned, Float, float(4_294_967_296.0), false),
+        // floats to integers round toward zero
+        (float(1.9), Float, Signed, 1, false),
+        (float(-1.9), Float, Signed, signed(-1), false),
+        (float(-0.0), Float, Signed, 0, false),
+        (
+            float(-2_147_483_648.0),
+            Float,
+            Signed,
+            signed(i32::MIN),
+            false,
+        ),
+        (
+            float(2_147_483_648.0),
+            Float,
+            Signed,
+            signed(i32::MAX),
+            true,
+        ),
+        (float(-3e9), Float, Signed, signed(i32::MIN), true),
+        (float(f32::INFINITY), Float, Signed, signed(i32::MAX), true),
+        (
+            float(f32::NEG_INFINITY),
+            Float,
+            Signed,
+            signed(i32::MIN),
+            true,
+        ),
+        (nan, Float, Signed, 0, true),
+        (float(1.9), Float, Unsigned, 1, false),
+        (float(-0.5), Float, Unsigned, 0, false),
+        (float(-1.5), Float, Unsigned, 0, true),
+        (
+            float(4_294_967_040.0),
+            Float,
+            Unsigned,
+            4_294_967_040,
+            false,
+        ),
+        (float(4_294_967_296.0), Float, Unsigned, u32::MAX, true),
+        (float(f32::INFINITY), Float, Unsigned, u32::MAX, true),
+        (float(f32::NEG_INFINITY), Float, Unsigned, 0, true),
+        (nan, Float, Unsigned, 0, true),
+        // between integers
+        (5, Unsigned, Signed, 5, false),
+        (1 << 31, Unsigned, Signed, signed(i32::MAX), true),
+        (signed(-1), Signed, Unsigned, 0, true),
+        (signed(i32::MAX), Signed, Unsigned, signed(i32::MAX), false),
+        // to the same type
+        (u32::MAX, Unsigned, Unsigned, u32::MAX, false),
+        (signed(-5), Signed, Signed, signed(-5), false),
+        (0x7fc0_0001, Float, Float, 0x7fc0_0001, false),
+        (
+            float(f32::NEG_INFINITY),
+            Float,
+            Float,
+            float(f32::NEG_INFINITY),
+            false,
+        ),
+    ];
+    for (value, from, to, result, overflow) in table {
+        let cvt = Instruction::Convert(Register::R0, direct(value), from, to);
+        let (value, flags) = run(cvt);
+        assert_eq!(value, result, "{}", String::from(cvt));
+        assert_eq!(
+            flags.contains(&Flag::Overflow),
+            overflow,
+            "{}",
+            String::from(cvt)
+        );
+    }
+}
+
+#[test]
+fn cvt_sets_flags_as_the_type_it_converts_to() {
+    use MathType::*;
+    let table = [
+        (float(-2.5), Float, Signed, Some(Ordering::Less)),
+        (float(-2.5), Float, Unsigned, Some(Ordering::Equal)),
+        (float(0.5), Float, Signed, Some(Ordering::Equal)),
+        (signed(-3), Signed, Float, Some(Ordering::Less)),
+        (u32::MAX, Unsigned, Signed, Some(Ordering::Greater)),
+        (float(f32::NAN), Float, Float, None),
+    ];
+    for (value, from, to, expected) in table {
+        let cvt = Instruction::Convert(Register::R0, direct(value), from, to);
+        let mut flags = run(cvt).1;
+        flags.remove(&Flag::Overflow);
+        assert_eq!(flags, ordering(expected), "{}", String::from(cvt));
+    }
+}
+
+#[test]
+fn sext_and_zext_extend_the_low_bytes() {
+    use Width::*;
+    let table = [
+        (0x80, Byte, 0xffff_ff80, 0x80),
+        (0x7f, Byte, 0x7f, 0x7f),
+        (0x1234_5680, Byte, 0xffff_ff80, 0x80),
+        (0xffff_ff00, Byte, 0, 0),
+        (0x8000, Half, 0xffff_8000, 0x8000),
+        (0xdead_7fff, Half, 0x7fff, 0x7fff),
+        (0xdead_beef, Half, 0xffff_beef, 0xbeef),
+        (0xdead_beef, Word, 0xdead_beef, 0xdead_beef),
+        (0x7fff_ffff, Word, 0x7fff_ffff, 0x7fff_ffff),
+    ];
+    for (value, width, extended_sign, extended_zero) in table {
+        let sext = Instruction::SignExtend(Register::R0, direct(value), width);
+        let (result, flags) = run(sext);
+        assert_eq!(result, extended_sign, "{}", String::from(sext));
+        let expected = ordering(Some((extended_sign as i32).cmp(&0)));
+        assert_eq!(flags, expected, "{}", String::from(sext));
+
+        let zext = Instruction::ZeroExtend(Register::R0, direct(value), width);
+        let (result, flags) = run(zext);
+        assert_eq!(result, extended_zero, "{}", String::from(zext));
+        assert_eq!(
+            flags,
+            ordering(Some(extended_zero.cmp(&0))),
+            "{}",
+            String::from(zext)
+        );
+    }
+}
+
+#[test]
+fn conversions_assemble() {
+    let cases = [
+        (
+            "cmp r1 [4] S",
+            Instruction::Compare(
+                Value::Direct(Operand::Register(Register::R1)),
+                Value::Indirect(Operand::U32(4)),
+                MathType::Signed,
+            ),
+        ),
+        (
+            "cmp 1 2",
+            Instruction::Compare(direct(1), direct(2), MathType::Unsigned),
+        ),
+        (
+            "cvt r2 r3 F S",
+            Instruction::Convert(
+                Register::R2,
+                Value::Direct(Operand::Register(Register::R3)),
+                MathType::Float,
+                MathType::Signed,
+            ),
+        ),
+        (
+            "sext8 r0 255",
+            Instruction::SignExtend(Register::R0, direct(255), Width::Byte),
+        ),
+        (
+            "zext16 r0 r0",
+            Instruction::ZeroExtend(
+                Register::R0,
+                Value::Direct(Operand::Register(Register::R0)),
+                Width::Half,
+            ),
+        ),
+    ];
+    for (text, instruction) in cases {
+        assert_eq!(Instruction::try_from(text.to_string()), Ok(instruction));
+        assert_eq!(String::from(instruction), text);
+    }
+    assert_eq!(
+        Instruction::try_from("cmp 1 2 U".to_string()),
+        Ok(Instruction::Compare(direct(1), direct(2), MathType::Unsigned))
+    );
+    for text in [
+        "cmp 1",
+        "cmp 1 2 X",
+        "cmp 1 2 U U",
+        "cvt r0 1 F",
+        "sext r0 1",
+        "zext64 r0 1",
+        "sext8 1 1",
+    ] {
+        assert_eq!(Instruction::try_from(text.to_string()), Err(()), "{}", text);
+    }
+}
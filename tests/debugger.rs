@@ -0,0 +1,299 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::debugger::*;
+use plac::codegen::history::History;
+use plac::codegen::interpreter::*;
+
+fn load(source: &str) -> Debugger {
+    let object = common::object(source);
+    let symbols = object.symbols.clone();
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(object).unwrap();
+    let mut debugger = Debugger::new(interpreter);
+    debugger.add_symbols(&symbols);
+    debugger
+}
+
+/// `value` ends up as 3 * 2 * 2, `count` calls itself until r1 is 0
+const PROGRAM: &str = "
+value: .zero 4
+double:
+    add r0 r0 r0 U
+    ret
+count:
+    cmp r1 0
+    jmp: E done
+    sub r1 r1 1 U
+    call A count
+done:
+    ret
+main:
+    load r0 3
+    call A double
+    store32 value r0
+    call A double
+    store32 value r0
+    load r1 3
+    call A count
+    halt
+.entry main
+";
+
+fn adress(debugger: &Debugger, label: &str) -> u32 {
+    debugger.resolve(label).unwrap()
+}
+
+#[test]
+fn breakpoints_stop_at_adresses_and_labels() {
+    let mut debugger = load(PROGRAM);
+    assert_eq!(debugger.pc(), adress(&debugger, "main"));
+    let double = adress(&debugger, "double");
+    debugger.break_at(double);
+    assert_eq!(debugger.run(), Stop::Breakpoint(double));
+    assert_eq!(debugger.run(), Stop::Breakpoint(double));
+    assert_eq!(*debugger.interpreter.get_register(&Register::R0), 6);
+    assert!(debugger.delete(double));
+    assert!(!debugger.delete(double));
+    let Stop::Trap(trap) = debugger.run() else {
+        panic!("the program did not end");
+    };
+    assert_eq!(trap.kind, TrapKind::Halt(12));
+    assert_eq!(debugger.breakpoints().count(), 0);
+
+    assert_eq!(debugger.resolve("0x10"), Some(16));
+    assert_eq!(debugger.resolve("16"), Some(16));
+    assert_eq!(debugger.resolve("nowhere"), None);
+}
+
+#[test]
+fn steps_execute_one_instruction() {
+    let mut debugger = load(PROGRAM);
+    let main = adress(&debugger, "main");
+    assert_eq!(debugger.step(), Stop::Step);
+    assert_eq!(debugger.pc(), main + 4);
+    assert_eq!(debugger.step(), Stop::Step);
+    assert_eq!(debugger.pc(), adress(&debugger, "double"));
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let mut debugger = load(PROGRAM);
+    let main = adress(&debugger, "main");
+    for index in 1..=7 {
+        assert_eq!(debugger.step_over(), Stop::Step);
+        assert_eq!(debugger.pc(), main + 4 * index);
+    }
+    assert_eq!(*debugger.interpreter.get_register(&Register::R1), 0);
+
+    // a breakpoint in the callee still stops, also in a recursive one
+    let mut debugger = load(PROGRAM);
+    let done = adress(&debugger, "done");
+    debugger.break_at(adress(&debugger, "main") + 24);
+    debugger.run();
+    debugger.break_at(done);
+    assert_eq!(debugger.step_over(), Stop::Breakpoint(done));
+    assert!(debugger.delete(done));
+    // returns from the innermost call, to `done` again
+    assert_eq!(debugger.step_over(), Stop::Step);
+    assert_eq!(debugger.pc(), done);
+}
+
+#[test]
+fn watches_stop_on_changes() {
+    let mut debugger = load(PROGRAM);
+    let value = adress(&debugger, "value");
+    debugger.watch(Watch::Memory(value));
+    debugger.watch(Watch::Memory(9000));
+    debugger.watch(Watch::Register(Register::R1));
+    debugger.watch(Watch::Register(Register::R1));
+    assert_eq!(debugger.watches().count(), 3);
+    let changed = |watch, old, new| Stop::Changed { watch, old, new };
+    assert_eq!(
+        debugger.run(),
+        changed(Watch::Memory(value), Some(0), Some(6))
+    );
+    assert_eq!(
+        debugger.run(),
+        changed(Watch::Memory(value), Some(6), Some(12))
+    );
+    assert_eq!(
+        debugger.run(),
+        changed(Watch::Register(Register::R1), Some(0), Some(3))
+    );
+    assert!(debugger.unwatch(Watch::Register(Register::R1)));
+    assert!(!debugger.unwatch(Watch::Register(Register::R1)));
+    assert!(matches!(debugger.run(), Stop::Trap(trap) if trap.is_halt()));
+}
+
+#[test]
+fn views_show_the_state() {
+    let mut debugger = load(PROGRAM);
+    let main = adress(&debugger, "main");
+    let double = adress(&debugger, "double");
+    debugger.break_at(main + 8);
+    debugger.step();
+    debugger.step();
+    assert_eq!(
+        debugger.list(1),
+        format!(
+            "double:\n=> {:>8}  add r0 r0 r0 U\n   {:>8}  ret\n",
+            double,
+            double + 4
+        )
+    );
+    debugger.step();
+    assert_eq!(
+        debugger.stack(8),
+        format!(
+            "{:>8}: {:#010x} {}\n",
+            debugger.interpreter.stack.end - 4,
+            main + 8,
+            main + 8
+        )
+    );
+    debugger.step();
+    assert_eq!(
+        debugger.list(1),
+        format!(
+            "   {:>8}  call A {}\n=> {:>8}  store32 0 r0\n   {:>8}  call A {}\n",
+            main + 4,
+            double,
+            main + 8,
+            main + 12,
+            double
+        )
+    );
+    debugger.step();
+    debugger.break_at(main + 16);
+    assert!(debugger.list(1).contains(&format!(" * {:>8}", main + 16)));
+    assert!(debugger
+        .registers()
+        .starts_with("r0  0x00000006          6           6\n"));
+    assert_eq!(debugger.registers().lines().count(), Register::ALL.len());
+    assert_eq!(debugger.flags(), "Greater Always\n");
+}
+
+#[test]
+fn lists_stop_at_the_limit_and_the_ends_of_memory() {
+    let mut debugger = load(PROGRAM);
+    let all = debugger.list(LIST_LIMIT);
+    assert_eq!(all.lines().filter(|line| line.contains("=>")).count(), 1);
+    for count in ["65", "1000000000", "3000000000", "4294967295"] {
+        assert_eq!(debugger.command(&format!("l {}", count)), Ok(all.clone()));
+    }
+    debugger
+        .interpreter
+        .set_register(Register::ProgramCounter, u32::MAX - 1);
+    assert_eq!(debugger.list(u32::MAX), "");
+    debugger
+        .interpreter
+        .set_register(Register::ProgramCounter, 0);
+    assert_eq!(debugger.list(u32::MAX), debugger.list(LIST_LIMIT));
+    assert!(debugger.list(u32::MAX).contains("halt"));
+}
+
+#[test]
+fn commands_drive_the_debugger() {
+    let mut debugger = load(PROGRAM);
+    let main = adress(&debugger, "main");
+    let double = adress(&debugger, "double");
+    assert_eq!(
+        debugger.command("break double"),
+        Ok(format!("breakpoint at {}\n", double))
+    );
+    assert_eq!(
+        debugger.command("c"),
+        Ok(format!(
+            "breakpoint at {}\ndouble:\n=> {:>8}  add r0 r0 r0 U\n",
+            double, double
+        ))
+    );
+    assert_eq!(debugger.command("d double"), Ok(String::new()));
+    assert_eq!(debugger.command("watch value"), Ok(String::new()));
+    assert_eq!(
+        debugger.command("continue"),
+        Ok(format!(
+            "[0] changed from 0 to 6\n=> {:>8}  call A {}\n",
+            main + 12,
+            double
+        ))
+    );
+    assert_eq!(debugger.command("unwatch value"), Ok(String::new()));
+    assert_eq!(
+        debugger.command("s 2"),
+        Ok(format!("=> {:>8}  ret\n", double + 4))
+    );
+    assert_eq!(
+        debugger.command("n"),
+        Ok(format!("=> {:>8}  store32 0 r0\n", main + 16))
+    );
+    assert_eq!(debugger.command(""), Ok(String::new()));
+    assert!(debugger.command("help").unwrap().contains("step [n]"));
+    assert!(debugger
+        .command("s 1000")
+        .unwrap()
+        .starts_with(&format!("stopped at {}: Halt(12)\n", main + 28)));
+
+    assert_eq!(
+        debugger.command("fly"),
+        Err("unknown command `fly`, try `help`".to_string())
+    );
+    assert_eq!(
+        debugger.command("b"),
+        Err("expected an adress or label".to_string())
+    );
+    assert_eq!(
+        debugger.command("b nowhere"),
+        Err("no adress or label `nowhere`".to_string())
+    );
+    assert_eq!(
+        debugger.command("d 4"),
+        Err("no breakpoint at 4".to_string())
+    );
+    assert_eq!(
+        debugger.command("s many"),
+        Err("expected a number, not `many`".to_string())
+    );
+    assert_eq!(
+        debugger.command("u r1"),
+        Err("r1 is not watched".to_string())
+    );
+}
+
+#[test]
+fn back_and_rewind_undo_instructions() {
+    let mut debugger = load(PROGRAM);
+    let main = adress(&debugger, "main");
+    let double = adress(&debugger, "double");
+    assert_eq!(
+        debugger.command("back"),
+        Err("nothing to step back over".to_string())
+    );
+    debugger.interpreter.set_history(Some(History::new(100)));
+    debugger.command("s 7").unwrap();
+    assert_eq!(debugger.pc(), double + 4);
+    assert_eq!(
+        debugger.command("p 2"),
+        Ok(format!("=> {:>8}  call A {}\n", main + 12, double))
+    );
+    assert_eq!(*debugger.interpreter.get_register(&Register::R0), 6);
+    debugger.command("watch value").unwrap();
+    assert_eq!(
+        debugger.command("rewind value"),
+        Ok(format!("=> {:>8}  store32 0 r0\n", main + 8))
+    );
+    assert_eq!(
+        debugger.command("rewind value"),
+        Err("no write to 0 in the history".to_string())
+    );
+    // the watch takes the value from after the rewind
+    assert_eq!(
+        debugger.run(),
+        Stop::Changed {
+            watch: Watch::Memory(0),
+            old: Some(0),
+            new: Some(6)
+        }
+    );
+}
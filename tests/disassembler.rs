@@ -0,0 +1,95 @@
+mod common;
+
+use plac::codegen::disassembler::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::object::Object;
+use plac::codegen::*;
+
+fn assert_round_trips(object: &Object) {
+    let text = disassemble(object);
+    let reassembled = common::object(&text);
+    assert_eq!(reassembled.data, object.data, "{}", text);
+    assert_eq!(reassembled.code, object.code, "{}", text);
+    assert_eq!(reassembled.entry, object.entry, "{}", text);
+}
+
+const FIBONACCI: &str = "
+    load r0 15
+    load r1 1
+    load r2 2
+0:  sub r0 r0 1 U
+    swap r1 r2
+    add r2 r1 r2 U
+    print r2
+    cmp r0 0
+    jmpn: E 0
+    halt
+";
+
+const DATA: &str = "
+msg:    .string \"hi\\n\"
+        .align 4
+table:  .word 1 2 3 4 5 6 7 8 9 10
+        .zero 2
+helper:
+    printstr msg msg_len
+    jmp: A back
+start:
+    load r1 [table]
+    jmp: A helper
+back:
+    halt
+.entry start
+";
+
+#[test]
+fn labels_jump_targets_and_entry() {
+    let object = common::object(DATA);
+    let text = disassemble(&object);
+    assert!(text.contains("msg:\n"));
+    assert!(text.contains("table:\n"));
+    assert!(text.contains("start:\n    load r1 [4]\n    jmp: A helper\n"));
+    assert!(text.contains(".entry start"));
+}
+
+#[test]
+fn synthesizes_labels_without_symbols() {
+    let mut assembler = Assembler::new();
+    for line in FIBONACCI.lines().filter(|line| !line.trim().is_empty()) {
+        assembler.add_ir(Ir::try_from(line.to_string()).unwrap());
+    }
+    let (data, code, entry) = assembler.compile(0).unwrap();
+    let object = Object {
+        data,
+        code,
+        entry,
+        symbols: Vec::new(),
+        relocations: Vec::new(),
+    };
+    let text = disassemble(&object);
+    assert!(text.contains("L12:\n    sub r0 r0 1 U"));
+    assert!(text.contains("jmpn: E L12"));
+    assert_round_trips(&object);
+}
+
+#[test]
+fn assembled_programs_round_trip() {
+    assert_round_trips(&common::object(FIBONACCI));
+    assert_round_trips(&common::object(DATA));
+    assert_round_trips(&common::object("halt\n"));
+}
+
+#[test]
+fn loaded_memory_round_trips() {
+    let object = common::object(DATA);
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(object.clone()).unwrap();
+    let from_memory = from_memory(&interpreter.memory, object.entry);
+    assert_eq!(from_memory.data, object.data);
+    assert_eq!(from_memory.code, object.code);
+    assert_round_trips(&from_memory);
+    assert_eq!(
+        common::object(&disassemble_interpreter(&interpreter)).code,
+        object.code
+    );
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::Buffer;
+use plac::codegen::memory::FlatMemory;
+
+fn registers<M: Memory, I: Io>(interpreter: &mut Interpreter<M, I>) -> Vec<u32> {
+    Register::ALL
+        .iter()
+        .map(|register| *interpreter.get_register(register))
+        .collect()
+}
+
+/// runs `interpreter` with both `Interpreter::execute` and `Engine`, which have to agree
+fn differential(interpreter: Interpreter) -> Trap {
+    let interpreter = interpreter.with_io(Buffer::with_input("12 -3 x\nline\n\n7"));
+    let mut reference = interpreter.clone();
+    let mut decoded = interpreter;
+    let trap = reference.execute();
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+    assert_eq!(registers(&mut decoded), registers(&mut reference));
+    assert_eq!(decoded.flags, reference.flags);
+    assert_eq!(decoded.memory, reference.memory);
+    assert_eq!(decoded.usage, reference.usage);
+    assert_eq!(decoded.io, reference.io);
+    assert_eq!(decoded.heap, reference.heap);
+    assert_eq!(decoded.in_handler, reference.in_handler);
+    trap
+}
+
+#[test]
+fn fibonacci() {
+    let trap = differential(common::assemble(
+        "
+    load r0 30
+    load r1 1
+    load r2 2
+0:  sub r0 r0 1 U
+    swap r1 r2
+    add r2 r1 r2 U
+    print r2
+    cmp r0 0
+    jmpn: E 0
+    halt
+",
+    ));
+    assert!(trap.is_halt());
+}
+
+#[test]
+fn calls_and_memory() {
+    let trap = differential(common::assemble(
+        "
+table: .word 3 1 4 1 5
+bytes: .byte 1 2 3
+sum:
+    add r1 r1 [r0+table] U
+    add r0 r0 4 U
+    ret
+start:
+    call A sum
+    call A sum
+    call A sum
+    load8 r2 bytes+2
+    store16 bytes r1
+    load32 r3 bytes
+    push r3
+    pop r4
+    mov 3000 r4
+    halt
+.entry start
+",
+    ));
+    assert!(trap.is_halt(), "{:?}", trap);
+}
+
+#[test]
+fn traps_and_the_handler() {
+    for line in [
+        "div r0 1 0 U",
+        "sub r0 0 1 U",
+        "mul r0 65536 65536 U",
+        "load r0 [9000]",
+        "pop r0",
+        "push 2\nret",
+    ] {
+        let source = format!("handler: halt\nstart: {}\nhalt\n.entry start\n", line);
+        let trap = differential(common::assemble(&source));
+        assert!(!trap.is_halt(), "{}", line);
+        let mut interpreter = common::assemble(&source);
+        interpreter.set_trap_handler(Some(0));
+        assert!(differential(interpreter).is_halt());
+    }
+}
+
+#[test]
+fn faults_inside_the_handler_reach_the_host() {
+    let source = "
+handler:
+    load r2 r14
+    load r1 [70000]
+    jmp A r15+4
+start:
+    div r0 1 0 U
+    halt
+.entry start
+";
+    let mut interpreter = common::assemble(source);
+    interpreter.set_trap_handler(Some(0));
+    let trap = differential(interpreter);
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(70000));
+    assert_eq!(trap.pc, 4);
+}
+
+#[test]
+fn jumps_and_returns_inside_the_handler_do_not_leave_it() {
+    let source = "
+handler:
+    jmp: A fault
+check:
+    ret
+fault:
+    call A check
+    load r1 [70000]
+    jmp A r15+4
+start:
+    div r0 1 0 U
+    halt
+.entry start
+";
+    let mut interpreter = common::assemble(source);
+    interpreter.set_trap_handler(Some(0));
+    // a handler that left on its first jump would run for its own fault forever
+    interpreter.set_limits(Limits {
+        instructions: Some(100),
+        ..Limits::default()
+    });
+    let trap = differential(interpreter);
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(70000));
+    assert_eq!(trap.pc, 12);
+}
+
+#[test]
+fn writes_to_code_are_seen() {
+    let trap = differential(common::assemble(
+        "store8 8 0\nstore8 13 0\nload r0 1\nhalt\n",
+    ));
+    assert_eq!(trap.pc, 8);
+    assert_eq!(trap.kind, TrapKind::InvalidInstruction);
+}
+
+#[test]
+fn works_on_flat_memory() {
+    let mut reference = Interpreter::with_memory(FlatMemory::new(1 << 16));
+    reference
+        .load(
+            vec![],
+            vec![Instruction::Store(
+                Value::Direct(Operand::U32(1 << 16)),
+                Value::Direct(Operand::U32(1)),
+                Width::Byte,
+            )],
+            0,
+        )
+        .unwrap();
+    let mut decoded = reference.clone();
+    assert_eq!(Engine::new(&mut decoded).execute(), reference.execute());
+    assert_eq!(decoded, reference);
+}
+
+/// xorshift, good enough to pick instructions
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u32
+    }
+
+    fn below(&mut self, bound: u32) -> u32 {
+        self.next() % bound
+    }
+
+    fn limit(&mut self, bound: u32) -> Option<u32> {
+        match self.below(2) {
+            0 => Some(self.below(bound)),
+            _ => None,
+        }
+    }
+
+    fn register(&mut self) -> Register {
+        [
+            Register::R0,
+            Register::R1,
+            Register::R2,
+            Register::R3,
+            Register::StackPointer,
+        ][self.below(5) as usize]
+    }
+
+    fn operand(&mut self, adress: bool) -> Operand {
+        match self.below(3) {
+            0 => Operand::Register(self.register()),
+            _ if adress => Operand::U32(self.below(160)),
+            _ => Operand::U32(
+                [0, 1, 7, u32::MAX, i32::MAX as u32, 0x3f80_0000][self.below(6) as usize],
+            ),
+        }
+    }
+
+    fn value(&mut self, adress: bool) -> Value {
+        match self.below(5) {
+            0 => Value::Indirect(self.operand(true)),
+            1 => Value::OperationalDirect(self.operand(adress), self.operand(false)),
+            2 => Value::OperationalIndirect(self.operand(true), self.operand(false)),
+            _ => Value::Direct(self.operand(adress)),
+        }
+    }
+
+    fn flag(&mut self) -> Flag {
+        Flag::ALL[self.below(5) as usize]
+    }
+
+    fn instruction(&mut self, length: u32) -> Instruction {
+        let math_type = MathType::ALL[self.below(3) as usize];
+        let width = Width::ALL[self.below(3) as usize];
+        let target = Value::Direct(Operand::U32(4 * self.below(length + 1) + 2 * self.below(2)));
+        let (r, a, b) = (self.register(), self.value(false), self.value(false));
+        match self.below(17) {
+            0 => Instruction::Add(r, a, b, math_type),
+            1 => Instruction::Sub(r, a, b, math_type),
+            2 => Instruction::Mul(r, a, b, math_type),
+            3 => Instruction::Div(r, a, b, math_type),
+            4 => Instruction::Mod(r, a, b, math_type),
+            5 => [
+                Instruction::And(r, a, b),
+                Instruction::Or(r, a, b),
+                Instruction::Xor(r, a, b),
+                Instruction::Not(r, a),
+                Instruction::ShiftLeft(r, a, b),
+                Instruction::ShiftRight(r, a, b),
+            ][self.below(6) as usize],
+            6 => [
+                Instruction::Compare(a, b, math_type),
+                Instruction::Convert(r, a, math_type, MathType::ALL[self.below(3) as usize]),
+                Instruction::SignExtend(r, a, width),
+                Instruction::ZeroExtend(r, a, width),
+            ][self.below(4) as usize],
+            7 => [
+                Instruction::Jump(self.flag(), target),
+                Instruction::JumpNot(self.flag(), target),
+                Instruction::Call(self.flag(), target),
+                Instruction::CallNot(self.flag(), target),
+            ][self.below(4) as usize],
+            8 => Instruction::Ret,
+            9 => Instruction::Load(r, a),
+            10 => Instruction::Swap(r, self.register()),
+            11 => Instruction::LoadMemory(r, self.value(true), width),
+            12 => Instruction::Store(self.value(true), b, width),
+            13 => Instruction::Move(self.value(true), b),
+            14 => Instruction::Push(a),
+            15 => Instruction::SysCall(
+                [
+                    SysCall::ReadInt(r),
+                    SysCall::ReadChar(r),
+                    SysCall::ReadLine {
+                        length: r,
+                        start: self.value(true),
+                        max_len: Value::Direct(Operand::U32(self.below(8))),
+                    },
+                    SysCall::Alloc {
+                        pointer: r,
+                        size: a,
+                    },
+                    SysCall::Free(a),
+                ][self.below(5) as usize],
+            ),
+            _ => Instruction::Pop(r),
+        }
+    }
+}
+
+#[test]
+fn random_programs() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..2000 {
+        let length = 1 + random.below(40);
+        let mut instructions: Vec<Instruction> =
+            (0..length).map(|_| random.instruction(length)).collect();
+        instructions.push(Instruction::Halt);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_heap_checks(random.below(2) == 0);
+        interpreter.load(vec![], instructions, 0).unwrap();
+        if random.below(2) == 0 {
+            interpreter.set_trap_handler(Some(4 * length));
+        }
+        interpreter.set_limits(Limits {
+            instructions: Some(random.below(500) as u64),
+            memory: random.limit(16),
+            stack: random.limit(16),
+        });
+        differential(interpreter);
+    }
+}
@@ -0,0 +1,296 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::files::FAILED;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::Buffer;
+use plac::codegen::object::{decode_instruction, encode_instruction};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+fn load(source: &str, root: Option<&PathBuf>) -> Interpreter<BTreeMap<u32, MemoryNode>, Buffer> {
+    let mut interpreter = common::assemble(source).with_io(Buffer::with_input("typed\nrest"));
+    if let Some(root) = root {
+        interpreter.set_root(root).unwrap();
+    }
+    interpreter
+}
+
+/// what `Interpreter::execute` and `Engine` have to agree on
+fn agree(
+    decoded: &mut Interpreter<BTreeMap<u32, MemoryNode>, Buffer>,
+    reference: &mut Interpreter<BTreeMap<u32, MemoryNode>, Buffer>,
+) {
+    for register in Register::ALL {
+        assert_eq!(
+            decoded.get_register(&register),
+            reference.get_register(&register)
+        );
+    }
+    assert_eq!(decoded.memory, reference.memory);
+    assert_eq!(decoded.io, reference.io);
+    assert_eq!(decoded.files, reference.files);
+}
+
+/// an empty directory of its own for every test
+fn root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("plac-files-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// writes `text` to `name`, reads it back into `buffer` and prints it
+const ROUND_TRIP: &str = "
+name: .string \"out.txt\"
+text: .string \"hello file\"
+buffer: .zero 16
+    open r1 name name_len 1
+    write r2 r1 text text_len
+    close r3 r1
+    open r1 name name_len 0
+    read r4 r1 buffer 16
+    read r6 r1 buffer 16
+    close r3 r1
+    write r2 1 buffer r4
+    exit 7
+";
+
+#[test]
+fn files_round_trip() {
+    let root = root("round-trip");
+    let mut interpreter = load(ROUND_TRIP, Some(&root));
+    let trap = interpreter.execute();
+    assert_eq!(trap.exit_code(), Some(7));
+    assert_eq!(interpreter.io.text(), "hello file");
+    assert_eq!(*interpreter.get_register(&Register::R1), 3);
+    assert_eq!(*interpreter.get_register(&Register::R2), 10);
+    assert_eq!(*interpreter.get_register(&Register::R3), 0);
+    assert_eq!(*interpreter.get_register(&Register::R4), 10);
+    assert_eq!(*interpreter.get_register(&Register::R6), 0);
+    assert_eq!(interpreter.files.paths().count(), 0);
+    assert_eq!(
+        std::fs::read_to_string(root.join("out.txt")).unwrap(),
+        "hello file"
+    );
+
+    std::fs::remove_file(root.join("out.txt")).unwrap();
+    let mut decoded = load(ROUND_TRIP, Some(&root));
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+    agree(&mut decoded, &mut interpreter);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+fn open(root: Option<&PathBuf>, path: &str, mode: u32) -> u32 {
+    let source = format!(
+        "name: .string \"{}\"\n    open r1 name name_len {}\n    halt\n",
+        path, mode
+    );
+    let mut interpreter = load(&source, root);
+    assert!(interpreter.execute().is_halt());
+    *interpreter.get_register(&Register::R1)
+}
+
+#[test]
+fn paths_stay_inside_the_root() {
+    let root = root("sandbox");
+    std::fs::create_dir(root.join("inner")).unwrap();
+    std::fs::write(root.join("inner/file"), "x").unwrap();
+    assert_eq!(open(Some(&root), "inner/file", 0), 3);
+    assert_eq!(open(Some(&root), "./inner/file", 0), 3);
+    assert_eq!(open(Some(&root), "inner/new", 1), 3);
+    assert_eq!(open(Some(&root), "inner/../inner/file", 0), FAILED);
+    assert_eq!(open(Some(&root), "../escape", 1), FAILED);
+    assert_eq!(open(Some(&root), "/etc/passwd", 0), FAILED);
+    assert_eq!(open(Some(&root), "inner", 0), FAILED);
+    assert_eq!(open(Some(&root), "", 0), FAILED);
+    assert_eq!(open(Some(&root), "missing", 0), FAILED);
+    assert_eq!(open(Some(&root), "missing/file", 1), FAILED);
+    assert_eq!(open(Some(&root), "inner/file", 3), FAILED);
+    assert_eq!(open(None, "inner/file", 0), FAILED);
+
+    #[cfg(unix)]
+    {
+        let outside = self::root("sandbox-outside");
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("target"), root.join("dangling")).unwrap();
+        assert_eq!(open(Some(&root), "link/file", 1), FAILED);
+        assert_eq!(open(Some(&root), "dangling", 1), FAILED);
+        assert!(!outside.join("file").exists() && !outside.join("target").exists());
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn descriptors_below_three_are_the_io() {
+    let source = "
+buffer: .zero 8
+    read r1 0 buffer 8
+    write r2 2 buffer r1
+    read r3 0 buffer 2
+    write r4 1 buffer r3
+    read r6 1 buffer 2
+    write r7 0 buffer 2
+    close r8 1
+    halt
+";
+    let mut interpreter = load(source, None);
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(interpreter.io.text(), "typed\nre");
+    let registers = [(Register::R1, 6), (Register::R2, 6), (Register::R3, 2)];
+    let failed = [
+        (Register::R6, FAILED),
+        (Register::R7, FAILED),
+        (Register::R8, FAILED),
+    ];
+    for (register, value) in registers.into_iter().chain(failed) {
+        assert_eq!(
+            *interpreter.get_register(&register),
+            value,
+            "{:?}",
+            register
+        );
+    }
+
+    let mut decoded = load(source, None);
+    Engine::new(&mut decoded).execute();
+    agree(&mut decoded, &mut interpreter);
+}
+
+#[test]
+fn modes_decide_what_a_descriptor_allows() {
+    let root = root("modes");
+    std::fs::write(root.join("log"), "a").unwrap();
+    let source = "
+name: .string \"log\"
+text: .string \"bc\"
+    open r1 name name_len 2
+    write r2 r1 text text_len
+    read r3 r1 text 1
+    open r4 name name_len 0
+    write r6 r4 text 1
+    close r7 r1
+    close r7 r1
+    open r8 name name_len 0
+    halt
+";
+    let mut interpreter = load(source, Some(&root));
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(std::fs::read_to_string(root.join("log")).unwrap(), "abc");
+    assert_eq!(*interpreter.get_register(&Register::R2), 2);
+    assert_eq!(*interpreter.get_register(&Register::R3), FAILED);
+    assert_eq!(*interpreter.get_register(&Register::R4), 4);
+    assert_eq!(*interpreter.get_register(&Register::R6), FAILED);
+    assert_eq!(*interpreter.get_register(&Register::R7), FAILED);
+    // the lowest free descriptor is reused
+    assert_eq!(*interpreter.get_register(&Register::R8), 3);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn exit_halts_with_its_code() {
+    let mut interpreter = load("load r0 1\nexit r1\nhalt\n", None);
+    interpreter.set_register(Register::R1, 42);
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::Halt(42));
+    assert_eq!(trap.exit_code(), Some(42));
+    assert_eq!(trap.pc, 4);
+
+    // exit is not a fault, the trap handler does not see it
+    let mut interpreter = load("exit 3\nhalt\n", None);
+    interpreter.set_trap_handler(Some(4));
+    assert_eq!(interpreter.execute().exit_code(), Some(3));
+
+    let mut interpreter = load("div r0 1 0 U\n", None);
+    assert_eq!(interpreter.execute().exit_code(), None);
+}
+
+#[test]
+fn file_syscalls_round_trip() {
+    let register = |register| Value::Direct(Operand::Register(register));
+    for instruction in [
+        SysCall::Open {
+            fd: Register::R1,
+            path: Value::Indirect(Operand::U32(0xdead_beef)),
+            path_len: register(Register::R2),
+            mode: Value::Direct(Operand::U32(2)),
+        },
+        SysCall::Read {
+            count: Register::R3,
+            fd: register(Register::R1),
+            start: Value::OperationalIndirect(Operand::Register(Register::R4), Operand::U32(8)),
+            length: Value::Direct(Operand::U32(u32::MAX)),
+        },
+        SysCall::Write {
+            count: Register::R12,
+            fd: Value::Direct(Operand::U32(1)),
+            start: Value::OperationalDirect(Operand::Register(Register::R2), Operand::U32(4)),
+            length: register(Register::R3),
+        },
+        SysCall::Close {
+            result: Register::R0,
+            fd: register(Register::R1),
+        },
+        SysCall::Exit(register(Register::R9)),
+    ]
+    .map(Instruction::SysCall)
+    {
+        let text = String::from(instruction);
+        assert_eq!(Instruction::try_from(text), Ok(instruction));
+        assert_eq!(
+            decode_instruction(&encode_instruction(&instruction)),
+            Ok(instruction)
+        );
+    }
+}
+
+#[test]
+fn a_read_that_faults_leaves_the_file_where_it_was() {
+    let root = root("faulting-read");
+    std::fs::write(root.join("in.txt"), "first").unwrap();
+    let source = "
+name: .string \"in.txt\"
+    open r1 name name_len 0
+    alloc r2 8
+    free r2
+    read r3 r1 r2 8
+    halt
+";
+    let mut interpreter = load(source, Some(&root));
+    interpreter.set_heap_checks(true);
+    let trap = interpreter.execute();
+    let block = *interpreter.get_register(&Register::R2);
+    assert_eq!(trap.kind, TrapKind::UseAfterFree(block));
+
+    interpreter.set_register(Register::R2, 5000);
+    interpreter.set_register(Register::ProgramCounter, trap.pc);
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R3), 5);
+    assert_eq!(interpreter.read_bytes(5000, 5), Ok(b"first".to_vec()));
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn only_regular_files_are_opened_and_links_are_not_followed() {
+    let root = root("regular");
+    std::fs::write(root.join("file"), "kept").unwrap();
+    std::os::unix::fs::symlink(root.join("file"), root.join("link")).unwrap();
+    std::fs::create_dir(root.join("inner")).unwrap();
+    std::os::unix::fs::symlink(root.join("inner"), root.join("directory")).unwrap();
+    let fifo = std::ffi::CString::new(root.join("fifo").into_os_string().into_encoded_bytes());
+    assert_eq!(unsafe { libc::mkfifo(fifo.unwrap().as_ptr(), 0o600) }, 0);
+    for mode in [0, 1, 2] {
+        assert_eq!(open(Some(&root), "link", mode), FAILED);
+        assert_eq!(open(Some(&root), "directory/new", mode), FAILED);
+        assert_eq!(open(Some(&root), "fifo", mode), FAILED);
+        assert_eq!(open(Some(&root), "inner", mode), FAILED);
+    }
+    assert_eq!(std::fs::read_to_string(root.join("file")).unwrap(), "kept");
+    assert!(!root.join("inner/new").exists());
+    assert_eq!(open(Some(&root), "file", 1), 3);
+    assert_eq!(std::fs::read_to_string(root.join("file")).unwrap(), "");
+    std::fs::remove_dir_all(root).unwrap();
+}
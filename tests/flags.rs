@@ -0,0 +1,126 @@
+mod common;
+
+use plac::codegen::asm::*;
+use std::collections::BTreeSet;
+
+fn flags(lines: &[&str]) -> BTreeSet<Flag> {
+    let lines: Vec<&str> = lines.iter().copied().chain(["halt"]).collect();
+    let mut interpreter = common::compile(&lines);
+    interpreter.execute();
+    interpreter.flags.remove(&Flag::Always);
+    interpreter.flags
+}
+
+fn set<const N: usize>(flags: [Flag; N]) -> BTreeSet<Flag> {
+    flags.into_iter().collect()
+}
+
+fn float(value: f32) -> String {
+    value.to_bits().to_string()
+}
+
+#[test]
+fn cmp_replaces_comparison_flags() {
+    assert_eq!(flags(&["cmp 2 1"]), set([Flag::Greater]));
+    assert_eq!(flags(&["cmp 1 2"]), set([Flag::Lesser]));
+    assert_eq!(flags(&["cmp 1 1"]), set([Flag::Equal]));
+    assert_eq!(flags(&["cmp 2 1", "cmp 1 2"]), set([Flag::Lesser]));
+    assert_eq!(flags(&["cmp 1 2", "cmp 1 1"]), set([Flag::Equal]));
+}
+
+#[test]
+fn cmp_keeps_overflow() {
+    assert_eq!(
+        flags(&["add r0 4294967295 1 U", "cmp 1 2"]),
+        set([Flag::Lesser, Flag::Overflow])
+    );
+}
+
+#[test]
+fn add() {
+    assert_eq!(flags(&["add r0 1 2 U"]), set([Flag::Greater]));
+    assert_eq!(flags(&["add r0 0 0 U"]), set([Flag::Equal]));
+    assert_eq!(
+        flags(&["add r0 4294967295 1 U"]),
+        set([Flag::Equal, Flag::Overflow])
+    );
+    assert_eq!(flags(&["add r0 4294967295 0 S"]), set([Flag::Lesser]));
+    assert_eq!(
+        flags(&["add r0 2147483647 1 S"]),
+        set([Flag::Lesser, Flag::Overflow])
+    );
+}
+
+#[test]
+fn sub() {
+    assert_eq!(flags(&["sub r0 2 1 U"]), set([Flag::Greater]));
+    assert_eq!(flags(&["sub r0 2 2 U"]), set([Flag::Equal]));
+    assert_eq!(
+        flags(&["cmp 1 1", "sub r0 1 2 U"]),
+        set([Flag::Equal, Flag::Overflow])
+    );
+    assert_eq!(flags(&["sub r0 1 2 S"]), set([Flag::Lesser]));
+    assert_eq!(
+        flags(&["sub r0 2147483648 1 S"]),
+        set([Flag::Greater, Flag::Overflow])
+    );
+}
+
+#[test]
+fn mul() {
+    assert_eq!(flags(&["mul r0 3 4 U"]), set([Flag::Greater]));
+    assert_eq!(flags(&["mul r0 3 0 U"]), set([Flag::Equal]));
+    assert_eq!(flags(&["mul r0 4294967295 2 S"]), set([Flag::Lesser]));
+    assert_eq!(
+        flags(&["mul r0 65536 65536 S"]),
+        set([Flag::Equal, Flag::Overflow])
+    );
+}
+
+#[test]
+fn div_and_mod() {
+    assert_eq!(flags(&["div r0 7 2 U"]), set([Flag::Greater]));
+    assert_eq!(flags(&["div r0 1 2 U"]), set([Flag::Equal]));
+    assert_eq!(flags(&["div r0 4294967290 2 S"]), set([Flag::Lesser]));
+    assert_eq!(
+        flags(&["div r0 2147483648 4294967295 S"]),
+        set([Flag::Lesser, Flag::Overflow])
+    );
+    assert_eq!(flags(&["mod r0 7 2 U"]), set([Flag::Greater]));
+    assert_eq!(flags(&["mod r0 8 2 U"]), set([Flag::Equal]));
+    assert_eq!(flags(&["mod r0 4294967289 2 S"]), set([Flag::Lesser]));
+}
+
+#[test]
+fn float_arithmetic() {
+    let add = format!("add r0 {} {} F", float(1.5), float(-2.0));
+    assert_eq!(flags(&[&add]), set([Flag::Lesser]));
+    let sub = format!("sub r0 {} {} F", float(1.5), float(1.5));
+    assert_eq!(flags(&[&sub]), set([Flag::Equal]));
+    let mul = format!("mul r0 {} {} F", float(f32::MAX), float(2.0));
+    assert_eq!(flags(&[&mul]), set([Flag::Greater, Flag::Overflow]));
+    let div = format!("div r0 {} 0 F", float(0.0));
+    assert_eq!(flags(&["cmp 1 1", &div]), set([]));
+}
+
+#[test]
+fn bitwise() {
+    assert_eq!(flags(&["and r0 6 3"]), set([Flag::Greater]));
+    assert_eq!(flags(&["and r0 4 3"]), set([Flag::Equal]));
+    assert_eq!(flags(&["or r0 0 0"]), set([Flag::Equal]));
+    assert_eq!(flags(&["xor r0 5 5"]), set([Flag::Equal]));
+    assert_eq!(flags(&["not r0 4294967295"]), set([Flag::Equal]));
+    assert_eq!(flags(&["shl r0 1 31"]), set([Flag::Greater]));
+    assert_eq!(flags(&["shr r0 1 1"]), set([Flag::Equal]));
+    assert_eq!(
+        flags(&["add r0 4294967295 1 U", "or r0 1 0"]),
+        set([Flag::Greater])
+    );
+}
+
+#[test]
+fn other_instructions_keep_flags() {
+    for line in ["load r0 0", "mov 100 0", "swap r0 r1", "jmp G 0", "print 1"] {
+        assert_eq!(flags(&["cmp 1 2", line]), set([Flag::Lesser]));
+    }
+}
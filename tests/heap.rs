@@ -0,0 +1,193 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::heap::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::object::{decode_instruction, encode_instruction};
+
+fn load(lines: &[&str], checked: bool) -> Interpreter {
+    let mut interpreter = common::compile(lines);
+    interpreter.set_heap_checks(checked);
+    interpreter
+}
+
+/// runs `lines` with both `Interpreter::execute` and `Engine`, which have to agree
+fn run(lines: &[&str], checked: bool) -> (Trap, Interpreter) {
+    let mut interpreter = load(lines, checked);
+    let trap = interpreter.execute();
+    let mut decoded = load(lines, checked);
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+    assert_eq!(decoded.heap, interpreter.heap);
+    assert_eq!(decoded.memory, interpreter.memory);
+    for register in Register::ALL {
+        assert_eq!(
+            decoded.get_register(&register),
+            interpreter.get_register(&register)
+        );
+    }
+    (trap, interpreter)
+}
+
+fn register(interpreter: &mut Interpreter, register: Register) -> u32 {
+    *interpreter.get_register(&register)
+}
+
+#[test]
+fn blocks_lie_above_the_stack() {
+    let (trap, mut interpreter) = run(
+        &[
+            "alloc r1 5",
+            "alloc r2 8",
+            "alloc r3 0",
+            "store32 r1 1234",
+            "store32 r2 r1",
+            "load32 r4 r2",
+            "load32 r0 r4",
+            "halt",
+        ],
+        false,
+    );
+    assert_eq!(trap.kind, TrapKind::Halt(1234));
+    let start = interpreter.stack.end;
+    assert_eq!(interpreter.heap.range, start..start + HEAP_SIZE);
+    assert_eq!(start % HEAP_ALIGNMENT, 0);
+    assert_eq!(register(&mut interpreter, Register::R1), start);
+    assert_eq!(register(&mut interpreter, Register::R2), start + 8);
+    assert_eq!(register(&mut interpreter, Register::R3), start + 16);
+    let blocks: Vec<_> = interpreter.heap.blocks().collect();
+    assert_eq!(blocks, [(start, 8), (start + 8, 8), (start + 16, 8)]);
+}
+
+#[test]
+fn freed_blocks_are_reused_and_merged() {
+    let (_, mut interpreter) = run(
+        &[
+            "alloc r1 8",
+            "alloc r2 8",
+            "alloc r3 8",
+            "free r1",
+            "alloc r4 4",
+            "free r4",
+            "free r2",
+            "alloc r6 16",
+            "free 0",
+            "free 12345",
+            "free r6",
+            "free r6",
+            "halt",
+        ],
+        false,
+    );
+    let start = interpreter.stack.end;
+    assert_eq!(register(&mut interpreter, Register::R4), start);
+    assert_eq!(register(&mut interpreter, Register::R6), start);
+    let blocks: Vec<_> = interpreter.heap.blocks().collect();
+    assert_eq!(blocks, [(start + 16, 8)]);
+}
+
+#[test]
+fn alloc_returns_zero_when_the_heap_is_full() {
+    let mut heap = Heap::new(64..64 + HEAP_SIZE);
+    assert_eq!(heap.alloc(HEAP_SIZE + 1), None);
+    assert_eq!(heap.alloc(u32::MAX), None);
+    assert_eq!(heap.alloc(HEAP_SIZE), Some(64));
+    assert_eq!(heap.alloc(1), None);
+    heap.free(64).unwrap();
+    assert_eq!(heap.alloc(HEAP_SIZE - 8), Some(64));
+    assert_eq!(heap.alloc(8), Some(64 + HEAP_SIZE - 8));
+
+    let (_, mut interpreter) = run(&["alloc r1 4294967295", "halt"], false);
+    assert_eq!(register(&mut interpreter, Register::R1), 0);
+}
+
+/// a program, the trap it ends with and the offset into the heap the trap carries
+type Misuse = (&'static [&'static str], fn(u32) -> TrapKind, u32);
+
+#[test]
+fn checked_heaps_catch_misuse() {
+    let cases: [Misuse; 5] = [
+        (
+            &["alloc r1 8", "free r1", "free r1"],
+            TrapKind::DoubleFree,
+            0,
+        ),
+        (
+            &["alloc r1 8", "add r2 r1 4 U", "free r2"],
+            TrapKind::InvalidFree,
+            4,
+        ),
+        (
+            &["alloc r1 8", "store8 r1 7", "free r1", "load8 r2 r1"],
+            TrapKind::UseAfterFree,
+            0,
+        ),
+        (
+            &["alloc r1 8", "free r1", "add r2 r1 6 U", "store16 r2 1"],
+            TrapKind::UseAfterFree,
+            6,
+        ),
+        (
+            &["alloc r1 8", "store8 r1 1", "free r1", "printstr r1 1"],
+            TrapKind::UseAfterFree,
+            0,
+        ),
+    ];
+    for (lines, kind, offset) in cases {
+        let (trap, interpreter) = run(lines, true);
+        assert_eq!(trap.kind, kind(interpreter.heap.range.start + offset));
+        assert_eq!(trap.pc, 1 + 4 * (lines.len() as u32 - 1));
+
+        let (trap, _) = run(lines, false);
+        assert_eq!(trap.kind, TrapKind::InvalidInstruction);
+    }
+}
+
+#[test]
+fn checked_heaps_do_not_reuse_blocks() {
+    let lines = [
+        "alloc r1 8",
+        "free r1",
+        "alloc r2 8",
+        "load32 r3 r1",
+        "halt",
+    ];
+    let (trap, mut interpreter) = run(&lines, true);
+    assert_eq!(trap.kind, TrapKind::UseAfterFree(interpreter.stack.end));
+    assert_eq!(
+        register(&mut interpreter, Register::R2),
+        interpreter.stack.end + 8
+    );
+}
+
+#[test]
+fn misuse_goes_to_the_trap_handler() {
+    let mut interpreter = load(&["alloc r1 8", "free r1", "free r1", "halt"], true);
+    interpreter.set_trap_handler(Some(13));
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(
+        register(&mut interpreter, TRAP_CODE),
+        TrapKind::DoubleFree(0).code()
+    );
+    assert_eq!(register(&mut interpreter, TRAP_PC), 9);
+}
+
+#[test]
+fn heap_syscalls_round_trip() {
+    for instruction in [
+        SysCall::Alloc {
+            pointer: Register::R3,
+            size: Value::OperationalIndirect(Operand::Register(Register::R1), Operand::U32(4)),
+        },
+        SysCall::Free(Value::Direct(Operand::Register(Register::R12))),
+    ]
+    .map(Instruction::SysCall)
+    {
+        let text = String::from(instruction);
+        assert_eq!(Instruction::try_from(text), Ok(instruction));
+        assert_eq!(
+            decode_instruction(&encode_instruction(&instruction)),
+            Ok(instruction)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::history::History;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::Buffer;
+use std::collections::BTreeMap;
+
+type Buffered = Interpreter<BTreeMap<u32, MemoryNode>, Buffer>;
+
+fn load(source: &str, capacity: usize) -> Buffered {
+    let mut interpreter = common::assemble(source).with_io(Buffer::with_input("abc\n"));
+    interpreter.set_history(Some(History::new(capacity)));
+    interpreter
+}
+
+/// everything a delta restores
+fn state(interpreter: &Buffered) -> impl PartialEq + std::fmt::Debug {
+    (
+        interpreter.registers.clone(),
+        interpreter.flags.clone(),
+        interpreter.memory.clone(),
+        interpreter.usage,
+        interpreter.heap.clone(),
+    )
+}
+
+/// touches registers, flags, memory, the stack, the heap and the input, and faults once
+const PROGRAM: &str = "
+line: .zero 4
+total: .zero 4
+twice:
+    add r0 r0 r0 U
+    ret
+handler:
+    load r9 1
+    add r15 r15 4 U
+    push r15
+    ret
+main:
+    readline r1 line 4
+    alloc r2 8
+    load r0 0
+    load r3 5
+next:
+    add r0 r0 r3 U
+    call A twice
+    store32 total r0
+    sub r3 r3 1 U
+    cmp r3 0
+    jmpn: E next
+    store32 r2 r0
+    free r2
+    div r4 1 0 U
+    printc 33
+    halt
+.entry main
+";
+
+/// the adresses of `handler` and `main` in `PROGRAM`
+const HANDLER: u32 = 16;
+const MAIN: u32 = 32;
+
+fn run(source: &str, capacity: usize) -> (Buffered, Trap) {
+    let mut interpreter = load(source, capacity);
+    interpreter.set_trap_handler(Some(HANDLER));
+    let trap = interpreter.execute();
+    (interpreter, trap)
+}
+
+#[test]
+fn stepping_back_restores_every_earlier_state() {
+    let mut interpreter = load(PROGRAM, usize::MAX);
+    interpreter.set_trap_handler(Some(HANDLER));
+    let mut states = vec![state(&interpreter)];
+    let trap = loop {
+        match interpreter.tick() {
+            Ok(()) => states.push(state(&interpreter)),
+            Err(trap) => break trap,
+        }
+    };
+    assert_eq!(trap.kind, TrapKind::Halt(258));
+    assert_eq!(*interpreter.get_register(&Register::R9), 1);
+    assert_eq!(interpreter.io.text(), "!");
+    let end = state(&interpreter);
+    let ticks = interpreter.history.as_ref().unwrap().past().count();
+    assert_eq!(ticks, states.len());
+
+    // the halt advanced PC, stepping back over it first
+    assert!(interpreter.step_back());
+    for expected in states.iter().rev() {
+        assert_eq!(&state(&interpreter), expected);
+        interpreter.step_back();
+    }
+    assert!(!interpreter.step_back());
+    assert_eq!(&state(&interpreter), &states[0]);
+    assert_eq!(interpreter.history.as_ref().unwrap().future(), ticks);
+
+    for expected in &states[1..] {
+        assert_eq!(interpreter.replay(), Some(Ok(())));
+        assert_eq!(&state(&interpreter), expected);
+    }
+    assert_eq!(interpreter.replay(), Some(Err(trap)));
+    assert_eq!(interpreter.replay(), None);
+    assert_eq!(state(&interpreter), end);
+    // replaying neither reads nor writes again
+    assert_eq!(interpreter.io.text(), "!");
+    assert_eq!(interpreter.io.position, 4);
+}
+
+#[test]
+fn execution_continues_after_the_replayed_ticks() {
+    let (mut reference, trap) = run(PROGRAM, usize::MAX);
+    let mut interpreter = load(PROGRAM, usize::MAX);
+    interpreter.set_trap_handler(Some(HANDLER));
+    for _ in 0..40 {
+        interpreter.tick().unwrap();
+    }
+    for _ in 0..25 {
+        interpreter.step_back();
+    }
+    assert_eq!(interpreter.execute(), trap);
+    assert_eq!(state(&interpreter), state(&reference));
+    assert_eq!(interpreter.io, reference.io);
+    assert!(reference.step_back());
+}
+
+#[test]
+fn the_history_keeps_the_last_ticks() {
+    let (mut interpreter, _) = run(PROGRAM, 3);
+    assert_eq!(interpreter.history.as_ref().unwrap().past().count(), 3);
+    let pcs: Vec<u32> = interpreter
+        .history
+        .as_ref()
+        .unwrap()
+        .past()
+        .map(|delta| delta.pc)
+        .collect();
+    assert_eq!(pcs, [HANDLER + 12, MAIN + 52, MAIN + 56]);
+    assert!(interpreter.step_back() && interpreter.step_back() && interpreter.step_back());
+    assert!(!interpreter.step_back());
+    assert_eq!(
+        *interpreter.get_register(&Register::ProgramCounter),
+        HANDLER + 12
+    );
+
+    let (mut interpreter, _) = run(PROGRAM, 0);
+    assert!(!interpreter.step_back());
+    let mut interpreter = load(PROGRAM, 10);
+    interpreter.set_history(None);
+    assert!(!interpreter.step_back());
+    assert_eq!(interpreter.replay(), None);
+}
+
+#[test]
+fn rewinding_stops_before_the_last_write() {
+    let (mut interpreter, _) = run(PROGRAM, usize::MAX);
+    // `total` is written last in the final iteration of the loop, with 258
+    assert_eq!(interpreter.rewind_to_write(6), Some(13));
+    assert_eq!(
+        *interpreter.get_register(&Register::ProgramCounter),
+        MAIN + 24
+    );
+    assert_eq!(interpreter.read(4, Width::Word), Ok(128));
+    assert_eq!(*interpreter.get_register(&Register::R0), 258);
+    assert_eq!(interpreter.rewind_to_write(4), Some(8));
+    assert_eq!(interpreter.read(4, Width::Word), Ok(62));
+    // the line was read once, before the history runs out
+    assert_eq!(interpreter.read(0, Width::Byte), Ok(b'a' as u32));
+    assert!(interpreter.rewind_to_write(0).is_some());
+    assert_eq!(interpreter.read(0, Width::Byte), Ok(0));
+    assert_eq!(*interpreter.get_register(&Register::ProgramCounter), MAIN);
+    assert_eq!(interpreter.rewind_to_write(0), None);
+    assert_eq!(interpreter.rewind_to_write(9000), None);
+}
+
+#[test]
+fn the_engine_clears_the_history() {
+    let mut interpreter = load(PROGRAM, usize::MAX);
+    interpreter.set_trap_handler(Some(HANDLER));
+    for _ in 0..10 {
+        interpreter.tick().unwrap();
+    }
+    interpreter.step_back();
+    let trap = Engine::new(&mut interpreter).execute();
+    assert_eq!(trap.kind, TrapKind::Halt(258));
+    let history = interpreter.history.as_ref().unwrap();
+    assert_eq!((history.past().count(), history.future()), (0, 0));
+    assert!(!interpreter.step_back());
+}
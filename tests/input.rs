@@ -0,0 +1,198 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::Buffer;
+use plac::codegen::memory::FlatMemory;
+use plac::codegen::object::{decode_instruction, encode_instruction};
+
+fn load(
+    source: &str,
+    input: &str,
+) -> Interpreter<std::collections::BTreeMap<u32, MemoryNode>, Buffer> {
+    common::assemble(source).with_io(Buffer::with_input(input))
+}
+
+/// the output of `source` given `input`, the same with both engines
+fn run(source: &str, input: &str) -> String {
+    let mut interpreter = load(source, input);
+    let trap = interpreter.execute();
+    assert!(trap.is_halt(), "{:?}", trap);
+    let mut decoded = load(source, input);
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+    assert_eq!(decoded.io, interpreter.io);
+    interpreter.io.text()
+}
+
+const ECHO: &str = "
+buffer: .zero 8
+next:
+    readline r1 buffer 8
+    jmp: I end
+    printc 91
+    printstr buffer r1
+    printc 93
+    jmp: A next
+end:
+    halt
+.entry next
+";
+
+const SUM: &str = "
+    load r0 0
+next:
+    readint r1
+    jmp: I end
+    jmp: O end
+    add r0 r0 r1 S
+    jmp: A next
+end:
+    print r0
+    halt
+";
+
+const COUNT: &str = "
+    load r0 0
+next:
+    readc r1
+    jmp: I end
+    cmp r1 10
+    jmpn: E next
+    add r0 r0 1 U
+    jmp: A next
+end:
+    print r0
+    halt
+";
+
+#[test]
+fn golden() {
+    for (source, input, output) in [
+        (ECHO, "", ""),
+        (ECHO, "hi\n\nthere", "[hi][][there]"),
+        (ECHO, "a longer line\n", "[a longer][ line]"),
+        (ECHO, "exactly8\nx\n", "[exactly8][][x]"),
+        (SUM, "1 2 3", "6\n"),
+        (SUM, "  -5\n\t+12\n", "7\n"),
+        (SUM, "4 x 5", "4\n"),
+        (SUM, "", "0\n"),
+        (COUNT, "a\nb\n\nc", "3\n"),
+        (COUNT, "", "0\n"),
+    ] {
+        assert_eq!(run(source, input), output, "{:?}", input);
+    }
+}
+
+fn read_int(input: &str) -> (u32, bool, bool, usize) {
+    let mut interpreter = load("readint r0\nhalt\n", input);
+    interpreter.execute();
+    (
+        *interpreter.get_register(&Register::R0),
+        interpreter.flags.contains(&Flag::Overflow),
+        interpreter.flags.contains(&Flag::EndOfInput),
+        interpreter.io.position,
+    )
+}
+
+#[test]
+fn read_int_flags_and_wrapping() {
+    assert_eq!(read_int("42 "), (42, false, false, 2));
+    assert_eq!(read_int("-1"), (u32::MAX, false, false, 2));
+    assert_eq!(read_int("4294967295"), (u32::MAX, false, false, 10));
+    assert_eq!(read_int("4294967296"), (0, true, false, 10));
+    assert_eq!(read_int("-2147483648"), (1 << 31, false, false, 11));
+    assert_eq!(read_int("-2147483649"), (0x7fff_ffff, true, false, 11));
+    assert_eq!(read_int(" x"), (0, true, false, 1));
+    assert_eq!(read_int(" \n "), (0, false, true, 3));
+}
+
+#[test]
+fn read_char() {
+    let mut interpreter = load("readc r0\nreadc r1\nreadc r2\nhalt\n", "é");
+    interpreter.execute();
+    assert_eq!(*interpreter.get_register(&Register::R0), 0xc3);
+    assert_eq!(*interpreter.get_register(&Register::R1), 0xa9);
+    assert_eq!(*interpreter.get_register(&Register::R2), 0);
+    assert!(interpreter.flags.contains(&Flag::EndOfInput));
+}
+
+#[test]
+fn read_syscalls_round_trip() {
+    for instruction in [
+        SysCall::ReadInt(Register::R3),
+        SysCall::ReadChar(Register::R12),
+        SysCall::ReadLine {
+            length: Register::R1,
+            start: Value::OperationalDirect(Operand::Register(Register::R2), Operand::U32(4)),
+            max_len: Value::Direct(Operand::U32(80)),
+        },
+    ]
+    .map(Instruction::SysCall)
+    {
+        let text = String::from(instruction);
+        assert_eq!(Instruction::try_from(text), Ok(instruction));
+        assert_eq!(
+            decode_instruction(&encode_instruction(&instruction)),
+            Ok(instruction)
+        );
+    }
+    assert_eq!(
+        String::from(Instruction::SysCall(SysCall::ReadLine {
+            length: Register::R1,
+            start: Value::Direct(Operand::U32(100)),
+            max_len: Value::Direct(Operand::U32(8)),
+        })),
+        "readline r1 100 8"
+    );
+    assert_eq!(Flag::try_from("I".to_string()), Ok(Flag::EndOfInput));
+}
+
+#[test]
+fn a_line_is_only_taken_once_it_can_be_stored() {
+    for decoded in [false, true] {
+        let mut interpreter = load("readline r1 5000 6\nhalt\n", "abcdef\n");
+        interpreter.limits.memory = Some(3);
+        let execute = |interpreter: &mut Interpreter<_, _>| match decoded {
+            true => Engine::new(interpreter).execute(),
+            false => interpreter.execute(),
+        };
+        let trap = execute(&mut interpreter);
+        assert!(
+            matches!(trap.kind, TrapKind::Exhausted(Resource::Memory, _)),
+            "{:?}",
+            trap
+        );
+        assert_eq!(interpreter.io.position, 0);
+        interpreter.limits.memory = None;
+        assert!(execute(&mut interpreter).is_halt());
+        assert_eq!(*interpreter.get_register(&Register::R1), 6);
+        assert_eq!(interpreter.read_bytes(5000, 6), Ok(b"abcdef".to_vec()));
+    }
+}
+
+#[test]
+fn faulting_reads_neither_take_input_nor_write() {
+    for read in ["readline r1 r2 4", "read r1 0 r2 4"] {
+        let source = format!("alloc r2 8\nfree r2\n{}\nhalt\n", read);
+        let mut interpreter = load(&source, "abc\n");
+        interpreter.set_heap_checks(true);
+        let trap = interpreter.execute();
+        let block = *interpreter.get_register(&Register::R2);
+        assert_eq!(trap.kind, TrapKind::UseAfterFree(block), "{}", read);
+        assert_eq!(interpreter.io.position, 0, "{}", read);
+
+        let mut interpreter =
+            Interpreter::with_memory(FlatMemory::new(4096)).with_io(Buffer::with_input("abc\n"));
+        let source = read.replace("r2", "4094");
+        interpreter
+            .load_object(common::object(&format!("{}\nhalt\n", source)))
+            .unwrap();
+        assert_eq!(interpreter.execute().kind, TrapKind::OutOfMemory(4096));
+        assert_eq!(interpreter.io.position, 0, "{}", read);
+        assert_eq!(
+            interpreter.read(4094, Width::Byte),
+            Err(TrapKind::UninitialisedRead(4094))
+        );
+    }
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::interpreter::*;
+
+fn run(lines: &[&str]) -> (Interpreter, Trap) {
+    let mut interpreter = common::compile(lines);
+    let trap = interpreter.execute();
+    (interpreter, trap)
+}
+
+#[test]
+fn halt_carries_exit_code() {
+    let (_, trap) = run(&["load r0 42", "halt"]);
+    assert_eq!(
+        trap,
+        Trap {
+            pc: 5,
+            instruction: Some(Instruction::Halt),
+            kind: TrapKind::Halt(42),
+        }
+    );
+    assert!(trap.is_halt());
+}
+
+#[test]
+fn division_by_zero_traps() {
+    for line in [
+        "div r0 7 0 U",
+        "mod r0 7 0 U",
+        "div r0 7 0 S",
+        "mod r0 7 0 S",
+    ] {
+        let (_, trap) = run(&["load r1 1", line, "halt"]);
+        assert_eq!(trap.pc, 5);
+        assert_eq!(trap.instruction, Some(line.to_string().try_into().unwrap()));
+        assert_eq!(trap.kind, TrapKind::DivisionByZero);
+    }
+}
+
+#[test]
+fn unsigned_underflow_traps() {
+    let (mut interpreter, trap) = run(&["load r0 1", "sub r0 r0 2 U", "halt"]);
+    assert_eq!(trap.kind, TrapKind::Underflow);
+    assert_eq!(trap.pc, 5);
+    assert_eq!(*interpreter.get_register(&Register::R0), 1);
+}
+
+#[test]
+fn running_off_the_code_traps() {
+    let (_, trap) = run(&["load r0 1"]);
+    assert_eq!(
+        trap,
+        Trap {
+            pc: 5,
+            instruction: None,
+            kind: TrapKind::InvalidInstruction,
+        }
+    );
+}
+
+#[test]
+fn uninitialised_read_traps() {
+    let (_, trap) = run(&["load r0 [2000]", "halt"]);
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(2000));
+    assert_eq!(trap.pc, 1);
+}
+
+#[test]
+fn unsigned_overflow_traps_and_sets_flag() {
+    let (interpreter, trap) = run(&["mul r0 65536 65536 U", "halt"]);
+    assert_eq!(trap.kind, TrapKind::Overflow);
+    assert!(interpreter.flags.contains(&Flag::Overflow));
+
+    let (interpreter, trap) = run(&["sub r0 0 1 U", "halt"]);
+    assert_eq!(trap.kind, TrapKind::Underflow);
+    assert!(interpreter.flags.contains(&Flag::Overflow));
+
+    let (interpreter, trap) = run(&["mul r0 65536 65535 U", "halt"]);
+    assert!(trap.is_halt());
+    assert!(!interpreter.flags.contains(&Flag::Overflow));
+}
+
+#[test]
+fn wide_shifts_do_not_panic() {
+    let (mut interpreter, trap) = run(&["shl r0 1 32", "shr r1 1 40", "halt"]);
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 0);
+    assert_eq!(*interpreter.get_register(&Register::R1), 0);
+}
+
+#[test]
+fn trap_handler_can_resume() {
+    let mut interpreter = common::compile(&[
+        "load r0 0",
+        "div r1 7 r0 U",
+        "add r3 r3 1 U",
+        "halt",
+        "load r2 r14",
+        "jmp A r15+4",
+    ]);
+    interpreter.set_trap_handler(Some(17));
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::Halt(0));
+    assert_eq!(*interpreter.get_register(&Register::R2), 2);
+    assert_eq!(*interpreter.get_register(&TRAP_PC), 5);
+    assert_eq!(*interpreter.get_register(&Register::R3), 1);
+}
+
+#[test]
+fn faults_in_the_handler_entry_reach_the_host() {
+    let mut interpreter = common::compile(&["div r0 1 0 U", "halt"]);
+    interpreter.set_trap_handler(Some(100));
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::InvalidInstruction);
+    assert_eq!(trap.pc, 100);
+    assert_eq!(*interpreter.get_register(&TRAP_PC), 1);
+}
+
+#[test]
+fn faults_inside_the_handler_reach_the_host() {
+    let mut interpreter = common::compile(&[
+        "div r0 1 0 U",
+        "halt",
+        "load r2 r14",
+        "load r1 [70000]",
+        "jmp A r15+4",
+    ]);
+    interpreter.set_trap_handler(Some(9));
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(70000));
+    assert_eq!(trap.pc, 13);
+    assert_eq!(*interpreter.get_register(&TRAP_PC), 1);
+    assert_eq!(interpreter.usage.instructions, 3);
+}
+
+#[test]
+fn jumps_and_returns_inside_the_handler_do_not_leave_it() {
+    let mut interpreter = common::compile(&[
+        "div r0 1 0 U",
+        "halt",
+        "jmp A 13",
+        "call A 25",
+        "load r1 [70000]",
+        "jmp A r15+4",
+        "ret",
+    ]);
+    interpreter.set_trap_handler(Some(9));
+    // a handler that left on its first jump would run for its own fault forever
+    interpreter.set_limits(Limits {
+        instructions: Some(100),
+        ..Limits::default()
+    });
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(70000));
+    assert_eq!(trap.pc, 17);
+    assert_eq!(*interpreter.get_register(&TRAP_PC), 1);
+    assert_eq!(interpreter.usage.instructions, 5);
+}
+
+#[test]
+fn the_handler_is_entered_again_after_it_resumed() {
+    let mut interpreter = common::compile(&[
+        "div r0 1 0 U",
+        "div r0 2 0 U",
+        "halt",
+        "add r3 r3 1 U",
+        "jmp A r15+4",
+    ]);
+    interpreter.set_trap_handler(Some(13));
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::Halt(0));
+    assert_eq!(*interpreter.get_register(&Register::R3), 2);
+}
+
+#[test]
+fn indexed_reads_read_the_word_at_the_sum() {
+    // `[r1+4]` is the word at r1 + 4, not the word at r1 plus 4
+    let (mut interpreter, trap) = run(&[
+        "mov 2000 40",
+        "mov 2004 7",
+        "load r1 2000",
+        "load r0 [r1+4]",
+        "load r2 [r1]",
+        "add r2 r2 4 U",
+        "halt",
+    ]);
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 7);
+    assert_eq!(*interpreter.get_register(&Register::R2), 44);
+}
+
+#[test]
+fn indexed_reads_add_before_reading() {
+    let (mut interpreter, trap) = run(&["mov 2001 7", "load r1 2000", "load r0 [r1+1]", "halt"]);
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 7);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::*;
+
+const HELLO: &str = "
+message: .string \"hé\\n\"
+    print 42
+    printc 33
+    printc 10
+    printstr message message_len
+    halt
+";
+
+#[test]
+fn buffer_captures_the_output() {
+    let mut interpreter = common::assemble(HELLO).with_io(Buffer::new());
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(interpreter.io.text(), "42\n!\nhé\n");
+
+    let mut decoded = common::assemble(HELLO).with_io(Buffer::new());
+    Engine::new(&mut decoded).execute();
+    assert_eq!(decoded.io, interpreter.io);
+}
+
+#[test]
+fn nothing_is_printed_from_uninitialised_memory() {
+    let mut interpreter =
+        common::assemble("print 1\nprintstr 9000 2\nhalt\n").with_io(Buffer::new());
+    assert_eq!(
+        interpreter.execute().kind,
+        TrapKind::UninitialisedRead(9000)
+    );
+    assert_eq!(interpreter.io.text(), "1\n");
+}
+
+#[test]
+fn files_receive_the_output() {
+    let path = std::env::temp_dir().join(format!("plac-io-{}", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+    let mut interpreter = common::assemble(HELLO).with_io(file);
+    assert!(interpreter.execute().is_halt());
+    drop(interpreter);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "42\n!\nhé\n");
+    std::fs::remove_file(path).unwrap();
+}
+
+struct Broken;
+
+impl Io for Broken {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+#[test]
+fn failing_io_traps() {
+    let mut interpreter = common::assemble(HELLO).with_io(Broken);
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::IoError);
+    assert_eq!(trap.pc, 4);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::engine::Engine;
+use plac::codegen::interpreter::*;
+
+fn load(lines: &[&str], limits: Limits) -> Interpreter {
+    let mut interpreter = common::compile(lines);
+    interpreter.set_limits(limits);
+    interpreter
+}
+
+const LOOP: [&str; 3] = ["load r0 0", "0: add r0 r0 1 U", "jmp: A 0"];
+
+#[test]
+fn instruction_budget_stops_endless_loops() {
+    let limits = Limits {
+        instructions: Some(100),
+        ..Limits::default()
+    };
+    let mut interpreter = load(&LOOP, limits);
+    let trap = interpreter.execute();
+    let usage = Usage {
+        instructions: 100,
+        ..Usage::default()
+    };
+    assert_eq!(
+        trap.kind,
+        TrapKind::Exhausted(Resource::Instructions, usage)
+    );
+    assert_eq!(trap.pc, 9);
+    assert_eq!(interpreter.usage, usage);
+    assert_eq!(*interpreter.get_register(&Register::R0), 50);
+
+    let mut decoded = load(&LOOP, limits);
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+}
+
+#[test]
+fn raising_the_limit_resumes() {
+    let mut interpreter = load(
+        &[
+            "load r0 3",
+            "0: push r0",
+            "sub r0 r0 1 U",
+            "cmp r0 0",
+            "jmpn: E 0",
+            "halt",
+        ],
+        Limits {
+            instructions: Some(5),
+            ..Limits::default()
+        },
+    );
+    assert!(!interpreter.execute().is_halt());
+    interpreter.limits.instructions = Some(6);
+    assert_eq!(interpreter.execute().pc, 9);
+    interpreter.limits.instructions = None;
+    assert_eq!(interpreter.execute().kind, TrapKind::Halt(0));
+    assert_eq!(interpreter.usage.instructions, 14);
+    assert_eq!(interpreter.usage.stack, 12);
+}
+
+#[test]
+fn memory_limit_counts_fresh_cells() {
+    let limits = Limits {
+        memory: Some(6),
+        ..Limits::default()
+    };
+    let lines = [
+        "store32 5000 1",
+        "store16 5002 1",
+        "store16 5004 1",
+        "store8 5006 1",
+        "halt",
+    ];
+    let mut interpreter = load(&lines, limits);
+    let trap = interpreter.execute();
+    assert_eq!(trap.pc, 13);
+    let usage = Usage {
+        instructions: 3,
+        memory: 6,
+        stack: 0,
+    };
+    assert_eq!(trap.kind, TrapKind::Exhausted(Resource::Memory, usage));
+    assert_eq!(
+        interpreter.read(5006, Width::Byte),
+        Err(TrapKind::UninitialisedRead(5006))
+    );
+}
+
+#[test]
+fn stack_limit_bounds_recursion() {
+    let limits = Limits {
+        stack: Some(16),
+        ..Limits::default()
+    };
+    let lines = ["call A 1"];
+    let mut interpreter = load(&lines, limits);
+    let trap = interpreter.execute();
+    let usage = Usage {
+        instructions: 4,
+        memory: 16,
+        stack: 16,
+    };
+    assert_eq!(trap.kind, TrapKind::Exhausted(Resource::Stack, usage));
+    let mut decoded = load(&lines, limits);
+    assert_eq!(Engine::new(&mut decoded).execute(), trap);
+}
+
+#[test]
+fn exhaustion_is_not_delivered_to_the_handler() {
+    let mut interpreter = load(
+        &LOOP,
+        Limits {
+            instructions: Some(10),
+            ..Limits::default()
+        },
+    );
+    interpreter.set_trap_handler(Some(1));
+    assert!(matches!(
+        interpreter.execute().kind,
+        TrapKind::Exhausted(..)
+    ));
+}
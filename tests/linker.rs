@@ -0,0 +1,174 @@
+use plac::codegen::asm::*;
+use plac::codegen::assembly;
+use plac::codegen::interpreter::*;
+use plac::codegen::linker::*;
+use plac::codegen::object::*;
+use plac::codegen::Assembler;
+use plac::parser::Parser;
+
+fn object(name: &str, source: &str) -> Object {
+    let mut parser = Parser::new(name.to_string(), source.to_string());
+    let mut assembler = Assembler::new();
+    if let Err(error) = assembly::parse(&mut parser, &mut assembler) {
+        panic!("{}", error.render().join("\n"));
+    }
+    assembler.assemble(0)
+}
+
+const MAIN: &str = "
+.extern greeting
+.extern print_twice
+.extern count
+start:
+    load r0 greeting
+    jmp: A print_twice
+back:
+    load r1 [count]
+    halt
+.global back
+";
+
+const RUNTIME: &str = "
+greeting: .string \"hi\"
+count: .word 7
+.global greeting
+.global count
+loop: halt
+print_twice:
+    printstr greeting greeting_len
+    printstr greeting greeting_len
+    jmp: A back
+.global print_twice
+.extern back
+";
+
+#[test]
+fn links_objects_and_relocates_symbols() {
+    let mut linker = Linker::new();
+    linker.add("main".to_string(), object("main", MAIN));
+    linker.add("runtime".to_string(), object("runtime", RUNTIME));
+    let linked = linker.link().unwrap_or_else(|error| panic!("{}", error.render().join("\n")));
+
+    assert_eq!(linked.data, vec![104, 105, 7, 0, 0, 0]);
+    assert!(linked.relocations.is_empty());
+    let adress = |name: &str| {
+        linked
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .unwrap()
+            .adress
+    };
+    assert_eq!(adress("greeting"), 0);
+    assert_eq!(adress("count"), 2);
+    assert_eq!(adress("start"), 6);
+    assert_eq!(linked.entry, 6);
+    assert_eq!(adress("print_twice"), 6 + 5 * 4);
+
+    assert_eq!(
+        linked.code[0],
+        Instruction::Load(Register::R0, Value::Direct(Operand::U32(0)))
+    );
+    assert_eq!(
+        linked.code[1],
+        Instruction::Jump(Flag::Always, Value::Direct(Operand::U32(26)))
+    );
+    assert_eq!(
+        linked.code[2],
+        Instruction::Load(Register::R1, Value::Indirect(Operand::U32(2)))
+    );
+    assert_eq!(
+        linked.code[5],
+        Instruction::SysCall(SysCall::PrintString {
+            start: Value::Direct(Operand::U32(0)),
+            length: Value::Direct(Operand::U32(2)),
+        })
+    );
+    assert_eq!(
+        linked.code[7],
+        Instruction::Jump(Flag::Always, Value::Direct(Operand::U32(14)))
+    );
+
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(linked).unwrap();
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R1), 7);
+}
+
+#[test]
+fn local_symbols_do_not_leak_between_objects() {
+    let first = object("first", "loop: jmp: A loop\n");
+    let second = object("second", "halt\nloop: jmp: A loop\n");
+    let mut linker = Linker::new();
+    linker.add("first".to_string(), first);
+    linker.add("second".to_string(), second);
+    let linked = linker.link().ok().unwrap();
+    assert_eq!(
+        linked.code[0],
+        Instruction::Jump(Flag::Always, Value::Direct(Operand::U32(0)))
+    );
+    assert_eq!(
+        linked.code[2],
+        Instruction::Jump(Flag::Always, Value::Direct(Operand::U32(8)))
+    );
+}
+
+#[test]
+fn reports_duplicate_and_undefined_symbols() {
+    let mut linker = Linker::new();
+    linker.add("a".to_string(), object("a", "f: halt\n.global f\n.extern g\njmp: A g\n"));
+    linker.add("b".to_string(), object("b", "f: halt\n.global f\n"));
+    let rendered = linker.link().err().unwrap().render().join("\n");
+    assert!(rendered.contains(&DUPLICATE_SYMBOL.to_string()));
+    assert!(rendered.contains("symbol `f` is defined more than once"));
+    assert!(rendered.contains("defined in `a` and `b`"));
+    assert!(rendered.contains(&UNDEFINED_SYMBOL.to_string()));
+    assert!(rendered.contains("cannot find symbol `g`"));
+    assert!(rendered.contains("referenced in `a`"));
+}
+
+#[test]
+fn rejects_code_adresses_inside_the_data() {
+    let bad = Object {
+        data: vec![0; 8],
+        code: vec![Instruction::Halt],
+        entry: 4,
+        symbols: vec![Symbol {
+            name: "f".to_string(),
+            adress: 2,
+            section: Section::Code,
+            binding: Binding::Global,
+        }],
+        relocations: vec![],
+    };
+    let mut linker = Linker::new();
+    linker.add("bad".to_string(), bad);
+    let rendered = linker.link().err().unwrap().render().join("\n");
+    assert!(rendered.contains(&INVALID_ADRESS.to_string()));
+    assert!(rendered.contains("code symbol `f` of `bad` points before its code"));
+    assert!(rendered.contains("the entry of `bad` points before its code"));
+    assert!(rendered.contains("it is at 2, the code starts at 8"));
+}
+
+#[test]
+fn rejects_relocations_outside_the_code() {
+    let relocation = |instruction, operand| Relocation {
+        instruction,
+        operand,
+        symbol: "f".to_string(),
+    };
+    let bad = Object {
+        data: vec![],
+        code: vec![Instruction::Halt],
+        entry: 0,
+        symbols: vec![],
+        relocations: vec![relocation(3, 0), relocation(0, 0)],
+    };
+    let mut linker = Linker::new();
+    linker.add("bad".to_string(), bad);
+    let rendered = linker.link().err().unwrap().render().join("\n");
+    assert!(rendered.contains(&INVALID_RELOCATION.to_string()));
+    assert!(rendered.contains("relocation of `f` in `bad` names an operand its code does not have"));
+    assert!(rendered.contains("it names operand 0 of instruction 3, the code has 1 instructions"));
+    assert!(rendered.contains("it names operand 0 of instruction 0, the code has 1 instructions"));
+}
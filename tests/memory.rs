@@ -0,0 +1,219 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::memory::*;
+
+fn run(source: &str) -> (Interpreter, Trap) {
+    let mut interpreter = common::assemble(source);
+    let trap = interpreter.execute();
+    (interpreter, trap)
+}
+
+#[test]
+fn sized_instructions_round_trip_as_text() {
+    for width in Width::ALL {
+        for instruction in [
+            Instruction::LoadMemory(
+                Register::R3,
+                Value::OperationalDirect(Operand::Register(Register::R1), Operand::U32(2)),
+                width,
+            ),
+            Instruction::Store(
+                Value::Direct(Operand::U32(100)),
+                Value::Direct(Operand::Register(Register::R2)),
+                width,
+            ),
+        ] {
+            assert_eq!(
+                Instruction::try_from(String::from(instruction)),
+                Ok(instruction)
+            );
+        }
+    }
+    assert_eq!(
+        String::from(Instruction::Store(
+            Value::Direct(Operand::U32(1)),
+            Value::Direct(Operand::U32(2)),
+            Width::Half
+        )),
+        "store16 1 2"
+    );
+}
+
+#[test]
+fn stores_are_little_endian() {
+    let (mut interpreter, trap) = run("
+    store32 5000 287454020
+    load8 r0 5000
+    load8 r1 5003
+    load16 r2 5001
+    load32 r3 5000
+    store8 5001 255
+    load32 r4 5000
+    store16 5002 4294967295
+    load32 r6 5000
+    halt
+");
+    assert!(trap.is_halt(), "{:?}", trap);
+    assert_eq!(*interpreter.get_register(&Register::R0), 0x44);
+    assert_eq!(*interpreter.get_register(&Register::R1), 0x11);
+    assert_eq!(*interpreter.get_register(&Register::R2), 0x2233);
+    assert_eq!(*interpreter.get_register(&Register::R3), 0x1122_3344);
+    assert_eq!(*interpreter.get_register(&Register::R4), 0x1122_ff44);
+    assert_eq!(*interpreter.get_register(&Register::R6), 0xffff_ff44);
+    assert_eq!(interpreter.read(5000, Width::Word), Ok(0xffff_ff44));
+}
+
+#[test]
+fn words_and_strings_are_packed() {
+    let source = "
+message: .string \"hé\"
+numbers: .word 1 258
+small: .byte 1 -1
+    load32 r0 numbers+4
+    load8 r1 small+1
+    load8 r2 message+1
+    halt
+";
+    let object = common::object(source);
+    assert_eq!(
+        object.data,
+        vec![b'h', 0xc3, 0xa9, 1, 0, 0, 0, 2, 1, 0, 0, 1, 255]
+    );
+    let length = |name: &str| {
+        object
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .unwrap()
+            .adress
+    };
+    assert_eq!(length("message_len"), 3);
+    assert_eq!(length("numbers_len"), 8);
+    assert_eq!(length("small_len"), 2);
+
+    let (mut interpreter, trap) = run(source);
+    assert!(trap.is_halt(), "{:?}", trap);
+    assert_eq!(*interpreter.get_register(&Register::R0), 258);
+    assert_eq!(*interpreter.get_register(&Register::R1), 255);
+    assert_eq!(*interpreter.get_register(&Register::R2), 0xc3);
+}
+
+#[test]
+fn partially_written_words_are_uninitialised() {
+    let (_, trap) = run("store8 6000 1\nload32 r0 6000\nhalt\n");
+    assert_eq!(trap.kind, TrapKind::UninitialisedRead(6001));
+}
+
+const FIBONACCI: &str = "
+    load r0 15
+    load r1 1
+    load r2 2
+0:  sub r0 r0 1 U
+    swap r1 r2
+    add r2 r1 r2 U
+    store32 [counter] r2
+    cmp r0 0
+    jmpn: E 0
+    halt
+counter: .word 5000
+";
+
+#[test]
+fn flat_memory_runs_like_the_map() {
+    let object = common::object(FIBONACCI);
+    let mut map = Interpreter::new();
+    map.load_object(object.clone()).unwrap();
+    let mut flat = Interpreter::with_memory(FlatMemory::new(1 << 16));
+    flat.load_object(object).unwrap();
+    assert_eq!(map.execute(), flat.execute());
+    assert_eq!(map.registers, flat.registers);
+    assert_eq!(map.flags, flat.flags);
+    assert_eq!(map.debug(), flat.debug());
+    assert!(flat.memory.nodes().eq(map.memory.nodes()));
+    assert_eq!(flat.read(5000, Width::Word), Ok(2584));
+}
+
+#[test]
+fn flat_memory_traps_past_its_limit() {
+    let mut interpreter = Interpreter::with_memory(FlatMemory::new(4096));
+    interpreter
+        .load_object(common::object("store8 4095 1\nstore8 4096 1\nhalt\n"))
+        .unwrap();
+    let trap = interpreter.execute();
+    assert_eq!(trap.pc, 4);
+    assert_eq!(trap.kind, TrapKind::OutOfMemory(4096));
+    assert_eq!(interpreter.read(4095, Width::Byte), Ok(1));
+    assert_eq!(
+        interpreter.read(4096, Width::Byte),
+        Err(TrapKind::UninitialisedRead(4096))
+    );
+}
+
+#[test]
+fn stack_past_the_limit_is_out_of_memory() {
+    let mut interpreter = Interpreter::with_memory(FlatMemory::new(1024));
+    interpreter
+        .load_object(common::object("push 1\nhalt\n"))
+        .unwrap();
+    assert!(matches!(
+        interpreter.execute().kind,
+        TrapKind::OutOfMemory(_)
+    ));
+}
+
+#[test]
+fn programs_that_do_not_fit_are_not_loaded() {
+    let mut interpreter = Interpreter::with_memory(FlatMemory::new(10));
+    let code = vec![Instruction::Halt; 2];
+    assert_eq!(
+        interpreter.load(vec![1, 2, 3], code.clone(), 3),
+        Err(TrapKind::OutOfMemory(10))
+    );
+    assert_eq!(interpreter.memory.nodes().count(), 0);
+    assert_eq!(interpreter.load(vec![1, 2], code, 2), Ok(()));
+}
+
+#[test]
+fn writes_across_the_limit_write_nothing() {
+    let mut interpreter = Interpreter::with_memory(FlatMemory::new(4096));
+    interpreter
+        .load_object(common::object("store32 4094 4294967295\nhalt\n"))
+        .unwrap();
+    assert_eq!(interpreter.execute().kind, TrapKind::OutOfMemory(4096));
+    assert_eq!(
+        interpreter.read(4094, Width::Byte),
+        Err(TrapKind::UninitialisedRead(4094))
+    );
+}
+
+#[test]
+fn flat_memory_stores_what_the_map_stores() {
+    let mut map = std::collections::BTreeMap::new();
+    let mut flat = FlatMemory::new(1 << 16);
+    let writes = [
+        (2000, MemoryNode::Data(7)),
+        (5, MemoryNode::Instruction(Instruction::Halt)),
+        (6, MemoryNode::FilledByInstruction),
+        (4, MemoryNode::Data(0)),
+        (5, MemoryNode::Data(255)),
+        (6, MemoryNode::None),
+        (2000, MemoryNode::Instruction(Instruction::Ret)),
+        (1023, MemoryNode::Data(1)),
+        (1024, MemoryNode::Data(2)),
+    ];
+    for (adress, node) in writes {
+        Memory::insert(&mut map, adress, node.clone()).unwrap();
+        flat.insert(adress, node).unwrap();
+        assert!(flat.nodes().eq(map.nodes()));
+        for adress in [4, 5, 6, 1023, 1024, 2000] {
+            let expected = Memory::get(&map, adress).filter(|node| **node != MemoryNode::None);
+            assert_eq!(flat.get(adress), expected, "{}", adress);
+        }
+    }
+    assert_eq!(
+        flat.insert(1 << 16, MemoryNode::Data(1)),
+        Err(TrapKind::OutOfMemory(1 << 16))
+    );
+}
@@ -0,0 +1,274 @@
+use plac::codegen::asm::*;
+use plac::codegen::object::*;
+use plac::codegen::Assembler;
+
+fn values() -> Vec<Value> {
+    vec![
+        Value::Direct(Operand::U32(0)),
+        Value::Direct(Operand::U32(u32::MAX)),
+        Value::Direct(Operand::Register(Register::R15)),
+        Value::Indirect(Operand::Register(Register::StackPointer)),
+        Value::Indirect(Operand::U32(1234)),
+        Value::OperationalDirect(Operand::Register(Register::R1), Operand::U32(7)),
+        Value::OperationalIndirect(
+            Operand::U32(0xdead_beef),
+            Operand::Register(Register::ProgramPointer),
+        ),
+    ]
+}
+
+fn instructions() -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    for value1 in values() {
+        for value2 in values() {
+            for register in Register::ALL {
+                for math_type in MathType::ALL {
+                    instructions.push(Instruction::Add(register, value1, value2, math_type));
+                    instructions.push(Instruction::Sub(register, value1, value2, math_type));
+                    instructions.push(Instruction::Mul(register, value1, value2, math_type));
+                    instructions.push(Instruction::Div(register, value1, value2, math_type));
+                    instructions.push(Instruction::Mod(register, value1, value2, math_type));
+                }
+                instructions.push(Instruction::And(register, value1, value2));
+                instructions.push(Instruction::Or(register, value1, value2));
+                instructions.push(Instruction::Xor(register, value1, value2));
+                instructions.push(Instruction::ShiftLeft(register, value1, value2));
+                instructions.push(Instruction::ShiftRight(register, value1, value2));
+            }
+            for math_type in MathType::ALL {
+                instructions.push(Instruction::Compare(value1, value2, math_type));
+            }
+            instructions.push(Instruction::Move(value1, value2));
+            instructions.push(Instruction::SysCall(SysCall::PrintString {
+                start: value1,
+                length: value2,
+            }));
+        }
+        for register in Register::ALL {
+            instructions.push(Instruction::Not(register, value1));
+            instructions.push(Instruction::Load(register, value1));
+            for width in Width::ALL {
+                instructions.push(Instruction::SignExtend(register, value1, width));
+                instructions.push(Instruction::ZeroExtend(register, value1, width));
+            }
+            for from in MathType::ALL {
+                for to in MathType::ALL {
+                    instructions.push(Instruction::Convert(register, value1, from, to));
+                }
+            }
+        }
+        for flag in Flag::ALL {
+            instructions.push(Instruction::Jump(flag, value1));
+            instructions.push(Instruction::JumpNot(flag, value1));
+            instructions.push(Instruction::Call(flag, value1));
+            instructions.push(Instruction::CallNot(flag, value1));
+        }
+        instructions.push(Instruction::Push(value1));
+        for width in Width::ALL {
+            instructions.push(Instruction::Store(value1, value1, width));
+        }
+        instructions.push(Instruction::SysCall(SysCall::Print(value1)));
+        instructions.push(Instruction::SysCall(SysCall::PrintChar(value1)));
+    }
+    for register1 in Register::ALL {
+        instructions.push(Instruction::Pop(register1));
+        for width in Width::ALL {
+            instructions.push(Instruction::LoadMemory(
+                register1,
+                Value::Indirect(Operand::U32(7)),
+                width,
+            ));
+        }
+        for register2 in Register::ALL {
+            instructions.push(Instruction::Swap(register1, register2));
+        }
+    }
+    instructions.push(Instruction::Halt);
+    instructions.push(Instruction::Ret);
+    instructions
+}
+
+#[test]
+fn every_instruction_round_trips() {
+    for instruction in instructions() {
+        let bytes = encode_instruction(&instruction);
+        assert_eq!(decode_instruction(&bytes), Ok(instruction));
+    }
+}
+
+#[test]
+fn object_round_trips() {
+    let object = Object {
+        data: vec![1, 2, 3, u8::MAX],
+        code: instructions(),
+        entry: 8,
+        symbols: vec![
+            Symbol {
+                name: "msg".to_string(),
+                adress: 0,
+                section: Section::Data,
+                binding: Binding::Global,
+            },
+            Symbol {
+                name: "start".to_string(),
+                adress: 8,
+                section: Section::Code,
+                binding: Binding::Local,
+            },
+        ],
+        relocations: vec![Relocation {
+            instruction: 0,
+            operand: 1,
+            symbol: "msg".to_string(),
+        }],
+    };
+    let bytes = object.encode().unwrap();
+    assert_eq!(
+        bytes.len(),
+        26 + 4
+            + object.code.len() * ENCODED_INSTRUCTION_SIZE
+            + (6 + 2 + 3)
+            + (6 + 2 + 5)
+            + (8 + 2 + 3)
+    );
+    assert_eq!(Object::decode(&bytes), Ok(object));
+}
+
+#[test]
+fn assembled_program_round_trips() {
+    let mut assembler = Assembler::new();
+    assembler.add_static("numbers".to_string(), vec![4, 5, 6]);
+    for line in ["load r0 3", "0: sub r0 r0 1 U", "cmp r0 0", "jmpn: E 0", "halt"] {
+        assembler.add_ir(line.to_string().try_into().unwrap());
+    }
+    let object = assembler.assemble(1);
+    assert_eq!(object.data, vec![0, 4, 0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0]);
+    assert_eq!(object.entry, 13);
+    assert_eq!(Object::decode(&object.encode().unwrap()), Ok(object));
+}
+
+#[test]
+fn rejects_malformed_objects() {
+    let bytes = Object {
+        data: vec![1],
+        code: vec![Instruction::Halt],
+        entry: 1,
+        symbols: vec![],
+        relocations: vec![],
+    }
+    .encode()
+    .unwrap();
+    assert_eq!(Object::decode(&bytes[..3]), Err(ObjectError::UnexpectedEnd));
+    assert_eq!(
+        Object::decode(&bytes[..bytes.len() - 1]),
+        Err(ObjectError::UnexpectedEnd)
+    );
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Object::decode(&trailing), Err(ObjectError::TrailingBytes));
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert_eq!(Object::decode(&magic), Err(ObjectError::InvalidMagic));
+
+    let mut version = bytes.clone();
+    version[4] = 99;
+    assert_eq!(
+        Object::decode(&version),
+        Err(ObjectError::UnsupportedVersion(99))
+    );
+
+    let mut relocation = Object {
+        data: vec![],
+        code: vec![Instruction::Halt],
+        entry: 0,
+        symbols: vec![],
+        relocations: vec![Relocation {
+            instruction: 0,
+            operand: 0,
+            symbol: "x".to_string(),
+        }],
+    }
+    .encode()
+    .unwrap();
+    assert_eq!(
+        Object::decode(&relocation),
+        Err(ObjectError::InvalidRelocation)
+    );
+    relocation[26 + ENCODED_INSTRUCTION_SIZE] = 5;
+    assert_eq!(
+        Object::decode(&relocation),
+        Err(ObjectError::InvalidRelocation)
+    );
+
+    let mut opcode = encode_instruction(&Instruction::Halt);
+    opcode[0] = 200;
+    assert_eq!(
+        decode_instruction(&opcode),
+        Err(ObjectError::InvalidOpcode(200))
+    );
+}
+
+#[test]
+fn rejects_out_of_range_registers_with_their_index() {
+    let mut operand = encode_operand(&Operand::Register(Register::R0));
+    operand[1..].copy_from_slice(&0x100u32.to_le_bytes());
+    assert_eq!(
+        decode_operand(&operand),
+        Err(ObjectError::InvalidRegister(0x100))
+    );
+    let mut pop = encode_instruction(&Instruction::Pop(Register::R0));
+    pop[1] = 200;
+    assert_eq!(
+        decode_instruction(&pop),
+        Err(ObjectError::InvalidRegister(200))
+    );
+}
+
+#[test]
+fn rejects_bytes_an_instruction_does_not_use() {
+    let push = Instruction::Push(Value::Direct(Operand::U32(7)));
+    for index in [
+        1,
+        2,
+        3,
+        5 + ENCODED_OPERAND_SIZE,
+        4 + ENCODED_VALUE_SIZE,
+        ENCODED_INSTRUCTION_SIZE - 1,
+    ] {
+        let mut bytes = encode_instruction(&push);
+        bytes[index] = 1;
+        assert_eq!(
+            decode_instruction(&bytes),
+            Err(ObjectError::InvalidPadding),
+            "{}",
+            index
+        );
+    }
+    let mut value = encode_value(&Value::Indirect(Operand::U32(7)));
+    value[ENCODED_VALUE_SIZE - 1] = 1;
+    assert_eq!(decode_value(&value), Err(ObjectError::InvalidPadding));
+}
+
+#[test]
+fn names_longer_than_their_length_field_are_not_encoded() {
+    let object = |length: usize| Object {
+        data: vec![],
+        code: vec![Instruction::Halt],
+        entry: 0,
+        symbols: vec![Symbol {
+            name: "a".repeat(length),
+            adress: 0,
+            section: Section::Code,
+            binding: Binding::Global,
+        }],
+        relocations: vec![],
+    };
+    let longest = object(u16::MAX as usize);
+    assert_eq!(Object::decode(&longest.encode().unwrap()), Ok(longest));
+    assert_eq!(
+        object(u16::MAX as usize + 1).encode(),
+        Err(ObjectError::NameTooLong(u16::MAX as usize + 1))
+    );
+}
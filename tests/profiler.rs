@@ -0,0 +1,112 @@
+mod common;
+
+use plac::codegen::interpreter::*;
+use plac::codegen::profiler::Profiler;
+use std::sync::{Arc, Mutex};
+
+/// runs `source` and returns the profile, with the trap handler at the label `handler`
+fn profile(source: &str, limits: Limits) -> (Trap, Profiler) {
+    let object = common::object(source);
+    let mut profiler = Profiler::new();
+    profiler.add_symbols(&object.symbols);
+    let handler = object
+        .symbols
+        .iter()
+        .find(|symbol| symbol.name == "handler")
+        .map(|symbol| symbol.adress);
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(object).unwrap();
+    interpreter.set_trap_handler(handler);
+    interpreter.set_limits(limits);
+    let profiler = Arc::new(Mutex::new(profiler));
+    interpreter.set_tracer(Some(profiler.clone()));
+    let trap = interpreter.execute();
+    let profiler = profiler.lock().unwrap().clone();
+    (trap, profiler)
+}
+
+/// `count` calls itself until r1 is 0, the division faults into `handler`
+const PROGRAM: &str = "
+count:
+    cmp r1 0
+    jmp: E done
+    sub r1 r1 1 U
+    call A count
+done:
+    div r2 1 r1 U
+    ret
+handler:
+    add r15 r15 4 U
+    load r2 r14
+    jmp A r15
+main:
+    load r1 2
+    call A count
+    halt
+.entry main
+";
+
+#[test]
+fn instructions_count_per_adress_and_label() {
+    let (trap, profiler) = profile(PROGRAM, Limits::default());
+    assert!(trap.is_halt());
+    // count runs 4 instructions when it calls itself and 2 when it does not, r1 is 0 in
+    // every `done` so each of the 3 faults into the handler
+    assert_eq!(profiler.total(), 3 + 4 * 2 + 2 + 3 * 2 + 3 * 3);
+    assert_eq!(
+        profiler.by_label(),
+        [
+            ("count".to_string(), 10),
+            ("handler".to_string(), 9),
+            ("done".to_string(), 6),
+            ("main".to_string(), 3),
+        ]
+    );
+    let counts: Vec<(u32, u64)> = profiler
+        .counts()
+        .map(|(adress, _, count)| (adress, count))
+        .collect();
+    assert_eq!(counts[..4], [(0, 3), (4, 3), (8, 2), (12, 2)]);
+    assert_eq!(profiler.name(8), "count+8");
+    assert_eq!(profiler.name(16), "done");
+}
+
+#[test]
+fn the_report_lists_hot_spots() {
+    let (_, profiler) = profile(PROGRAM, Limits::default());
+    assert_eq!(
+        profiler.report(2),
+        "28 instructions\n\
+         \n   count       %  label\n\
+         \x20     10  35.71%  count\n\
+         \x20      9  32.14%  handler\n\
+         \n   count       %    adress  instruction\n\
+         \x20      3  10.71%         0  cmp r1 0\n\
+         \x20      3  10.71%         4  jmp E 16\n"
+    );
+}
+
+#[test]
+fn folded_stacks_follow_calls() {
+    let (_, profiler) = profile(PROGRAM, Limits::default());
+    // the handler runs in the frame that faulted and does not end it by returning
+    assert_eq!(
+        profiler.folded(),
+        "main 3\nmain;count 9\nmain;count;count 9\nmain;count;count;count 7\n"
+    );
+    let path = std::env::temp_dir().join(format!("plac-folded-{}", std::process::id()));
+    profiler.write_folded(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), profiler.folded());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn only_executed_instructions_count() {
+    let limits = Limits {
+        instructions: Some(5),
+        ..Limits::default()
+    };
+    let (trap, profiler) = profile(PROGRAM, limits);
+    assert!(matches!(trap.kind, TrapKind::Exhausted(..)));
+    assert_eq!(profiler.total(), 5);
+}
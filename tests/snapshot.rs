@@ -0,0 +1,286 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::io::Buffer;
+use plac::codegen::snapshot::*;
+use plac::codegen::*;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+type Buffered = Interpreter<BTreeMap<u32, MemoryNode>, Buffer>;
+
+const INPUT: &str = "xy";
+
+fn load(source: &str, root: &PathBuf) -> Buffered {
+    let mut interpreter = common::assemble(source).with_io(Buffer::with_input(INPUT));
+    interpreter.set_root(root).unwrap();
+    interpreter.set_heap_checks(true);
+    interpreter.set_limits(Limits {
+        instructions: Some(1000),
+        ..Limits::default()
+    });
+    interpreter
+}
+
+/// an interpreter with nothing loaded that opens files in `root`
+fn empty(root: &PathBuf) -> Buffered {
+    let mut interpreter: Buffered = Interpreter::new().with_io(Buffer::with_input(INPUT));
+    interpreter.set_root(root).unwrap();
+    interpreter
+}
+
+/// an empty directory of its own for every test
+fn root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("plac-snapshot-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// reads from the input, the heap and a file on both sides of the fifth instruction
+const PROGRAM: &str = "
+name: .string \"in.txt\"
+buffer: .zero 8
+    readc r9
+    alloc r6 16
+    store8 r6 r9
+    open r1 name name_len 0
+    read r2 r1 buffer 3
+    read r3 r1 buffer 3
+    load8 r7 buffer
+    readc r8
+    free r6
+    write r4 1 buffer r3
+    exit r7
+";
+
+#[test]
+fn restored_programs_continue_where_they_were() {
+    let root = root("continue");
+    std::fs::write(root.join("in.txt"), "abcdef").unwrap();
+    let mut reference = load(PROGRAM, &root);
+    let trap = reference.execute();
+    assert_eq!(trap.exit_code(), Some(b'd' as u32));
+    assert_eq!(reference.io.text(), "def");
+
+    let mut interpreter = load(PROGRAM, &root);
+    for _ in 0..5 {
+        interpreter.tick().unwrap();
+    }
+    let snapshot = interpreter.snapshot().unwrap();
+    assert_eq!(snapshot.input, Some(1));
+    assert_eq!(
+        snapshot.files,
+        [(3, PathBuf::from("in.txt"), files::Mode::Read, 3)]
+    );
+    assert_eq!(Snapshot::decode(&snapshot.encode()), Ok(snapshot.clone()));
+
+    // continues with nothing of the program loaded
+    let mut restored = empty(&root);
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.execute(), trap);
+    assert_eq!(restored.snapshot().unwrap(), reference.snapshot().unwrap());
+    assert_eq!(restored.io, reference.io);
+    assert_eq!(*restored.get_register(&Register::R8), b'y' as u32);
+
+    // the interpreter the snapshot was taken from is not affected
+    assert_eq!(interpreter.execute(), trap);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn restoring_replaces_the_memory() {
+    let root = root("replace");
+    let mut interpreter = load(PROGRAM, &root);
+    let snapshot = interpreter.snapshot().unwrap();
+    interpreter.write(9000, 1, Width::Byte).unwrap();
+    interpreter.tick().unwrap();
+    interpreter.set_history(Some(history::History::new(10)));
+    interpreter.tick().unwrap();
+    interpreter.restore(&snapshot).unwrap();
+    assert_eq!(interpreter.snapshot().unwrap(), snapshot);
+    assert_eq!(
+        interpreter.read(9000, Width::Byte),
+        Err(TrapKind::UninitialisedRead(9000))
+    );
+    assert!(!interpreter.step_back());
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn written_files_are_not_emptied() {
+    let root = root("written");
+    let source = "
+name: .string \"out.txt\"
+text: .string \"abc\"
+more: .string \"de\"
+    open r1 name name_len 1
+    write r2 r1 text text_len
+    write r2 r1 more more_len
+    halt
+";
+    let mut interpreter = load(source, &root);
+    for _ in 0..2 {
+        interpreter.tick().unwrap();
+    }
+    let path = root.join("state");
+    interpreter.snapshot().unwrap().save(&path).unwrap();
+    drop(interpreter);
+
+    let mut restored = empty(&root);
+    restored.restore(&Snapshot::open(&path).unwrap()).unwrap();
+    assert!(restored.execute().is_halt());
+    assert_eq!(
+        std::fs::read_to_string(root.join("out.txt")).unwrap(),
+        "abcde"
+    );
+
+    // a file that went away cannot be continued
+    std::fs::remove_file(root.join("out.txt")).unwrap();
+    let mut restored = empty(&root);
+    let before = restored.clone();
+    let error = restored.restore(&Snapshot::open(&path).unwrap());
+    assert!(matches!(error, Err(SnapshotError::Io(_))));
+    assert_eq!(restored, before);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn files_are_reopened_in_the_current_root() {
+    let root = root("root");
+    std::fs::write(root.join("in.txt"), "abcdef").unwrap();
+    let mut interpreter = load(PROGRAM, &root);
+    for _ in 0..5 {
+        interpreter.tick().unwrap();
+    }
+    let snapshot = interpreter.snapshot().unwrap();
+
+    // the same file in another root
+    let other = self::root("other");
+    std::fs::write(other.join("in.txt"), "uvwxyz").unwrap();
+    let mut restored = empty(&other);
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.execute().exit_code(), Some(b'x' as u32));
+
+    // without a root nothing can be reopened
+    let mut restored: Buffered = Interpreter::new().with_io(Buffer::with_input(INPUT));
+    let before = restored.clone();
+    assert_eq!(restored.restore(&snapshot), Err(SnapshotError::InvalidPath));
+    assert_eq!(restored, before);
+
+    for path in [
+        PathBuf::from("../in.txt"),
+        root.join("in.txt"),
+        PathBuf::from("a/../../in.txt"),
+    ] {
+        let mut escaping = snapshot.clone();
+        escaping.files[0].1 = path;
+        let mut restored = empty(&other);
+        let before = restored.clone();
+        assert_eq!(restored.restore(&escaping), Err(SnapshotError::InvalidPath));
+        assert_eq!(restored, before);
+    }
+    std::fs::remove_dir_all(root).unwrap();
+    std::fs::remove_dir_all(other).unwrap();
+}
+
+#[test]
+fn the_input_has_to_be_seekable() {
+    let root = root("input");
+    let mut interpreter = load(PROGRAM, &root);
+    interpreter.tick().unwrap();
+    let snapshot = interpreter.snapshot().unwrap();
+
+    let mut console = Interpreter::new();
+    let before = console.clone();
+    assert!(matches!(
+        console.restore(&snapshot),
+        Err(SnapshotError::Io(_))
+    ));
+    assert_eq!(console, before);
+    let mut short: Buffered = Interpreter::new().with_io(Buffer::new());
+    assert!(short.restore(&snapshot).is_err());
+
+    // the console cannot tell how much it read, which restores anywhere
+    let mut console = Interpreter::new();
+    console
+        .load(Vec::new(), vec![Instruction::Halt], 0)
+        .unwrap();
+    let snapshot = console.snapshot().unwrap();
+    assert_eq!(snapshot.input, None);
+    let mut restored = empty(&root);
+    restored.restore(&snapshot).unwrap();
+    assert!(restored.execute().is_halt());
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn invalid_snapshots_do_not_decode() {
+    let root = root("invalid");
+    let interpreter = load(PROGRAM, &root);
+    let snapshot = interpreter.snapshot().unwrap();
+    let bytes = snapshot.encode();
+    std::fs::remove_dir_all(root).unwrap();
+
+    assert_eq!(
+        Snapshot::decode(b"PLAC\x01\x00"),
+        Err(SnapshotError::InvalidMagic)
+    );
+    let mut version = bytes.clone();
+    version[4] = 9;
+    assert_eq!(
+        Snapshot::decode(&version),
+        Err(SnapshotError::UnsupportedVersion(9))
+    );
+    assert_eq!(
+        Snapshot::decode(&bytes[..bytes.len() - 1]),
+        Err(SnapshotError::UnexpectedEnd)
+    );
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Snapshot::decode(&trailing),
+        Err(SnapshotError::TrailingBytes)
+    );
+    // the first run holds the data, the second the first instruction
+    let data = u32::from_le_bytes(bytes[15..19].try_into().unwrap()) as usize;
+    let mut node = bytes.clone();
+    node[19 + data + 4] = 7;
+    assert_eq!(Snapshot::decode(&node), Err(SnapshotError::InvalidNode(7)));
+    let mut opcode = bytes.clone();
+    opcode[19 + data + 5] = 200;
+    assert_eq!(
+        Snapshot::decode(&opcode),
+        Err(SnapshotError::InvalidInstruction(
+            object::ObjectError::InvalidOpcode(200)
+        ))
+    );
+
+    // ranges that would reach past the end of the adress space
+    let mut stack = snapshot.clone();
+    stack.stack = u32::MAX - 1..u32::MAX;
+    let mut range = snapshot.clone();
+    range.heap.range = Range { start: 8, end: 4 };
+    for snapshot in [stack, range] {
+        assert_eq!(
+            Snapshot::decode(&snapshot.encode()),
+            Err(SnapshotError::InvalidRange)
+        );
+    }
+    // the free space of the heap moved to the end of the adress space
+    let heap = &snapshot.heap.range;
+    let space = [
+        heap.start.to_le_bytes(),
+        (heap.end - heap.start).to_le_bytes(),
+    ]
+    .concat();
+    let index = bytes
+        .windows(8)
+        .rposition(|window| window == space)
+        .unwrap();
+    let mut free = bytes.clone();
+    free[index..index + 4].copy_from_slice(&(u32::MAX - 4).to_le_bytes());
+    assert_eq!(Snapshot::decode(&free), Err(SnapshotError::InvalidRange));
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::disassembler::disassemble;
+use plac::codegen::interpreter::*;
+use plac::codegen::object::Object;
+use plac::codegen::*;
+
+fn run(object: Object) -> (Interpreter, Trap) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_object(object).unwrap();
+    let trap = interpreter.execute();
+    (interpreter, trap)
+}
+
+const SQUARE: &str = "
+    load r1 3
+    call A square
+    push r0
+    load r1 4
+    call A square
+    pop r2
+    add r0 r0 r2 U
+    halt
+square:
+    mul r0 r1 r1 U
+    ret
+";
+
+#[test]
+fn text_round_trips() {
+    for instruction in [
+        Instruction::Push(Value::Indirect(Operand::Register(Register::StackPointer))),
+        Instruction::Pop(Register::R15),
+        Instruction::Call(Flag::Equal, Value::Direct(Operand::U32(12))),
+        Instruction::CallNot(Flag::Lesser, Value::Direct(Operand::Register(Register::R1))),
+        Instruction::Ret,
+    ] {
+        assert_eq!(
+            Instruction::try_from(String::from(instruction)),
+            Ok(instruction)
+        );
+    }
+}
+
+#[test]
+fn push_and_pop() {
+    let (mut interpreter, trap) = run(common::object(
+        "push 1\npush 2\npush 3\npop r0\npop r1\npop r2\nhalt\n",
+    ));
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 3);
+    assert_eq!(*interpreter.get_register(&Register::R1), 2);
+    assert_eq!(*interpreter.get_register(&Register::R2), 1);
+    let top = interpreter.stack.end;
+    assert_eq!(*interpreter.get_register(&Register::StackPointer), top);
+}
+
+#[test]
+fn stack_starts_aligned_after_the_code() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load_object(common::object(".byte 1 2\nhalt\n"))
+        .unwrap();
+    assert_eq!(interpreter.stack, 8..8 + STACK_SIZE);
+    assert_eq!(
+        *interpreter.get_register(&Register::StackPointer),
+        8 + STACK_SIZE
+    );
+}
+
+#[test]
+fn stack_bounds_trap() {
+    let (interpreter, trap) = run(common::object("pop r0\nhalt\n"));
+    assert_eq!(trap.kind, TrapKind::StackOutOfBounds(interpreter.stack.end));
+
+    let (interpreter, trap) = run(common::object("loop: push 0\njmp: A loop\n"));
+    assert_eq!(
+        trap.kind,
+        TrapKind::StackOutOfBounds(interpreter.stack.start)
+    );
+    assert_eq!(trap.pc, 0);
+
+    let (_, trap) = run(common::object("ret\n"));
+    assert!(matches!(trap.kind, TrapKind::StackOutOfBounds(_)));
+}
+
+#[test]
+fn call_and_ret() {
+    let (mut interpreter, trap) = run(common::object(SQUARE));
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 25);
+    let top = interpreter.stack.end;
+    assert_eq!(*interpreter.get_register(&Register::StackPointer), top);
+}
+
+#[test]
+fn conditional_calls() {
+    let source = "
+    cmp 1 2
+    call G one
+    calln G two
+    halt
+one:
+    add r0 r0 1 U
+    ret
+two:
+    add r0 r0 2 U
+    ret
+";
+    let (mut interpreter, trap) = run(common::object(source));
+    assert!(trap.is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 2);
+}
+
+#[test]
+fn blocks_lower_to_subroutines() {
+    let mut assembler = Assembler::new();
+    let square = assembler.add_block(assembler.block(assembler.init()).unwrap().stackframe());
+    for line in ["mul r0 r1 r1 U", "ret"] {
+        assembler.add_ir_to(square, line.to_string().try_into().unwrap());
+    }
+    assembler.add_ir("load r1 7".to_string().try_into().unwrap());
+    assembler.add_ir(Ir::Call(Flag::Always, square));
+    assembler.add_ir("halt".to_string().try_into().unwrap());
+    let (data, code, entry) = assembler.compile(0).unwrap();
+    assert_eq!(
+        code[1],
+        Instruction::Call(Flag::Always, Value::Direct(Operand::U32(12)))
+    );
+
+    let mut interpreter = Interpreter::new();
+    interpreter.load(data, code, entry).unwrap();
+    assert!(interpreter.execute().is_halt());
+    assert_eq!(*interpreter.get_register(&Register::R0), 49);
+}
+
+#[test]
+fn calls_disassemble_to_labels() {
+    let object = common::object(SQUARE);
+    let text = disassemble(&object);
+    assert!(text.contains("call A square"), "{}", text);
+    assert_eq!(common::object(&text).code, object.code);
+}
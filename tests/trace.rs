@@ -0,0 +1,117 @@
+mod common;
+
+use plac::codegen::asm::*;
+use plac::codegen::interpreter::*;
+use plac::codegen::trace::*;
+use std::sync::{Arc, Mutex};
+
+fn traced(interpreter: &mut Interpreter) -> Arc<Mutex<Trace<Vec<u8>>>> {
+    let trace = Arc::new(Mutex::new(Trace::new(Vec::new())));
+    interpreter.set_tracer(Some(trace.clone()));
+    trace
+}
+
+fn text(trace: &Arc<Mutex<Trace<Vec<u8>>>>) -> String {
+    String::from_utf8(trace.lock().unwrap().writer().clone()).unwrap()
+}
+
+const PROGRAM: &str = "
+value: .zero 4
+double:
+    add r0 r0 r0 U
+    ret
+main:
+    load r0 3
+    call A double
+    store32 value r0
+    div r1 r0 0 U
+    halt
+.entry main
+";
+
+#[test]
+fn every_tick_is_a_line() {
+    let mut interpreter = common::assemble(PROGRAM);
+    let sp = interpreter.stack.end;
+    let trace = traced(&mut interpreter);
+    let trap = interpreter.execute();
+    assert_eq!(trap.kind, TrapKind::DivisionByZero);
+    assert_eq!(
+        text(&trace),
+        format!(
+            "      12  load r0 3 r0=3\n      \
+             16  call A 4 PC=4 SP={}\n       \
+             4  add r0 r0 r0 U r0=6\n       \
+             8  ret PC=20 SP={}\n      \
+             20  store32 0 r0\n      \
+             24  div r1 r0 0 U ! DivisionByZero\n",
+            sp - 4,
+            sp
+        )
+    );
+    assert!(trace.lock().unwrap().flush().is_ok());
+}
+
+#[test]
+fn tracing_does_not_change_execution() {
+    let mut reference = common::assemble(PROGRAM);
+    let expected = reference.execute();
+    let mut interpreter = common::assemble(PROGRAM);
+    let trace = traced(&mut interpreter);
+    assert_eq!(interpreter.execute(), expected);
+    assert_eq!(interpreter.registers, reference.registers);
+    assert_eq!(interpreter.memory, reference.memory);
+    // clones trace into the same tracer
+    let mut clone = interpreter.clone();
+    assert_eq!(clone, interpreter);
+    clone.set_register(Register::ProgramCounter, 1000);
+    assert!(clone.tick().is_err());
+    assert!(text(&trace).ends_with("    1000  ? ! InvalidInstruction\n"));
+
+    interpreter.set_tracer(None);
+    interpreter.set_register(Register::ProgramCounter, 12);
+    interpreter.tick().unwrap();
+    assert_eq!(text(&trace).lines().count(), 7);
+}
+
+#[test]
+fn limits_show_in_the_trace() {
+    let mut interpreter = common::assemble(PROGRAM);
+    interpreter.set_limits(Limits {
+        instructions: Some(1),
+        ..Limits::default()
+    });
+    let trace = traced(&mut interpreter);
+    let trap = interpreter.execute();
+    assert!(matches!(trap.kind, TrapKind::Exhausted(..)));
+    let lines: Vec<String> = text(&trace).lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("      16  call A 4 ! Exhausted(Instructions"));
+}
+
+/// fails every write
+struct Broken;
+
+impl std::io::Write for Broken {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_errors_stop_the_trace() {
+    let mut interpreter = common::assemble(PROGRAM);
+    let trace = Arc::new(Mutex::new(Trace::new(Broken)));
+    interpreter.set_tracer(Some(trace.clone()));
+    assert_eq!(interpreter.execute().kind, TrapKind::DivisionByZero);
+    let mut trace = trace.lock().unwrap();
+    assert_eq!(
+        trace.flush().unwrap_err().kind(),
+        std::io::ErrorKind::BrokenPipe
+    );
+    assert!(trace.flush().is_ok());
+}